@@ -1,8 +1,10 @@
 use crate::{
-    formula, game::Action, monster::Monster, player::PlayerInfo, point::Point,
-    ranged_int::InclusiveRange, rect::Rectangle, world::World,
+    distance_map::DistanceMaps, formula, game::Action, monster::Monster, player::PlayerInfo,
+    point::Point, ranged_int::InclusiveRange, rect::Rectangle, world::World,
 };
 
+use std::fmt::{Display, Error, Formatter};
+
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
@@ -22,6 +24,19 @@ pub enum AIState {
     NoOp,
 }
 
+impl Display for AIState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        use self::AIState::*;
+        let s = match *self {
+            Idle => "Idle",
+            Chasing => "Chasing you",
+            CheckingOut(_) => "Checking something out",
+            NoOp => "Not moving",
+        };
+        f.write_str(s)
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 /// Values the AI can update about itself before performing the action
 /// it decided to make.
@@ -34,6 +49,7 @@ pub fn lone_attacker_act<R: Rng>(
     actor: &Monster,
     player_info: PlayerInfo,
     world: &mut World,
+    distance_maps: &DistanceMaps,
     rng: &mut R,
 ) -> (Update, Action) {
     if actor.ai_state == AIState::NoOp {
@@ -52,7 +68,7 @@ pub fn lone_attacker_act<R: Rng>(
     };
 
     let action = match ai_state {
-        AIState::Chasing => chasing_action(actor, player_info.pos),
+        AIState::Chasing => chasing_action(actor, world, distance_maps, player_info.pos),
         AIState::Idle => {
             let destination = idle_destination(actor, world, rng, player_info.pos);
             Action::Move(destination)
@@ -67,6 +83,7 @@ pub fn pack_attacker_act<R: Rng>(
     actor: &Monster,
     player_info: PlayerInfo,
     world: &mut World,
+    distance_maps: &DistanceMaps,
     rng: &mut R,
 ) -> (Update, Action) {
     if actor.ai_state == AIState::NoOp {
@@ -102,7 +119,7 @@ pub fn pack_attacker_act<R: Rng>(
                 }
             }
 
-            chasing_action(actor, player_info.pos)
+            chasing_action(actor, world, distance_maps, player_info.pos)
         }
 
         AIState::Idle => {
@@ -119,6 +136,7 @@ pub fn friendly_act<R: Rng>(
     actor: &Monster,
     player_info: PlayerInfo,
     world: &mut World,
+    _distance_maps: &DistanceMaps,
     rng: &mut R,
 ) -> (Update, Action) {
     if actor.ai_state == AIState::NoOp {
@@ -165,6 +183,7 @@ pub fn noop_act<R: Rng>(
     actor: &Monster,
     _player_info: PlayerInfo,
     _world: &mut World,
+    _distance_maps: &DistanceMaps,
     _rng: &mut R,
 ) -> (Update, Action) {
     noop_action(actor)
@@ -210,10 +229,30 @@ fn idle_destination<R: Rng>(
     }
 }
 
-fn chasing_action(actor: &Monster, target_position: Point) -> Action {
+fn chasing_action(
+    actor: &Monster,
+    world: &World,
+    distance_maps: &DistanceMaps,
+    target_position: Point,
+) -> Action {
     if actor.position.tile_distance(target_position) == 1 {
-        Action::Attack(target_position, actor.attack_damage())
-    } else {
-        Action::Move(target_position)
+        let attack = world.monster_definition(&actor.id).attack;
+        return Action::Attack(target_position, attack);
+    }
+    let map = distance_maps
+        .player(actor.blockers)
+        .filter(|map| map.distance(actor.position).is_some());
+    match map {
+        Some(map) => {
+            // NOTE: when the other monsters are in the way, wait for
+            // them to move on.
+            let step = map.downhill(actor.position, |pos| {
+                world.walkable(pos, actor.blockers, target_position)
+            });
+            Action::Move(step.unwrap_or(actor.position))
+        }
+        // NOTE: there's no way to the player. Let the path finding
+        // get as close as it can.
+        None => Action::Move(target_position),
     }
 }
@@ -1,241 +1,214 @@
 use crate::{
     color::Color,
-    point::{Point, SquareArea},
+    point::{self, CircularArea, Point, SquareArea},
     timer::Timer,
 };
 
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::{cmp, f32, time::Duration};
 
 pub trait AreaOfEffect {
     fn update(&mut self, dt: Duration);
     fn finished(&self) -> bool;
     fn tiles(&self) -> Box<dyn Iterator<Item = (Point, Color, TileEffect)>>;
+    /// Every tile the animation covers from its start to its end.
+    /// The tiles can repeat.
+    fn all_tiles(&self) -> Box<dyn Iterator<Item = (Point, TileEffect)>>;
 }
 
 bitflags::bitflags! {
     pub struct TileEffect: u32 {
-        const KILL    = 0b0000_0001;
-        const SHATTER = 0b0000_0010;
+        /// Kills the monster.
+        const KILL       = 0b0000_0001;
+        /// Destroys the trees and the items.
+        const SHATTER    = 0b0000_0010;
+        /// Explores the tile.
+        const REVEAL     = 0b0000_0100;
+        /// Stuns the monster for a few turns.
+        const STUN       = 0b0000_1000;
+        /// Grows a tree on an empty tile.
+        const PLANT_TREE = 0b0001_0000;
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct SquareExplosion {
-    pub center: Point,
-    pub max_radius: i32,
-    pub initial_radius: i32,
-    pub current_radius: i32,
-    pub color: Color,
-    pub wave_count: i32,
-    pub timer: Timer,
-}
-
-impl SquareExplosion {
-    pub fn new(center: Point, max_radius: i32, initial_radius: i32, color: Color) -> Self {
-        assert!(initial_radius <= max_radius);
-        // Count the initial wave plus the rest that makes the difference
-        let wave_count = max_radius - initial_radius + 1;
-        let wave_duration = Duration::from_millis(100);
-        SquareExplosion {
-            center,
-            max_radius,
-            initial_radius,
-            current_radius: initial_radius,
-            color,
-            wave_count,
-            timer: Timer::new(wave_duration.checked_mul(wave_count as u32).unwrap()),
-        }
+/// Serialise the `TileEffect` as the list of the flag names, e.g.
+/// `["KILL", "SHATTER"]`.
+mod names {
+    use super::TileEffect;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    const FLAGS: [(&str, TileEffect); 5] = [
+        ("KILL", TileEffect::KILL),
+        ("SHATTER", TileEffect::SHATTER),
+        ("REVEAL", TileEffect::REVEAL),
+        ("STUN", TileEffect::STUN),
+        ("PLANT_TREE", TileEffect::PLANT_TREE),
+    ];
+
+    pub fn serialize<S: Serializer>(effect: &TileEffect, serializer: S) -> Result<S::Ok, S::Error> {
+        FLAGS
+            .iter()
+            .filter(|(_, flag)| effect.contains(*flag))
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .serialize(serializer)
     }
-}
 
-impl AreaOfEffect for SquareExplosion {
-    fn update(&mut self, dt: Duration) {
-        if self.timer.finished() {
-            // do nothing
-        } else {
-            self.timer.update(dt);
-            let single_wave_percentage = 1.0 / (self.wave_count as f32);
-            self.current_radius = self.initial_radius
-                + (self.timer.percentage_elapsed() / single_wave_percentage) as i32;
-            if self.current_radius > self.max_radius {
-                self.current_radius = self.max_radius;
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<TileEffect, D::Error> {
+        let mut result = TileEffect::empty();
+        for name in Vec::<String>::deserialize(deserializer)? {
+            match FLAGS.iter().find(|(flag_name, _)| *flag_name == name) {
+                Some((_, flag)) => result |= *flag,
+                None => return Err(D::Error::custom(format!("unknown tile effect `{}`", name))),
             }
         }
+        Ok(result)
     }
-
-    fn finished(&self) -> bool {
-        self.timer.finished()
-    }
-
-    fn tiles(&self) -> Box<dyn Iterator<Item = (Point, Color, TileEffect)>> {
-        let color = self.color;
-        Box::new(
-            SquareArea::new(self.center, self.current_radius + 1)
-                .map(move |pos| (pos, color, TileEffect::KILL)),
-        )
-    }
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CardinalExplosion {
-    center: Point,
-    max_radius: i32,
-    initial_radius: i32,
-    current_radius: i32,
-    kill_color: Color,
-    shatter_color: Color,
-    wave_count: i32,
-    timer: Timer,
 }
 
-impl CardinalExplosion {
-    pub fn new(
-        center: Point,
-        max_radius: i32,
-        initial_radius: i32,
-        kill_color: Color,
-        shatter_color: Color,
-    ) -> Self {
-        assert!(initial_radius <= max_radius);
-        // Count the initial wave plus the rest that makes the difference
-        let wave_count = max_radius - initial_radius + 1;
-        let wave_duration = Duration::from_millis(100);
-        CardinalExplosion {
-            center,
-            max_radius,
-            initial_radius,
-            current_radius: initial_radius,
-            kill_color,
-            shatter_color,
-            wave_count,
-            timer: Timer::new(wave_duration.checked_mul(wave_count as u32).unwrap()),
-        }
-    }
+/// The shape of a single `Layer` of an `Explosion`.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Shape {
+    /// All tiles within the radius, diagonals included.
+    Square,
+    /// All tiles closer to the centre than the radius.
+    Circle,
+    /// The edge of the `Circle`, `width` tiles thick.
+    Ring { width: i32 },
+    /// The horizontal and vertical lines through the centre.
+    Cross,
+    /// The two diagonal lines through the centre.
+    Diagonal,
+    /// The quarter of the `Circle` facing the `direction`.
+    Cone { direction: Point },
+    /// The line from the centre in the `direction`, e.g. `(1, -1)`
+    /// for north-east.
+    Line { direction: Point },
+    /// The `arms` spiralling out of the centre.
+    Spiral { arms: i32 },
 }
 
-impl AreaOfEffect for CardinalExplosion {
-    fn update(&mut self, dt: Duration) {
-        if self.timer.finished() {
-            // do nothing
-        } else {
-            self.timer.update(dt);
-            let single_wave_percentage = 1.0 / (self.wave_count as f32);
-            self.current_radius = self.initial_radius
-                + (self.timer.percentage_elapsed() / single_wave_percentage) as i32;
-            if self.current_radius > self.max_radius {
-                self.current_radius = self.max_radius;
+impl Shape {
+    /// The tiles of the shape centred on `center`. The tiles can
+    /// repeat.
+    pub fn points(self, center: Point, radius: i32) -> Vec<Point> {
+        use self::Shape::*;
+        let radius = cmp::max(radius, 0);
+        match self {
+            Square => SquareArea::new(center, radius + 1).collect(),
+            Circle => CircularArea::new(center, radius).collect(),
+            Ring { width } => CircularArea::new(center, radius)
+                .filter(|&pos| center.distance(pos) >= (radius - width) as f32)
+                .collect(),
+            Cross => {
+                let horizontal = (-radius..=radius).map(|x| center + (x, 0));
+                let vertical = (-radius..=radius).map(|y| center + (0, y));
+                horizontal.chain(vertical).collect()
+            }
+            Diagonal => {
+                let rising = (-radius..=radius).map(|x| center + (x, -x));
+                let falling = (-radius..=radius).map(|x| center + (x, x));
+                rising.chain(falling).collect()
+            }
+            Cone { direction } => {
+                let direction_length = Point::zero().distance(direction);
+                CircularArea::new(center, radius)
+                    .filter(|&pos| {
+                        let offset = pos - center;
+                        let dot = offset.x * direction.x + offset.y * direction.y;
+                        // NOTE: within 45 degrees on either side
+                        let length = Point::zero().distance(offset) * direction_length;
+                        dot as f32 >= length * f32::consts::FRAC_1_SQRT_2
+                    })
+                    .collect()
+            }
+            Line { direction } => point::Line::new(center, center + direction * radius).collect(),
+            Spiral { arms } => {
+                let arms = cmp::max(arms, 1);
+                let steps_per_tile = 4;
+                let mut result = vec![];
+                for arm in 0..arms {
+                    let arm_angle = arm as f32 * 2.0 * f32::consts::PI / arms as f32;
+                    for step in 0..=radius * steps_per_tile {
+                        let distance = step as f32 / steps_per_tile as f32;
+                        let angle = arm_angle + distance;
+                        let offset = (
+                            (distance * angle.cos()).round() as i32,
+                            (distance * angle.sin()).round() as i32,
+                        );
+                        result.push(center + offset);
+                    }
+                }
+                result
             }
         }
     }
-
-    fn finished(&self) -> bool {
-        self.timer.finished()
-    }
-
-    fn tiles(&self) -> Box<dyn Iterator<Item = (Point, Color, TileEffect)>> {
-        let kill_color = self.kill_color;
-        let killzone_area =
-            SquareArea::new(self.center, 3).map(move |pos| (pos, kill_color, TileEffect::KILL));
-
-        let shatter_color = self.shatter_color;
-        let shatter_area = CrossIterator::new(self.center, self.current_radius)
-            .map(move |pos| (pos, shatter_color, TileEffect::KILL | TileEffect::SHATTER));
-        Box::new(killzone_area.chain(shatter_area))
-    }
 }
 
-#[derive(Debug)]
-pub struct CrossIterator {
-    center: Point,
-    range: i32,
-    x_offset: i32,
-    y_offset: i32,
-    horizontal: bool,
-    vertical: bool,
+/// One part of an `Explosion`: the tiles of the `shape` get the
+/// `color` and the `effect`.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Layer {
+    pub shape: Shape,
+    /// The layers without a radius grow with the explosion waves.
+    #[serde(default)]
+    pub radius: Option<i32>,
+    pub color: Color,
+    #[serde(with = "names")]
+    pub effect: TileEffect,
 }
 
-impl CrossIterator {
-    pub fn new(center: Point, range: i32) -> Self {
-        assert!(range >= 0);
-        CrossIterator {
-            center,
-            range,
-            x_offset: -range,
-            y_offset: -range,
-            horizontal: true,
-            vertical: false,
+#[cfg(test)]
+impl Layer {
+    /// The layer that grows with the explosion waves.
+    pub fn wave(shape: Shape, color: Color, effect: TileEffect) -> Self {
+        Layer {
+            shape,
+            radius: None,
+            color,
+            effect,
         }
     }
-}
-
-impl Iterator for CrossIterator {
-    type Item = Point;
-
-    fn next(&mut self) -> Option<Point> {
-        if self.horizontal {
-            let x_offset = self.x_offset;
-            if x_offset <= self.range {
-                self.x_offset += 1;
-                return Some(self.center + (x_offset, 0));
-            } else {
-                self.horizontal = false;
-                self.vertical = true;
-            }
-        }
 
-        if self.vertical {
-            let y_offset = self.y_offset;
-            if y_offset <= self.range {
-                self.y_offset += 1;
-                return Some(self.center + (0, y_offset));
-            } else {
-                self.vertical = false;
-            }
+    /// The layer that keeps the same `radius` in every wave.
+    pub fn fixed(shape: Shape, radius: i32, color: Color, effect: TileEffect) -> Self {
+        Layer {
+            shape,
+            radius: Some(radius),
+            color,
+            effect,
         }
-
-        None
     }
 }
 
-#[derive(Debug)]
-pub struct DiagonalExplosion {
+/// An explosion growing from its initial radius to the max one in
+/// waves. Its tiles are the tiles of all its layers.
+///
+/// Use `Explosion::builder` to create it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Explosion {
     center: Point,
     max_radius: i32,
     initial_radius: i32,
     current_radius: i32,
-    kill_color: Color,
-    shatter_color: Color,
     wave_count: i32,
     timer: Timer,
+    layers: Vec<Layer>,
 }
 
-impl DiagonalExplosion {
-    pub fn new(
-        center: Point,
-        max_radius: i32,
-        initial_radius: i32,
-        kill_color: Color,
-        shatter_color: Color,
-    ) -> Self {
-        assert!(initial_radius <= max_radius);
-        // Count the initial wave plus the rest that makes the difference
-        let wave_count = max_radius - initial_radius + 1;
-        let wave_duration = Duration::from_millis(100);
-        DiagonalExplosion {
+impl Explosion {
+    pub fn builder(center: Point) -> ExplosionBuilder {
+        ExplosionBuilder {
             center,
-            max_radius,
-            initial_radius,
-            current_radius: initial_radius,
-            kill_color,
-            shatter_color,
-            wave_count,
-            timer: Timer::new(wave_duration.checked_mul(wave_count as u32).unwrap()),
+            initial_radius: 0,
+            max_radius: 0,
+            layers: vec![],
         }
     }
 }
 
-impl AreaOfEffect for DiagonalExplosion {
+impl AreaOfEffect for Explosion {
     fn update(&mut self, dt: Duration) {
         if self.timer.finished() {
             // do nothing
@@ -255,70 +228,73 @@ impl AreaOfEffect for DiagonalExplosion {
     }
 
     fn tiles(&self) -> Box<dyn Iterator<Item = (Point, Color, TileEffect)>> {
-        let kill_color = self.kill_color;
-        let killzone_area =
-            SquareArea::new(self.center, 3).map(move |pos| (pos, kill_color, TileEffect::KILL));
-
-        let shatter_color = self.shatter_color;
-        let shatter_area = XIterator::new(self.center, self.current_radius)
-            .map(move |pos| (pos, shatter_color, TileEffect::KILL | TileEffect::SHATTER));
-        Box::new(killzone_area.chain(shatter_area))
+        let mut result = vec![];
+        for layer in &self.layers {
+            let radius = layer.radius.unwrap_or(self.current_radius);
+            result.extend(
+                layer
+                    .shape
+                    .points(self.center, radius)
+                    .into_iter()
+                    .map(|pos| (pos, layer.color, layer.effect)),
+            );
+        }
+        Box::new(result.into_iter())
+    }
+
+    fn all_tiles(&self) -> Box<dyn Iterator<Item = (Point, TileEffect)>> {
+        let mut result = vec![];
+        for wave_radius in self.initial_radius..=self.max_radius {
+            for layer in &self.layers {
+                let radius = layer.radius.unwrap_or(wave_radius);
+                result.extend(
+                    layer
+                        .shape
+                        .points(self.center, radius)
+                        .into_iter()
+                        .map(|pos| (pos, layer.effect)),
+                );
+            }
+        }
+        Box::new(result.into_iter())
     }
 }
 
-#[derive(Debug)]
-pub struct XIterator {
+pub struct ExplosionBuilder {
     center: Point,
-    range: i32,
-    x_offset: i32,
-    y_offset: i32,
-    horizontal: bool,
-    vertical: bool,
+    initial_radius: i32,
+    max_radius: i32,
+    layers: Vec<Layer>,
 }
 
-impl XIterator {
-    pub fn new(center: Point, range: i32) -> Self {
-        assert!(range >= 0);
-        XIterator {
-            center,
-            range,
-            x_offset: -range,
-            y_offset: -range,
-            horizontal: true,
-            vertical: false,
-        }
+impl ExplosionBuilder {
+    /// Grow the explosion from the `initial` radius to the `max` one.
+    pub fn radius(mut self, initial: i32, max: i32) -> Self {
+        assert!(initial <= max);
+        self.initial_radius = initial;
+        self.max_radius = max;
+        self
     }
-}
 
-impl Iterator for XIterator {
-    type Item = Point;
-
-    fn next(&mut self) -> Option<Point> {
-        // TODO: simplify the code. This is basically just copied from
-        // CrossIterator and the names could be better plus I don't
-        // think we need all the fields.
-        if self.horizontal {
-            let x_offset = self.x_offset;
-            if x_offset <= self.range {
-                self.x_offset += 1;
-                return Some(self.center + (x_offset, -x_offset));
-            } else {
-                self.horizontal = false;
-                self.vertical = true;
-            }
-        }
+    /// Add the `layer` on top of the previous ones.
+    pub fn layer(mut self, layer: Layer) -> Self {
+        self.layers.push(layer);
+        self
+    }
 
-        if self.vertical {
-            let y_offset = self.y_offset;
-            if y_offset <= self.range {
-                self.y_offset += 1;
-                return Some(self.center + (y_offset, y_offset));
-            } else {
-                self.vertical = false;
-            }
+    pub fn build(self) -> Explosion {
+        // Count the initial wave plus the rest that makes the difference
+        let wave_count = self.max_radius - self.initial_radius + 1;
+        let wave_duration = Duration::from_millis(100);
+        Explosion {
+            center: self.center,
+            max_radius: self.max_radius,
+            initial_radius: self.initial_radius,
+            current_radius: self.initial_radius,
+            wave_count,
+            timer: Timer::new(wave_duration.checked_mul(wave_count as u32).unwrap()),
+            layers: self.layers,
         }
-
-        None
     }
 }
 
@@ -380,3 +356,83 @@ impl ScreenFade {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{AreaOfEffect, Explosion, Layer, Shape, TileEffect};
+    use crate::{color, point::Point};
+
+    use std::time::Duration;
+
+    #[test]
+    fn test_explosion_layers() {
+        let center = Point::new(10, 10);
+        let mut explosion = Explosion::builder(center)
+            .radius(1, 3)
+            .layer(Layer::fixed(
+                Shape::Square,
+                1,
+                color::BRIGHT_BLUE,
+                TileEffect::KILL,
+            ))
+            .layer(Layer::wave(
+                Shape::Cross,
+                color::RED,
+                TileEffect::SHATTER | TileEffect::PLANT_TREE,
+            ))
+            .build();
+
+        let tiles = |explosion: &Explosion, effect| {
+            explosion
+                .tiles()
+                .filter(|&(_, _, e)| e == effect)
+                .map(|(pos, _, _)| pos)
+                .collect::<Vec<_>>()
+        };
+        let kill = TileEffect::KILL;
+        let shatter = TileEffect::SHATTER | TileEffect::PLANT_TREE;
+        assert_eq!(tiles(&explosion, kill).len(), 9);
+        assert_eq!(tiles(&explosion, shatter).len(), 6);
+
+        explosion.update(Duration::from_millis(250));
+        assert!(!explosion.finished());
+        assert_eq!(tiles(&explosion, kill).len(), 9);
+        assert!(tiles(&explosion, shatter).contains(&Point::new(10, 13)));
+        assert!(!tiles(&explosion, shatter).contains(&Point::new(11, 11)));
+
+        explosion.update(Duration::from_millis(100));
+        assert!(explosion.finished());
+    }
+
+    #[test]
+    fn test_shapes() {
+        let center = Point::zero();
+        assert_eq!(
+            Shape::Diagonal.points(center, 1),
+            vec![
+                Point::new(-1, 1),
+                Point::new(0, 0),
+                Point::new(1, -1),
+                Point::new(-1, -1),
+                Point::new(0, 0),
+                Point::new(1, 1),
+            ]
+        );
+        let direction = Point::new(1, 0);
+        assert_eq!(
+            Shape::Line { direction }.points(center, 2),
+            vec![Point::new(0, 0), Point::new(1, 0), Point::new(2, 0)]
+        );
+        let cone = Shape::Cone { direction }.points(center, 3);
+        assert!(cone.contains(&Point::new(2, 1)));
+        assert!(!cone.contains(&Point::new(-1, 0)));
+        assert!(!cone.contains(&Point::new(1, 2)));
+        let ring = Shape::Ring { width: 1 }.points(center, 3);
+        assert!(ring.contains(&Point::new(2, 0)));
+        assert!(!ring.contains(&Point::new(1, 0)));
+        assert!(Shape::Spiral { arms: 2 }
+            .points(center, 3)
+            .iter()
+            .all(|pos| center.tile_distance(*pos) <= 3));
+    }
+}
@@ -7,5 +7,43 @@ bitflags::bitflags! {
         const WALL    = 0b0000_0001;
         const MONSTER = 0b0000_0010;
         const PLAYER  = 0b0000_0100;
+        /// The doors. For the monsters that can't open them.
+        const DOOR    = 0b0000_1000;
+    }
+}
+
+/// (De)serialise the `Blocker` as a list of flag names, e.g.
+/// `["WALL", "MONSTER"]`. Use it with `#[serde(with = "...")]` in
+/// the files people edit by hand.
+pub mod names {
+    use super::Blocker;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    const FLAGS: [(&str, Blocker); 4] = [
+        ("WALL", Blocker::WALL),
+        ("MONSTER", Blocker::MONSTER),
+        ("PLAYER", Blocker::PLAYER),
+        ("DOOR", Blocker::DOOR),
+    ];
+
+    pub fn serialize<S: Serializer>(blockers: &Blocker, serializer: S) -> Result<S::Ok, S::Error> {
+        FLAGS
+            .iter()
+            .filter(|(_, flag)| blockers.contains(*flag))
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Blocker, D::Error> {
+        let mut result = Blocker::empty();
+        for name in Vec::<String>::deserialize(deserializer)? {
+            match FLAGS.iter().find(|(flag_name, _)| *flag_name == name) {
+                Some((_, flag)) => result |= *flag,
+                None => return Err(D::Error::custom(format!("unknown blocker `{}`", name))),
+            }
+        }
+        Ok(result)
     }
 }
@@ -0,0 +1,105 @@
+//! The friendly NPCs accompanying the player.
+//!
+//! The player can have up to `formula::MAX_COMPANIONS` companions at
+//! once. Each one gives its `CompanionBonus` while the player is
+//! sober. A companion waits while the player is high and leaves for
+//! good once the player gets high too many times.
+
+use crate::{formula, monster::CompanionBonus, world::MonsterId, world::World};
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// How long are the companions' lines shown.
+pub const LINE_DURATION: Duration = Duration::from_millis(3000);
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Companion {
+    pub id: MonsterId,
+    pub bonus: CompanionBonus,
+    /// How many more times can the player get high before the
+    /// companion leaves them.
+    pub patience: i32,
+}
+
+impl Companion {
+    pub fn new(id: MonsterId, bonus: CompanionBonus) -> Self {
+        Companion {
+            id,
+            bonus,
+            patience: formula::COMPANION_PATIENCE,
+        }
+    }
+}
+
+/// Rebuild the companions from the NPCs accompanying the player.
+/// Used for the saves from the 1.0.0 release.
+pub fn from_world(world: &World) -> Vec<Companion> {
+    world
+        .all_monsters()
+        .filter(|m| m.accompanying_player)
+        .filter_map(|m| {
+            let id = world.monster_id(m.position)?;
+            let bonus = m.companion_bonus?;
+            Some(Companion::new(id, bonus))
+        })
+        .collect()
+}
+
+/// What the NPC says when they join the player.
+pub fn joined_line(bonus: CompanionBonus) -> Option<&'static str> {
+    use crate::monster::CompanionBonus::*;
+    match bonus {
+        DoubleWillGrowth => Some("\"You don't have to face them alone. Let's go.\""),
+        HalveExhaustion => Some("\"Here, have a bite. We'll take it slow.\""),
+        ExtraActionPoint => Some("\"Come on, keep up!\""),
+        // NOTE: reaching the Victory NPC wins the game
+        Victory => None,
+    }
+}
+
+/// What the companion says when the player bumps into them again.
+pub const GREETING_LINE: &str = "\"I'm right here with you.\"";
+
+/// What the companion says when the player gets high and they're
+/// willing to wait.
+pub const WAITING_LINE: &str =
+    "\"I'll wait until you're yourself again. Please, don't do this again.\"";
+
+/// What the companion says when they leave for good.
+pub const GAVE_UP_LINE: &str = "\"I can't watch you do this to yourself anymore. Goodbye.\"";
+
+/// What an NPC who left the player says when bumped into.
+pub const REFUSAL_LINE: &str = "\"Leave me alone.\"";
+
+#[cfg(test)]
+mod test {
+    use crate::{formula, monster::CompanionBonus::*};
+
+    use num_rational::Ratio;
+
+    #[test]
+    fn test_bonus_stacking() {
+        assert_eq!(formula::anxiety_increment(&[]), 1);
+        assert_eq!(formula::anxiety_increment(&[DoubleWillGrowth]), 2);
+        assert_eq!(
+            formula::anxiety_increment(&[DoubleWillGrowth, DoubleWillGrowth, DoubleWillGrowth]),
+            3
+        );
+
+        assert_eq!(
+            formula::mind_drop_per_turn(&[HalveExhaustion, ExtraActionPoint]),
+            Ratio::new(formula::MIND_DROP_PER_TURN, 2)
+        );
+        assert_eq!(
+            formula::mind_drop_per_turn(&[HalveExhaustion, HalveExhaustion]),
+            Ratio::new(formula::MIND_DROP_PER_TURN, 3)
+        );
+
+        assert_eq!(
+            formula::player_max_ap(&[ExtraActionPoint, ExtraActionPoint]),
+            formula::PLAYER_BASE_AP + 1
+        );
+    }
+}
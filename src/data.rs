@@ -0,0 +1,52 @@
+//! Game data that can be changed without recompiling the game.
+//!
+//! The default data files live in the `data` directory and are built
+//! into the binary. A file with the same name in the per-user config
+//! directory (e.g. `~/.config/dose-response/monsters.json` on Linux)
+//! replaces the built-in one.
+
+use std::{fs, io, path::PathBuf};
+
+use serde::de::DeserializeOwned;
+
+/// The path of the user's data file with the given name.
+pub fn path(file_name: &str) -> PathBuf {
+    match dirs::config_dir() {
+        Some(dir) => dir.join("dose-response").join(file_name),
+        None => PathBuf::from(file_name),
+    }
+}
+
+/// Parse the built-in data file.
+pub fn built_in<T: DeserializeOwned>(file_name: &str, contents: &str) -> T {
+    serde_json::from_str(contents)
+        .unwrap_or_else(|error| panic!("The built-in {} is invalid: {}", file_name, error))
+}
+
+/// Load the user's data file or the built-in one if the user doesn't
+/// have it or it's invalid.
+pub fn load<T: DeserializeOwned>(file_name: &str, built_in_contents: &str) -> T {
+    let path = path(file_name);
+    match fs::read_to_string(&path) {
+        Ok(contents) => match serde_json::from_str(&contents) {
+            Ok(data) => {
+                log::info!("Loaded {}", path.display());
+                return data;
+            }
+            Err(error) => log::error!(
+                "Could not parse {}: {}. Using the built-in {}.",
+                path.display(),
+                error,
+                file_name
+            ),
+        },
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => {}
+        Err(error) => log::error!(
+            "Could not read {}: {}. Using the built-in {}.",
+            path.display(),
+            error,
+            file_name
+        ),
+    }
+    built_in(file_name, built_in_contents)
+}
@@ -0,0 +1,343 @@
+//! Distance maps (also known as Dijkstra maps) of the simulation area.
+//!
+//! Every tile of a map holds the cost of getting from there to the
+//! nearest goal. Anyone who wants to get to the goal just steps onto
+//! the neighbouring tile with the lowest value so a single map serves
+//! all the monsters chasing the player instead of each of them
+//! running its own path search.
+
+use crate::{
+    ai::Behavior, blocker::Blocker, formula, pathfinding::NEIGHBOURS, point::Point,
+    rect::Rectangle, world::World,
+};
+
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+/// The cost of getting from every walkable tile of the `area` to the
+/// nearest goal.
+#[derive(Debug)]
+pub struct DistanceMap {
+    area: Rectangle,
+    distances: Vec<Option<i32>>,
+}
+
+impl DistanceMap {
+    /// Build the map from the `goals` and their starting values.
+    ///
+    /// A goal with a negative value pulls in from further away than
+    /// one with zero.
+    fn new(terrain: &Terrain, walkable: &[bool], goals: &[(Point, i32)]) -> Self {
+        let area = terrain.area;
+        let mut distances = vec![None; walkable.len()];
+        let mut frontier = BinaryHeap::new();
+        for &(pos, value) in goals {
+            if let Some(index) = index(area, pos) {
+                if walkable[index] && distances[index].map_or(true, |d| value < d) {
+                    distances[index] = Some(value);
+                    frontier.push((Reverse(value), pos.x, pos.y));
+                }
+            }
+        }
+
+        while let Some((Reverse(distance), x, y)) = frontier.pop() {
+            let current = Point::new(x, y);
+            let current_index = index(area, current).unwrap();
+            if distances[current_index] != Some(distance) {
+                // NOTE: we've since found a shorter way here
+                continue;
+            }
+            // NOTE: the map is walked towards the goals so stepping
+            // from `next` to `current` costs what entering `current`
+            // does.
+            let new_distance = distance + terrain.costs[current_index];
+            for &offset in NEIGHBOURS.iter() {
+                let next = current + offset;
+                if let Some(next_index) = index(area, next) {
+                    let shorter = distances[next_index].map_or(true, |d| new_distance < d);
+                    if walkable[next_index] && shorter {
+                        distances[next_index] = Some(new_distance);
+                        frontier.push((Reverse(new_distance), next.x, next.y));
+                    }
+                }
+            }
+        }
+
+        DistanceMap { area, distances }
+    }
+
+    /// The cost of getting from `pos` to the nearest goal. `None`
+    /// when there's no way there.
+    pub fn distance(&self, pos: Point) -> Option<i32> {
+        index(self.area, pos).and_then(|index| self.distances[index])
+    }
+
+    /// The neighbour of `pos` closest to the goal. It must be closer
+    /// than `pos` itself and `free` must allow stepping on it.
+    pub fn downhill<F>(&self, pos: Point, free: F) -> Option<Point>
+    where
+        F: Fn(Point) -> bool,
+    {
+        let mut best = (pos, self.distance(pos));
+        for &offset in NEIGHBOURS.iter() {
+            let next = pos + offset;
+            if let Some(distance) = self.distance(next) {
+                let closer = best.1.map_or(true, |best| distance < best);
+                if closer && free(next) {
+                    best = (next, Some(distance));
+                }
+            }
+        }
+        if best.0 == pos {
+            None
+        } else {
+            Some(best.0)
+        }
+    }
+}
+
+/// All the distance maps the monsters and the player use in a turn.
+///
+/// They're built once per turn and again only when the player moves,
+/// their Will changes or something changes in the world (e.g. a
+/// monster moves or a dose is dropped).
+#[derive(Debug)]
+pub struct DistanceMaps {
+    /// The turn, the world revision, the player position and Will the
+    /// maps were built for.
+    key: (i32, u32, Point, i32),
+    /// The way to the player for every set of blockers the monsters
+    /// in the area have.
+    player: Vec<(Blocker, DistanceMap)>,
+    /// The way to the dose pulling the player in the most. The tiles
+    /// within a dose's irresistible area are below zero.
+    doses: DistanceMap,
+    /// The way away from the monsters chasing the player.
+    flee: DistanceMap,
+}
+
+impl DistanceMaps {
+    pub fn new(world: &World, area: Rectangle, turn: i32, player_pos: Point, will: i32) -> Self {
+        let terrain = Terrain::new(world, area);
+        let player_walkable = terrain.walkable(world, Blocker::WALL);
+
+        let mut blockers = world
+            .monsters(area)
+            .map(|monster| terrain_blockers(monster.blockers))
+            .collect::<Vec<_>>();
+        blockers.sort_by_key(|blockers| blockers.bits());
+        blockers.dedup();
+        let player = blockers
+            .into_iter()
+            .map(|blockers| {
+                let walkable = terrain.walkable(world, blockers);
+                (
+                    blockers,
+                    DistanceMap::new(&terrain, &walkable, &[(player_pos, 0)]),
+                )
+            })
+            .collect();
+
+        let mut doses = vec![];
+        for pos in area.points() {
+            if let Some(cell) = world.cell(pos) {
+                for dose in cell.items.iter().filter(|item| item.is_dose()) {
+                    let radius = formula::player_resist_radius(dose.irresistible, will);
+                    if radius > 0 {
+                        doses.push((pos, -radius * formula::AP_FRACTIONS));
+                    }
+                }
+            }
+        }
+        let doses = DistanceMap::new(&terrain, &player_walkable, &doses);
+
+        let threats = world
+            .monsters(area)
+            .filter(|monster| {
+                monster.behavior == Behavior::LoneAttacker
+                    || monster.behavior == Behavior::PackAttacker
+            })
+            .map(|monster| (monster.position, 0))
+            .collect::<Vec<_>>();
+        let threats = DistanceMap::new(&terrain, &player_walkable, &threats);
+        // NOTE: every tile becomes a goal worth more the further it
+        // is from the monsters. Walking the result leads away from
+        // them but not into a dead end.
+        let safety = area
+            .points()
+            .filter_map(|pos| {
+                threats
+                    .distance(pos)
+                    .map(|distance| (pos, -distance * formula::FLEE_DISTANCE_PERCENT / 100))
+            })
+            .collect::<Vec<_>>();
+        let flee = DistanceMap::new(&terrain, &player_walkable, &safety);
+
+        DistanceMaps {
+            key: (turn, world.revision(), player_pos, will),
+            player,
+            doses,
+            flee,
+        }
+    }
+
+    /// Were the maps built for this turn, the world as it is now and
+    /// the player position and Will?
+    pub fn is_current(&self, world: &World, turn: i32, player_pos: Point, will: i32) -> bool {
+        self.key == (turn, world.revision(), player_pos, will)
+    }
+
+    /// The way to the player for a monster with the given blockers.
+    pub fn player(&self, blockers: Blocker) -> Option<&DistanceMap> {
+        let blockers = terrain_blockers(blockers);
+        self.player
+            .iter()
+            .find(|(map_blockers, _)| *map_blockers == blockers)
+            .map(|(_, map)| map)
+    }
+
+    pub fn doses(&self) -> &DistanceMap {
+        &self.doses
+    }
+
+    pub fn flee(&self) -> &DistanceMap {
+        &self.flee
+    }
+}
+
+/// The move costs of the tiles in the area.
+struct Terrain {
+    area: Rectangle,
+    costs: Vec<i32>,
+}
+
+impl Terrain {
+    fn new(world: &World, area: Rectangle) -> Self {
+        let costs = area.points().map(|pos| world.move_cost(pos)).collect();
+        Terrain { area, costs }
+    }
+
+    fn walkable(&self, world: &World, blockers: Blocker) -> Vec<bool> {
+        // NOTE: the `blockers` don't include the player so we don't
+        // need the player's position here.
+        self.area
+            .points()
+            .map(|pos| world.walkable(pos, blockers, Point::zero()))
+            .collect()
+    }
+}
+
+/// The maps ignore the monsters and the player because they move
+/// about. Only the walls and the doors matter.
+fn terrain_blockers(blockers: Blocker) -> Blocker {
+    blockers - Blocker::MONSTER - Blocker::PLAYER
+}
+
+fn index(area: Rectangle, pos: Point) -> Option<usize> {
+    if area.contains(pos) {
+        let offset = pos - area.top_left();
+        Some((offset.y * area.width() + offset.x) as usize)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DistanceMap, DistanceMaps, Terrain};
+    use crate::blocker::Blocker;
+    use crate::item;
+    use crate::level::TileKind;
+    use crate::monster::Monster;
+    use crate::player::{Mind, PlayerInfo};
+    use crate::point::Point;
+    use crate::rect::Rectangle;
+    use crate::world::World;
+
+    fn new_world() -> World {
+        let player_info = PlayerInfo {
+            pos: Point::new(0, 0),
+            mind: Mind::Sober(crate::ranged_int::Ranged::new_max(crate::formula::SOBER)),
+            max_ap: 1,
+            will: 3,
+        };
+        World::new(
+            &mut crate::random::from_seed(0),
+            0,
+            64,
+            32,
+            player_info,
+            crate::generators::Generator::Forrest,
+            crate::monster::built_in_definitions(),
+            crate::item::built_in_definitions(),
+        )
+    }
+
+    #[test]
+    fn test_walk_around_a_wall() {
+        let mut world = new_world();
+        // NOTE: a wall between the start and the goal with a gap at
+        // the bottom
+        let area = Rectangle::from_point_and_size(Point::new(1, 1), Point::new(7, 7));
+        for pos in area.points() {
+            let kind = if pos.x == 4 && pos.y < 7 {
+                TileKind::Tree
+            } else {
+                TileKind::Empty
+            };
+            if let Some(cell) = world.cell_mut(pos) {
+                cell.tile.kind = kind;
+            }
+        }
+
+        let terrain = Terrain::new(&world, area);
+        let walkable = terrain.walkable(&world, Blocker::WALL);
+        let map = DistanceMap::new(&terrain, &walkable, &[(Point::new(7, 1), 0)]);
+        assert_eq!(map.distance(Point::new(7, 1)), Some(0));
+        assert_eq!(map.distance(Point::new(4, 1)), None);
+        assert_eq!(map.distance(Point::new(0, 0)), None);
+
+        let mut pos = Point::new(1, 1);
+        let mut steps = vec![];
+        while let Some(next) = map.downhill(pos, |_| true) {
+            assert!(map.distance(next) < map.distance(pos));
+            steps.push(next);
+            pos = next;
+        }
+        assert_eq!(pos, Point::new(7, 1));
+        assert!(steps.contains(&Point::new(4, 7)));
+        assert_eq!(
+            map.downhill(Point::new(6, 2), |pos| pos != Point::new(7, 1)),
+            None
+        );
+    }
+    #[test]
+    fn test_maps_are_rebuilt_when_the_world_changes() {
+        let mut world = new_world();
+        let area = Rectangle::from_point_and_size(Point::new(-5, -5), Point::new(11, 11));
+        for pos in area.points() {
+            world.remove_monster(pos);
+            if let Some(cell) = world.cell_mut(pos) {
+                cell.tile.kind = TileKind::Empty;
+            }
+        }
+        let anxiety = Monster::new(world.monster_definition("Anxiety"), Point::new(3, 3));
+        world
+            .chunk_mut(anxiety.position)
+            .unwrap()
+            .add_monster(anxiety);
+
+        let (turn, player_pos, will) = (1, Point::new(0, 0), 3);
+        let maps = DistanceMaps::new(&world, area, turn, player_pos, will);
+        assert!(maps.is_current(&world, turn, player_pos, will));
+        world.explore(player_pos, 4);
+        assert!(maps.is_current(&world, turn, player_pos, will));
+
+        world.move_monster(Point::new(3, 3), Point::new(3, 4), player_pos);
+        assert!(!maps.is_current(&world, turn, player_pos, will));
+
+        let maps = DistanceMaps::new(&world, area, turn, player_pos, will);
+        let dose = world.item_definition_of_kind(item::Kind::Dose).item();
+        world.cell_mut(Point::new(2, 2)).unwrap().items.push(dose);
+        assert!(!maps.is_current(&world, turn, player_pos, will));
+    }
+}
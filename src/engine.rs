@@ -3,7 +3,9 @@
 use crate::{
     color::{self, Color, ColorAlpha},
     game::RunningState,
+    keymap::Keymap,
     keys::Key,
+    palette::Palette,
     point::Point,
     rect::Rectangle,
     state::State,
@@ -11,8 +13,12 @@ use crate::{
     util,
 };
 
-use std::{ffi::CString, mem, os, ptr, time::Duration};
+use std::time::Duration;
 
+#[cfg(any(feature = "glutin-backend", feature = "sdl-backend"))]
+use std::{ffi::CString, mem, os, ptr};
+
+#[cfg(any(feature = "glutin-backend", feature = "sdl-backend"))]
 use gl::types::*;
 use serde::{Deserialize, Serialize};
 
@@ -25,6 +31,9 @@ pub mod sdl;
 #[cfg(feature = "remote")]
 pub mod remote;
 
+#[cfg(feature = "headless-backend")]
+pub mod headless;
+
 #[cfg(feature = "web")]
 pub mod wasm;
 
@@ -552,6 +561,8 @@ pub struct Display {
     drawcalls: Vec<Drawcall>,
     pub fade: ColorAlpha,
     clear_background_color: Option<Color>,
+    /// Palette the drawcalls are remapped to.
+    pub palette: Palette,
 }
 
 #[allow(dead_code)]
@@ -734,10 +745,14 @@ impl Display {
         let tilesize = self.tilesize;
         let font_size = tilesize as u32;
         let display_size_px = self.display_size * tilesize;
+        let palette = self.palette;
 
         if let Some(bg) = self.clear_background_color {
             let full_screen_rect = Rectangle::from_point_and_size(Point::zero(), display_size_px);
-            drawcalls.push(Drawcall::Rectangle(full_screen_rect, bg.into()));
+            drawcalls.push(Drawcall::Rectangle(
+                full_screen_rect,
+                palette.remap(bg).into(),
+            ));
         }
 
         // Render the background tiles separately and before all the other drawcalls.
@@ -762,16 +777,31 @@ impl Display {
             let glyph_dst = background_dst.offset(Point::new(x_offset, 0));
 
             if rect_intersects_area(background_dst, display_size_px) {
-                drawcalls.push(Drawcall::Rectangle(background_dst, cell.background.into()));
-                drawcalls.push(Drawcall::Image(texture_src, glyph_dst, cell.foreground));
+                drawcalls.push(Drawcall::Rectangle(
+                    background_dst,
+                    palette.remap(cell.background).into(),
+                ));
+                drawcalls.push(Drawcall::Image(
+                    texture_src,
+                    glyph_dst,
+                    palette.remap(cell.foreground),
+                ));
             }
         }
 
-        drawcalls.extend(self.drawcalls.iter());
+        drawcalls.extend(self.drawcalls.iter().map(|drawcall| match *drawcall {
+            Drawcall::Rectangle(rect, color) => {
+                Drawcall::Rectangle(rect, palette.remap_alpha(color))
+            }
+            Drawcall::Image(src, dst, color) => Drawcall::Image(src, dst, palette.remap(color)),
+        }));
 
         if self.fade.alpha > 0 {
             let full_screen_rect = Rectangle::from_point_and_size(Point::zero(), display_size_px);
-            drawcalls.push(Drawcall::Rectangle(full_screen_rect, self.fade));
+            drawcalls.push(Drawcall::Rectangle(
+                full_screen_rect,
+                palette.remap_alpha(self.fade),
+            ));
         }
     }
 }
@@ -783,6 +813,7 @@ fn rect_intersects_area(rect: Rectangle, area: Point) -> bool {
 
 /// The OpenGl context of our rendering pipeline. Contains the
 /// shaders, textures, vao and vbos, etc.
+#[cfg(any(feature = "glutin-backend", feature = "sdl-backend"))]
 #[derive(Default)]
 struct OpenGlApp {
     program: GLuint,
@@ -793,6 +824,7 @@ struct OpenGlApp {
     texture: GLuint,
 }
 
+#[cfg(any(feature = "glutin-backend", feature = "sdl-backend"))]
 impl OpenGlApp {
     #[allow(unsafe_code)]
     fn new(vertex_source: &str, fragment_source: &str) -> Self {
@@ -925,6 +957,7 @@ impl OpenGlApp {
     }
 }
 
+#[cfg(any(feature = "glutin-backend", feature = "sdl-backend"))]
 impl Drop for OpenGlApp {
     #[allow(unsafe_code)]
     fn drop(&mut self) {
@@ -939,6 +972,7 @@ impl Drop for OpenGlApp {
     }
 }
 
+#[cfg(any(feature = "glutin-backend", feature = "sdl-backend"))]
 #[allow(unsafe_code, too_many_arguments)]
 fn opengl_render(
     program: GLuint,
@@ -1072,6 +1106,7 @@ fn opengl_render(
     }
 }
 
+#[cfg(any(feature = "glutin-backend", feature = "sdl-backend"))]
 #[allow(unsafe_code)]
 fn check_gl_error(source: &str) {
     let err = unsafe { gl::GetError() };
@@ -1084,10 +1119,38 @@ fn check_gl_error(source: &str) {
 ///
 /// Things such as the fullscreen/windowed display, font size, font
 /// type, etc.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Settings {
     pub fullscreen: bool,
     pub font_size: i32,
+    /// Delete a saved game as soon as it's loaded.
+    pub permadeath: bool,
+    /// Graphics backend to use when none is passed on the command line.
+    pub backend: String,
+    pub palette: Palette,
+    /// Record a replay of every new game.
+    pub record_replays: bool,
+    /// The keys bound to each command.
+    pub keymap: Keymap,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let backend = if AVAILABLE_BACKENDS.contains(&"glutin") {
+            "glutin"
+        } else {
+            AVAILABLE_BACKENDS[0]
+        };
+        Settings {
+            fullscreen: false,
+            font_size: TILESIZE as i32,
+            permadeath: true,
+            backend: backend.into(),
+            palette: Palette::default(),
+            record_replays: true,
+            keymap: Keymap::default(),
+        }
+    }
 }
 
 #[allow(dead_code)]
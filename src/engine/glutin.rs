@@ -157,6 +157,7 @@ pub fn main_loop(
     default_background: Color,
     window_title: &str,
     mut state: Box<State>,
+    mut settings: Settings,
     update: UpdateFn,
 ) {
     // Force the DPI factor to be 1.0
@@ -191,7 +192,12 @@ pub fn main_loop(
     // Both are fixed with the line below:
     std::env::set_var("WINIT_UNIX_BACKEND", "x11");
 
-    let mut tilesize = super::TILESIZE;
+    let mut tilesize = if crate::engine::AVAILABLE_FONT_SIZES.contains(&settings.font_size) {
+        settings.font_size as u32
+    } else {
+        super::TILESIZE
+    };
+    settings.font_size = tilesize as i32;
     let mut desired_window_width = display_size.x as u32 * tilesize as u32;
     let mut desired_window_height = display_size.y as u32 * tilesize as u32;
 
@@ -283,9 +289,12 @@ pub fn main_loop(
     );
 
     let mut mouse = Mouse::new();
-    let mut settings = Settings {
+    // NOTE: the window is created in the windowed mode. Pretending
+    // that's what the previous settings were switches to fullscreen
+    // on the first frame if the player asked for it.
+    let mut previous_settings = Settings {
         fullscreen: false,
-        font_size: tilesize as i32,
+        ..settings.clone()
     };
     let mut window_size_px = Point::new(desired_window_width as i32, desired_window_height as i32);
 
@@ -484,8 +493,6 @@ pub fn main_loop(
             }
         });
 
-        let previous_settings = settings;
-
         let update_result = update(
             &mut state,
             dt,
@@ -559,6 +566,8 @@ pub fn main_loop(
             }
         }
 
+        previous_settings = settings.clone();
+
         drawcalls.clear();
         display.push_drawcalls(&mut drawcalls);
 
@@ -663,6 +672,12 @@ pub fn main_loop(
         gl_window.set_resizable(false);
     }
 
+    // NOTE: `running` is only false when the window was closed (as
+    // opposed to quitting from the game's menu)
+    if !running {
+        crate::game::autosave_on_exit(&mut state);
+    }
+
     log::debug!(
         "Drawcall count: {}. Capacity: {}.",
         overall_max_drawcall_count,
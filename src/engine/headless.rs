@@ -0,0 +1,370 @@
+//! Run the game simulation without any window, rendering or input
+//! handling.
+//!
+//! This is meant for balancing and regression testing: play a batch
+//! of games (or a replay) as fast as the simulation allows and print
+//! a one-line JSON summary of every game to stdout.
+
+use crate::{
+    formula, game, item,
+    player::{CauseOfDeath, Mind},
+    point::Point,
+    random::{self, Random},
+    state::{Command, Side, State},
+};
+
+use std::path::PathBuf;
+
+use rand::{seq::SliceRandom, Rng};
+use serde::Serialize;
+
+/// Number of consecutive steps in which nothing happens before we
+/// consider the game stuck (e.g. the player keeps walking into trees).
+const MAX_IDLE_STEPS: i32 = 1000;
+
+/// Where the player's commands come from.
+#[derive(Clone, Debug)]
+pub enum Source {
+    /// Replay the commands stored in the given replay file.
+    Replay(PathBuf),
+    /// Pick a random (mostly movement) command every time.
+    RandomAgent,
+    /// A very simple scripted player: eats when in withdrawal, walks
+    /// towards the nearest item it can see and wanders otherwise.
+    Bot,
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    pub source: Source,
+    /// How many games to play. Ignored when replaying.
+    pub games: u32,
+    /// Seed of the first game. Every following game increments it.
+    pub seed: Option<u32>,
+    /// End the game after this many turns.
+    pub max_turns: i32,
+    /// Record a replay of every game into this directory.
+    pub replay_dir: Option<PathBuf>,
+    pub invincible: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum Outcome {
+    Death,
+    Victory,
+    TurnLimit,
+    ReplayEnded,
+    Stuck,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    seed: u32,
+    turns: i32,
+    cause_of_death: Option<CauseOfDeath>,
+    victory: bool,
+    outcome: Outcome,
+    /// The first turn where the replay diverged from the recording.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    desync_turn: Option<i32>,
+}
+
+pub fn run(
+    world_size: Point,
+    map_size: i32,
+    panel_width: i32,
+    display_size: Point,
+    options: &Options,
+) {
+    let mut summaries = vec![];
+
+    if let Source::Replay(ref replay_path) = options.source {
+        let mut state = State::replay_game(
+            world_size,
+            map_size,
+            panel_width,
+            display_size,
+            replay_path,
+            false, // cheating
+            options.invincible,
+            true, // replay full speed
+            true, // exit after
+        )
+        .expect("Could not load the replay file");
+        let summary = play(&mut state, None, options.max_turns);
+        print_summary(&summary);
+        summaries.push(summary);
+    } else {
+        let first_seed = options.seed.unwrap_or_else(crate::util::random_seed);
+        for index in 0..options.games {
+            let seed = first_seed.wrapping_add(index);
+            let replay_path = options
+                .replay_dir
+                .as_ref()
+                .map(|dir| dir.join(format!("replay-seed-{}", seed)));
+            let mut state = State::new_game_with_seed(
+                world_size,
+                map_size,
+                panel_width,
+                display_size,
+                true, // exit after
+                replay_path,
+                options.invincible,
+                seed,
+            );
+            let mut agent_rng = random::from_seed(u64::from(seed));
+            let summary = play(
+                &mut state,
+                Some((&options.source, &mut agent_rng)),
+                options.max_turns,
+            );
+            print_summary(&summary);
+            summaries.push(summary);
+        }
+    }
+
+    let victories = summaries.iter().filter(|s| s.victory).count();
+    let total_turns: i32 = summaries.iter().map(|s| s.turns).sum();
+    let mean_turns = if summaries.is_empty() {
+        0.0
+    } else {
+        total_turns as f32 / summaries.len() as f32
+    };
+    log::info!(
+        "Simulated {} games. Victories: {}, mean turns: {:.1}",
+        summaries.len(),
+        victories,
+        mean_turns
+    );
+}
+
+fn print_summary(summary: &Summary) {
+    match serde_json::to_string(summary) {
+        Ok(line) => println!("{}", line),
+        Err(err) => log::error!("Could not serialise the game summary: {}", err),
+    }
+}
+
+/// Run the game until the player dies, wins, runs out of turns or
+/// (when replaying) out of commands.
+///
+/// When `agent` is `None`, only the commands already queued up in the
+/// `state` are processed.
+fn play(state: &mut State, mut agent: Option<(&Source, &mut Random)>, max_turns: i32) -> Summary {
+    let mut idle_steps = 0;
+
+    let outcome = loop {
+        if !state.player.alive() {
+            break Outcome::Death;
+        }
+        if state.side == Side::Victory {
+            break Outcome::Victory;
+        }
+        if state.turn >= max_turns {
+            break Outcome::TurnLimit;
+        }
+        if idle_steps > MAX_IDLE_STEPS {
+            break Outcome::Stuck;
+        }
+
+        // NOTE: the player only acts when they have action points
+        // left. Otherwise we're just letting the monsters finish
+        // their turn.
+        if state.player.has_ap(1) && state.commands.is_empty() {
+            match agent {
+                Some((source, ref mut rng)) => {
+                    let command = match source {
+                        Source::Bot => bot_command(state, rng),
+                        _ => random_command(state, rng),
+                    };
+                    state.commands.push_back(command);
+                }
+                None => break Outcome::ReplayEnded,
+            }
+        }
+
+        let turn = state.turn;
+        let player_ap = state.player.ap();
+        game::simulate_step(state);
+
+        if state.turn == turn && state.player.ap() == player_ap {
+            idle_steps += 1;
+        } else {
+            idle_steps = 0;
+        }
+    };
+
+    let cause_of_death = if state.player.alive() {
+        None
+    } else {
+        formula::cause_of_death(&state.player)
+    };
+
+    Summary {
+        seed: state.seed,
+        turns: state.turn,
+        cause_of_death,
+        victory: outcome == Outcome::Victory,
+        outcome,
+        desync_turn: state.replay_controller.desync_turn,
+    }
+}
+
+fn random_command(state: &State, rng: &mut Random) -> Command {
+    let mut options = vec![];
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            if let Some(command) = Command::movement(Point::new(dx, dy)) {
+                options.push((command, 10));
+            }
+        }
+    }
+    for item in &state.player.inventory {
+        options.push((Command::Use(item.id.clone()), 1));
+    }
+
+    options
+        .choose_weighted(rng, |option| option.1)
+        .map(|option| option.0.clone())
+        .unwrap_or(Command::N)
+}
+
+fn bot_command(state: &State, rng: &mut Random) -> Command {
+    use crate::blocker::Blocker;
+
+    let player = &state.player;
+    if let Mind::Withdrawal(value) = player.mind {
+        if value.to_int() <= value.middle() {
+            // NOTE: eat first, then take the weak starting dose
+            for &kind in &[item::Kind::Food, item::Kind::Dose] {
+                let id = &state.world.item_definition_of_kind(kind).id;
+                if player.inventory.iter().any(|item| item.id == *id) {
+                    return Command::Use(id.clone());
+                }
+            }
+        }
+    }
+
+    // NOTE: don't go after more doses when already High
+    let high = player.mind.is_high();
+    let radius = formula::exploration_radius(player.mind);
+    let target = player
+        .pos
+        .circular_area(radius)
+        .filter(|&pos| {
+            state.world.cell(pos).map_or(false, |cell| {
+                cell.items.iter().any(|item| !(item.is_dose() && high))
+            })
+        })
+        .min_by_key(|&pos| player.pos.tile_distance(pos));
+
+    let walkable = |direction: Point| {
+        state
+            .world
+            .walkable(player.pos + direction, Blocker::WALL, player.pos)
+    };
+
+    if let Some(target) = target {
+        let offset = target - player.pos;
+        let direction = Point::new(offset.x.signum(), offset.y.signum());
+        if walkable(direction) {
+            if let Some(command) = Command::movement(direction) {
+                return command;
+            }
+        }
+    }
+
+    let mut directions = vec![];
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let direction = Point::new(dx, dy);
+            if direction != Point::zero() && walkable(direction) {
+                directions.push(direction);
+            }
+        }
+    }
+
+    let direction = directions
+        .choose(rng)
+        .cloned()
+        .unwrap_or_else(|| Point::new(rng.gen_range(-1, 2), rng.gen_range(-1, 2)));
+    Command::movement(direction).unwrap_or(Command::N)
+}
+
+#[cfg(test)]
+mod test {
+    use super::play;
+    use crate::{replay, state::State};
+
+    use std::{fs, path::Path};
+
+    /// Replay every file in `tests/golden-replays` and check that the
+    /// game still plays out exactly the way it was recorded.
+    ///
+    /// If you've changed the gameplay on purpose, record the golden
+    /// replays again (see `DEVELOPER-NOTES.md`).
+    #[test]
+    #[cfg(feature = "replay")]
+    fn test_golden_replays() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("golden-replays");
+        let mut paths = fs::read_dir(&dir)
+            .expect("Could not read the golden replays directory")
+            .map(|entry| entry.expect("Could not read a golden replay").path())
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert!(!paths.is_empty(), "No golden replays in {}", dir.display());
+
+        let mut failures = vec![];
+        for path in &paths {
+            let mut state = State::replay_game(
+                crate::WORLD_SIZE,
+                crate::DISPLAYED_MAP_SIZE,
+                crate::PANEL_WIDTH,
+                crate::DISPLAY_SIZE,
+                path,
+                false, // cheating
+                false, // invincible
+                true,  // replay full speed
+                true,  // exit after
+            )
+            .unwrap_or_else(|err| panic!("Could not load {}: {}", path.display(), err));
+            assert!(
+                !state.verifications.is_empty(),
+                "{} has no verifications. Record it with the `verifications` feature.",
+                path.display()
+            );
+
+            play(&mut state, None, i32::max_value());
+
+            let controller = &state.replay_controller;
+            if let Some(turn) = controller.desync_turn {
+                let mut report = vec![];
+                replay::write_desync_report(&mut report, turn, &controller.divergences)
+                    .expect("Could not write the desync report");
+                failures.push(format!(
+                    "{}:\n{}",
+                    path.display(),
+                    String::from_utf8_lossy(&report)
+                ));
+            } else if !state.verifications.is_empty() {
+                failures.push(format!(
+                    "{}: the replay ended at turn {} but there are still {} verifications left.",
+                    path.display(),
+                    state.turn,
+                    state.verifications.len()
+                ));
+            }
+        }
+
+        assert!(
+            failures.is_empty(),
+            "{} of {} golden replays diverged:\n\n{}",
+            failures.len(),
+            paths.len(),
+            failures.join("\n")
+        );
+    }
+}
@@ -91,6 +91,7 @@ pub fn main_loop(
     _default_background: Color,
     _window_title: &str,
     mut state: Box<State>,
+    mut settings: Settings,
     update: UpdateFn,
 ) {
     let ipc = match ZeroMQ::new("ipc:///tmp/dose-response.ipc") {
@@ -100,7 +101,6 @@ pub fn main_loop(
 
     let tilesize = super::TILESIZE;
     let mouse = Mouse::new();
-    let mut settings = Settings { fullscreen: false };
     let mut keys = vec![];
     let mut display = Display::new(
         display_size,
@@ -114,9 +114,15 @@ pub fn main_loop(
     default_background: Color,
     window_title: &str,
     mut state: Box<State>,
+    mut settings: Settings,
     update: UpdateFn,
 ) {
-    let tilesize = super::TILESIZE;
+    let tilesize = if crate::engine::AVAILABLE_FONT_SIZES.contains(&settings.font_size) {
+        settings.font_size as u32
+    } else {
+        super::TILESIZE
+    };
+    settings.font_size = tilesize as i32;
     let (desired_window_width, desired_window_height) = (
         display_size.x as u32 * tilesize as u32,
         display_size.y as u32 * tilesize as u32,
@@ -168,7 +174,6 @@ pub fn main_loop(
         .expect("SDL event pump creation failed.");
 
     let mut mouse = Mouse::new();
-    let mut settings = Settings { fullscreen: false };
     let mut window_size_px = Point::new(desired_window_width as i32, desired_window_height as i32);
     let mut display = engine::Display::new(
         display_size,
@@ -305,7 +310,7 @@ pub fn main_loop(
             }
         }
 
-        let previous_settings = settings;
+        let previous_settings = settings.clone();
 
         let update_result = update(
             &mut state,
@@ -421,6 +426,12 @@ pub fn main_loop(
         //          frame_start_time.elapsed().subsec_nanos() as f32 / 1_000_000.0);
     }
 
+    // NOTE: `running` is only false when the window was closed (as
+    // opposed to quitting from the game's menu)
+    if !running {
+        crate::game::autosave_on_exit(&mut state);
+    }
+
     log::debug!(
         "Drawcall count: {}. Capacity: {}.",
         overall_max_drawcall_count,
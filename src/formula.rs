@@ -1,7 +1,7 @@
 use crate::{
-    item::{Item, Kind},
+    level::TileKind,
     monster::CompanionBonus,
-    player::{Bonus, CauseOfDeath, Mind, Modifier, Player},
+    player::{Bonus, CauseOfDeath, Mind, Player},
     point::Point,
     ranged_int::{InclusiveRange, Ranged},
 };
@@ -38,72 +38,26 @@ pub const HIGH: InclusiveRange = InclusiveRange(0, 80);
 
 pub const VICTORY_NPC_DISTANCE: InclusiveRange = InclusiveRange(80, 120);
 
-#[cfg(feature = "recording")]
-pub const DOSE_PREFAB: Item = Item {
-    kind: Kind::Dose,
-    irresistible: 2,
-    modifier: Modifier::Intoxication {
-        state_of_mind: 30,
-        tolerance_increase: 4,
-    },
-};
-
-#[cfg(not(feature = "recording"))]
-pub const DOSE_PREFAB: Item = Item {
-    kind: Kind::Dose,
-    irresistible: 2,
-    modifier: Modifier::Intoxication {
-        state_of_mind: 70,
-        tolerance_increase: 4,
-    },
-};
-
-pub const STRONG_DOSE_PREFAB: Item = Item {
-    kind: Kind::StrongDose,
-    irresistible: 4,
-    modifier: Modifier::Intoxication {
-        state_of_mind: 220,
-        tolerance_increase: 7,
-    },
-};
-
-pub const CARDINAL_DOSE_PREFAB: Item = Item {
-    kind: Kind::CardinalDose,
-    irresistible: 3,
-    modifier: Modifier::Intoxication {
-        state_of_mind: 130,
-        tolerance_increase: 6,
-    },
-};
-
-pub const DIAGONAL_DOSE_PREFAB: Item = Item {
-    kind: Kind::DiagonalDose,
-    irresistible: 3,
-    modifier: Modifier::Intoxication {
-        state_of_mind: 130,
-        tolerance_increase: 6,
-    },
-};
-
-pub const FOOD_PREFAB: Item = Item {
-    kind: Kind::Food,
-    irresistible: 0,
-    modifier: Modifier::Attribute {
-        state_of_mind: 20,
-        will: 0,
-    },
-};
-
-// This how much a given dose can vary from the prefab's base value
-pub const DOSE_MIND_VARIANCE: InclusiveRange = InclusiveRange(-5, 5);
-pub const STRONG_DOSE_MIND_VARIANCE: InclusiveRange = InclusiveRange(-15, -15);
-pub const CARDINAL_DOSE_MIND_VARIANCE: InclusiveRange = InclusiveRange(-10, 10);
-pub const DIAGONAL_DOSE_MIND_VARIANCE: InclusiveRange = InclusiveRange(-10, 10);
-
 pub const PLAYER_BASE_AP: i32 = 1;
+/// The player's action points are counted in this many fractions of
+/// a point so moving along a road can cost less than a whole one.
+pub const AP_FRACTIONS: i32 = 2;
 pub const PLAYER_STARTING_WILL: i32 = 2;
 pub const PANIC_TURNS: InclusiveRange = InclusiveRange(0, 10);
 pub const STUN_TURNS: InclusiveRange = InclusiveRange(0, 10);
+/// How long are the monsters hit by a `TileEffect::STUN` stunned.
+pub const EXPLOSION_STUN_TURNS: i32 = 3;
+/// A dose that gets the player this close to an overdose makes them
+/// nauseous.
+pub const NAUSEA_OVERDOSE_MARGIN: i32 = 5;
+pub const NAUSEA_TURNS: i32 = 20;
+/// Depressions this close (in tiles) to the player's companion are
+/// slowed down.
+pub const COMPANION_SLOW_DISTANCE: i32 = 3;
+pub const MAX_COMPANIONS: usize = 3;
+/// How many times can the player get high before a companion leaves
+/// them for good.
+pub const COMPANION_PATIENCE: i32 = 2;
 
 pub const CHASING_DISTANCE: i32 = 5;
 pub const HOWLING_DISTANCE: i32 = 15;
@@ -112,9 +66,23 @@ pub const ESTRANGED_NPC_MAX_AP: i32 = 2;
 
 pub const FRIENDLY_NPC_FREEZE_RADIUS: f32 = 3.0;
 
+/// The path finding estimate of the remaining distance is multiplied
+/// by this. Anything above `1` finds a path faster, but it can be
+/// this many times more expensive than the cheapest one.
+pub const PATHFINDING_HEURISTIC_WEIGHT: i32 = 2;
 pub const PATHFINDING_MONSTER_LIMIT: i32 = 50;
-pub const PATHFINDING_DOSE_RESIST_LIMIT: i32 = 50;
 pub const PATHFINDING_VNPC_REACHABILITY_LIMIT: i32 = 500;
+pub const PATHFINDING_TRAVEL_LIMIT: i32 = 500;
+pub const PATHFINDING_EXPLORE_RADIUS: i32 = 30;
+
+/// The panicking player runs from the monsters. The flee map values
+/// each tile by its distance from them times this percentage. Above
+/// `100` a detour past the monsters is worth it when the way ahead is
+/// a dead end.
+pub const FLEE_DISTANCE_PERCENT: i32 = 120;
+
+/// How far (in tiles) the player can throw a dose.
+pub const THROW_RANGE: i32 = 6;
 
 pub fn exploration_radius(mental_state: Mind) -> i32 {
     use crate::player::Mind::*;
@@ -141,12 +109,20 @@ pub fn player_resist_radius(dose_irresistible_value: i32, will: i32) -> i32 {
     cmp::max(dose_irresistible_value + 2 - will, 0)
 }
 
+/// How many times does the `bonus` apply. Every companion with the
+/// bonus counts, up to `CompanionBonus::max_stack`.
+pub fn companion_bonus_stack(bonuses: &[CompanionBonus], bonus: CompanionBonus) -> i32 {
+    let count = bonuses.iter().filter(|&&b| b == bonus).count() as i32;
+    cmp::min(count, bonus.max_stack())
+}
+
 pub fn mind_drop_per_turn(bonuses: &[CompanionBonus]) -> Rational32 {
-    if bonuses.contains(&CompanionBonus::HalveExhaustion) {
-        Ratio::new(MIND_DROP_PER_TURN, 2)
-    } else {
-        Ratio::from_integer(MIND_DROP_PER_TURN)
-    }
+    let stack = companion_bonus_stack(bonuses, CompanionBonus::HalveExhaustion);
+    Ratio::new(MIND_DROP_PER_TURN, 1 + stack)
+}
+
+pub fn anxiety_increment(bonuses: &[CompanionBonus]) -> i32 {
+    1 + companion_bonus_stack(bonuses, CompanionBonus::DoubleWillGrowth)
 }
 
 pub fn mind_take_turn(mind: Mind, drop: Rational32) -> Mind {
@@ -228,6 +204,14 @@ pub fn mind_bonus(mind: Mind) -> Option<Bonus> {
     }
 }
 
+/// Whether the player is so high they barely avoided an overdose.
+pub fn nearly_overdosed(mind: Mind) -> bool {
+    match mind {
+        Mind::High(val) => val.to_int() >= HIGH.1 - NAUSEA_OVERDOSE_MARGIN,
+        Mind::Withdrawal(_) | Mind::Sober(_) => false,
+    }
+}
+
 pub fn cause_of_death(player: &Player) -> Option<CauseOfDeath> {
     use self::CauseOfDeath::*;
     match player.mind {
@@ -271,9 +255,22 @@ pub fn victory_npc_position<R: Rng>(
 }
 
 pub fn player_max_ap(bonuses: &[CompanionBonus]) -> i32 {
-    if bonuses.contains(&CompanionBonus::ExtraActionPoint) {
-        PLAYER_BASE_AP + 1
-    } else {
-        PLAYER_BASE_AP
+    PLAYER_BASE_AP + companion_bonus_stack(bonuses, CompanionBonus::ExtraActionPoint)
+}
+
+/// The cheapest `move_cost` of all the tiles.
+pub const MIN_MOVE_COST: i32 = AP_FRACTIONS / 2;
+
+/// How many `AP_FRACTIONS` does moving onto the tile cost.
+pub fn move_cost(kind: TileKind) -> i32 {
+    use crate::level::TileKind::*;
+    match kind {
+        Road => MIN_MOVE_COST,
+        Empty | Door => AP_FRACTIONS,
+        Rubble => AP_FRACTIONS * 3 / 2,
+        ShallowWater => AP_FRACTIONS * 2,
+        // NOTE: nobody can walk into the walls, but standing in one
+        // (e.g. after a tree was planted) costs the usual.
+        Tree | Building => AP_FRACTIONS,
     }
 }
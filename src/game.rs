@@ -3,45 +3,52 @@ use crate::{
     animation::{self, AreaOfEffect},
     blocker::Blocker,
     color,
+    companion::{self, Companion},
+    distance_map::DistanceMaps,
     engine::{Display, Mouse, Settings, TextMetrics},
     formula, item,
+    keymap::{self, Keymap},
     keys::{Key, KeyCode, Keys},
-    level::TileKind,
+    level::{Tile, TileKind},
+    message::Event,
     monster::{self, CompanionBonus},
     pathfinding, player,
     point::{self, Point},
     ranged_int::{InclusiveRange, Ranged},
     rect::Rectangle,
-    render,
+    render, replay, savegame,
     state::{self, Command, Side, State},
     stats::{FrameStats, Stats},
+    status,
     timer::{Stopwatch, Timer},
+    travel::{self, Travel},
     util,
     window::{self, Window},
-    windows::{endgame, help, main_menu, settings, sidebar},
+    windows::{controls, endgame, help, load_game, main_menu, message_log, settings, sidebar},
     world::World,
 };
 
-use std::{
-    collections::{HashMap, VecDeque},
-    io::Write,
-    iter::FromIterator,
-    time::Duration,
-    u64,
-};
+use std::{cmp, collections::VecDeque, io::Write, time::Duration, u64};
 
 use rand::Rng;
 
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum Action {
     Move(Point),
     Attack(Point, player::Modifier),
-    Use(item::Kind),
+    Use(String),
+    Drop(String),
+    Throw(String, Point),
 }
 
 pub enum RunningState {
     Running,
     Stopped,
+    // NOTE: only the window backends start the new game.
+    #[cfg_attr(
+        not(any(feature = "glutin-backend", feature = "sdl-backend", feature = "web")),
+        allow(dead_code)
+    )]
     NewGame(Box<State>),
 }
 
@@ -58,6 +65,7 @@ pub fn update(
     display: &mut Display, // TODO: remove this from the engine and keep a transient state instead
 ) -> RunningState {
     let update_stopwatch = Stopwatch::start();
+    let previous_settings = settings.clone();
     state.clock += dt;
     state.replay_step += dt;
 
@@ -97,12 +105,18 @@ pub fn update(
 
     let current_window = state.window_stack.top();
     let game_update_result = match current_window {
-        Window::MainMenu => process_main_menu(state, &main_menu::Window, metrics),
+        Window::MainMenu => process_main_menu(state, settings, &main_menu::Window, metrics),
         Window::Game => process_game(state, settings, &sidebar::Window, metrics, dt),
         Window::Settings => process_settings_window(state, settings, &settings::Window, metrics),
         Window::Help => process_help_window(state, &help::Window, metrics),
-        Window::Endgame => process_endgame_window(state, &endgame::Window, metrics),
+        Window::LoadGame => process_load_game_window(state, settings, &load_game::Window, metrics),
+        Window::Controls => process_controls_window(state, settings, &controls::Window, metrics),
+        Window::Endgame => process_endgame_window(state, settings, &endgame::Window, metrics),
         Window::Message { .. } => process_message_window(state),
+        Window::Look => process_look_window(state, settings),
+        Window::MessageLog => process_message_log_window(state, &message_log::Window, metrics),
+        Window::Drop => process_drop_window(state, settings, &sidebar::Window, metrics),
+        Window::Throw => process_throw_window(state, settings, &sidebar::Window, metrics),
     };
 
     // NOTE: process the screen fading animation animation.
@@ -132,6 +146,13 @@ pub fn update(
     // NOTE: Clear any unprocessed keys
     while let Some(_key) = state.keys.get() {}
 
+    if *settings != previous_settings {
+        if let Err(error) = crate::settings::save(settings) {
+            log::error!("Could not save the settings: {}", error);
+        }
+    }
+    display.palette = settings.palette;
+
     let update_duration = update_stopwatch.finish();
 
     let drawcall_stopwatch = Stopwatch::start();
@@ -164,16 +185,26 @@ fn process_game(
     use self::sidebar::Action;
 
     let mut option = if state.mouse.left_clicked {
-        window.hovered(&state, metrics)
+        window.hovered(&state, &settings.keymap, metrics)
     } else {
         None
     };
 
     if option.is_none() {
-        option = if state.keys.matches_code(KeyCode::Esc) {
+        let keys = &mut state.keys;
+        let keymap = &settings.keymap;
+        option = if keys.matches_code(KeyCode::Esc) {
             Some(Action::MainMenu)
-        } else if state.keys.matches_code(KeyCode::QuestionMark) {
+        } else if keys.matches_code(KeyCode::QuestionMark) {
             Some(Action::Help)
+        } else if !state.game_ended && pressed(keys, keymap, keymap::Action::Look) {
+            Some(Action::Look)
+        } else if pressed(keys, keymap, keymap::Action::MessageLog) {
+            Some(Action::MessageLog)
+        } else if pressed(keys, keymap, keymap::Action::Drop) {
+            Some(Action::Drop)
+        } else if pressed(keys, keymap, keymap::Action::Throw) {
+            Some(Action::Throw)
         } else {
             None
         };
@@ -181,13 +212,52 @@ fn process_game(
 
     match option {
         Some(Action::MainMenu) => {
+            state.travel = None;
             state.window_stack.push(Window::MainMenu);
             return RunningState::Running;
         }
         Some(Action::Help) => {
+            state.travel = None;
             state.window_stack.push(Window::Help);
             return RunningState::Running;
         }
+        Some(Action::Look) => {
+            state.travel = None;
+            state.cursor = state.player.pos;
+            state.cursor_mouse_pos = state.mouse.tile_pos;
+            state.window_stack.push(Window::Look);
+            return RunningState::Running;
+        }
+        Some(Action::MessageLog) => {
+            state.travel = None;
+            state.message_log_scroll = 0;
+            state.window_stack.push(Window::MessageLog);
+            return RunningState::Running;
+        }
+        Some(Action::Drop) if can_handle_items(state) => {
+            state.travel = None;
+            if !state.player.inventory.is_empty() {
+                state.window_stack.push(Window::Drop);
+            }
+            return RunningState::Running;
+        }
+        Some(Action::Throw) if can_handle_items(state) => {
+            state.travel = None;
+            // NOTE: pick the first dose in the order of the sidebar
+            let inventory = &state.player.inventory;
+            state.throw_item = state
+                .world
+                .item_definitions
+                .iter()
+                .find(|d| d.kind == item::Kind::Dose && inventory.iter().any(|i| i.id == d.id))
+                .map(|d| d.id.clone());
+            if state.throw_item.is_some() {
+                state.cursor = state.player.pos;
+                state.cursor_mouse_pos = state.mouse.tile_pos;
+                state.window_stack.push(Window::Throw);
+            }
+            return RunningState::Running;
+        }
         _ => {}
     }
 
@@ -205,26 +275,32 @@ fn process_game(
         state.cheating = !state.cheating;
     }
 
-    // NOTE: this will not show up in the replay so that'll be out of
-    // sync. We can pass `--invincible` while running the replay
-    // though and that should always work, I think.
+    // NOTE: the cheats will not show up in the replay so that'll be
+    // out of sync. We can pass `--invincible` while running the
+    // replay though and that should always work, I think. The
+    // annotations at least tell us what happened.
     if cfg!(feature = "cheating") && state.keys.matches_code(KeyCode::I) && state.cheating {
         log::info!("Making the player invincible, you cheat!");
         state.player.invincible = true;
+        replay::log_annotation(&mut state.command_logger, "Cheat: invincible player");
     }
 
     if cfg!(feature = "cheating") && state.keys.matches_code(KeyCode::F) && state.cheating {
         log::info!("Adding one Food, you cheat!");
-        state.player.inventory.push(formula::FOOD_PREFAB);
+        let food = state.world.item_definition_of_kind(item::Kind::Food).item();
+        state.player.inventory.push(food);
+        replay::log_annotation(&mut state.command_logger, "Cheat: added one Food");
     }
 
     if cfg!(feature = "cheating") && state.keys.matches_code(KeyCode::W) && state.cheating {
         log::info!("Increasing Will by one, you cheat!");
         state.player.will += 1;
+        replay::log_annotation(&mut state.command_logger, "Cheat: increased Will by one");
     }
 
     if cfg!(feature = "cheating") && state.keys.matches_code(KeyCode::V) && state.cheating {
         let vnpc_pos = place_victory_npc(state);
+        replay::log_annotation(&mut state.command_logger, "Cheat: placed the Victory NPC");
 
         // NOTE: Scroll to the Victory NPC position
         {
@@ -240,6 +316,24 @@ fn process_game(
         state.paused
     };
 
+    // NOTE: seeking within the replay. Hold Shift to move by
+    // `SNAPSHOT_INTERVAL` turns, Left steps back one turn when paused.
+    if state.replay {
+        let seek_turns = if state.keys.matches(|k| k.code == KeyCode::Right && k.shift) {
+            Some(replay::SNAPSHOT_INTERVAL)
+        } else if state.keys.matches(|k| k.code == KeyCode::Left && k.shift) {
+            Some(-replay::SNAPSHOT_INTERVAL)
+        } else if state.paused && state.keys.matches_code(KeyCode::Left) {
+            Some(-1)
+        } else {
+            None
+        };
+        if let Some(turns) = seek_turns {
+            let turn = state.turn + turns;
+            seek_replay(state, turn);
+        }
+    }
+
     let paused_one_step = state.paused && state.keys.matches_code(KeyCode::Right);
     let timed_step = if state.replay
         && !state.paused
@@ -280,163 +374,70 @@ fn process_game(
     let simulation_area = Rectangle::center(state.player.pos, state.map_size);
 
     if (running || paused_one_step || timed_step) && state.side != Side::Victory && no_animations {
-        let monster_count = state.world.monsters(simulation_area).count();
-        let monster_with_ap_count = state
-            .world
-            .monsters(simulation_area)
-            .filter(|m| m.has_ap(1))
-            .count();
-        let monster_cumulative_ap: i32 = state
-            .world
-            .monsters(simulation_area)
-            .map(|m| m.ap.to_int())
-            .sum();
-        log::debug!(
-            "Player AP: {}, monsters: {}, active mon: {}, total mon AP: {}",
-            state.player.ap(),
-            monster_count,
-            monster_with_ap_count,
-            monster_cumulative_ap
+        let queued_commands = state.commands.len();
+        process_keys(
+            &mut state.keys,
+            &mut state.commands,
+            &settings.keymap,
+            &state.world.item_definitions,
         );
-
-        process_keys(&mut state.keys, &mut state.commands);
-        let mouse_command = match option {
-            Some(Action::UseFood) => Some(Command::UseFood),
-            Some(Action::UseDose) => Some(Command::UseDose),
-            Some(Action::UseCardinalDose) => Some(Command::UseCardinalDose),
-            Some(Action::UseDiagonalDose) => Some(Command::UseDiagonalDose),
-            Some(Action::UseStrongDose) => Some(Command::UseStrongDose),
-            _ => None,
-        };
+        let mouse_command = option
+            .as_ref()
+            .and_then(Action::item)
+            .map(|id| Command::Use(id.into()));
 
         if let Some(command) = mouse_command {
             state.commands.push_front(command);
         }
 
-        // NOTE: Process 1 action point of the player and then 1 AP of
-        // all monsters. This means that their turns will alternate.
-        // E.g. if the player has 2 APs and they're close to a
-        // Depression, the player will move 1 turn first, then
-        // Depression 1 etc.
-
-        let player_ap = state.player.ap();
-        if state.player.ap() >= 1 {
-            process_player(state, simulation_area);
-        }
-        let player_took_action = player_ap > state.player.ap();
-        let monsters_can_move = state.player.ap() == 0 || player_took_action;
-
-        if state.explosion_animation.is_none() {
-            if monsters_can_move {
-                process_monsters(
-                    &mut state.world,
-                    &mut state.player,
-                    simulation_area,
-                    &mut state.rng,
-                );
-            } else {
-                log::debug!("Monsters waiting for player.");
-            }
-        } else {
-            log::debug!("Monster's waiting for the explosion to end.");
+        // NOTE: any other command or a right click stops the travel
+        if state.commands.len() > queued_commands || state.mouse.right_clicked {
+            state.travel = None;
         }
 
-        // NOTE: the anxiety counter bar is hidden at the start, but
-        // we want to show it as soon as it increases.
-        if player_took_action && !state.player.anxiety_counter.is_min() {
-            state.show_anxiety_counter = true;
+        // NOTE: the explore key keeps exploring rather than taking
+        // a single step
+        if state.commands.len() > queued_commands
+            && state.commands.back() == Some(&Command::Explore)
+        {
+            state.commands.pop_back();
+            state.travel = Some(Travel::explore(state));
         }
 
-        if player_took_action && state.player.mind.is_high() {
-            if let Some(victory_npc_id) = state.victory_npc_id.take() {
-                log::info!("Player got High, the Victory NPC disappears!");
-                if let Some(vnpc) = state.world.monster_mut(victory_npc_id) {
-                    // TODO: move this (and other init stuff from
-                    // Monster::new) to custom functions?
-                    vnpc.kind = monster::Kind::Signpost;
-                    vnpc.color = color::signpost;
-                    vnpc.behavior = ai::Behavior::Immobile;
-                    vnpc.ai_state = ai::AIState::NoOp
-                }
-            }
+        let map_rect = Rectangle::from_point_and_size(Point::zero(), state.map_size);
+        if state.mouse.left_clicked
+            && option.is_none()
+            && map_rect.contains(state.mouse.tile_pos)
+            && !state.replay
+            && state.player.alive()
+        {
+            let screen_left_top_corner = state.screen_position_in_world - (state.map_size / 2);
+            let destination = state.mouse.tile_pos + screen_left_top_corner;
+            state.travel = Travel::new(state, destination);
         }
 
-        // Reset all action points only after everyone is at zero:
-        let player_turn_ended = !state.player.has_ap(1);
-        let monster_turn_ended = state
-            .world
-            .monsters(simulation_area)
-            .filter(|m| m.has_ap(1))
-            .count()
-            == 0;
-
-        entire_turn_ended = player_turn_ended && monster_turn_ended;
-    }
-
-    // Log or check verifications
-    if entire_turn_ended {
-        if state.replay {
-            if let Some(expected) = state.verifications.pop_front() {
-                let actual = state.verification();
-                verify_states(&expected, &actual);
-
-                if player_was_alive && !state.player.alive() && !state.commands.is_empty() {
-                    panic!(
-                        "Game quit too early -- there are still {} \
-                         commands queued up.",
-                        state.commands.len()
-                    );
-                }
-            } else {
-                // NOTE: no verifications were loaded. Probably
-                // replaying a release build.
-            }
-        } else if cfg!(feature = "verifications") {
-            let verification = state.verification();
-            state::log_verification(&mut state.command_logger, &verification);
-        }
-    }
+        process_travel(state, dt);
 
-    // Reset the player & monster action points
-    // NOTE: doing this only after we've logged the validations. Actually maybe we want to do this
-    // before we start turn processing??
-    if entire_turn_ended {
-        log::debug!("Starting new turn for player and monsters.");
-        state.player.new_turn();
-        for monster in state.world.monsters_mut(simulation_area) {
-            monster.new_turn();
-        }
+        entire_turn_ended = process_actions(state, simulation_area);
     }
 
     if entire_turn_ended {
-        log::debug!("Turn {} has ended.", state.turn);
-        state.turn += 1;
-    }
+        end_turn(state, simulation_area, player_was_alive);
 
-    // NOTE: Load up new chunks if necessary
-    if entire_turn_ended {
-        for pos in simulation_area.points() {
-            state.world.ensure_chunk_at_pos(pos);
+        if !state.replay && state.player.alive() && state.turn % savegame::AUTOSAVE_INTERVAL == 0 {
+            savegame::autosave(state);
         }
     }
 
-    // Run the dose explosion effect here:
-    if let Some(ref anim) = state.explosion_animation {
-        for (pos, _, effect) in anim.tiles() {
-            if effect.contains(animation::TileEffect::KILL) {
-                kill_monster(pos, &mut state.world);
-            }
-            if effect.contains(animation::TileEffect::SHATTER) {
-                if let Some(cell) = state.world.cell_mut(pos) {
-                    cell.tile.kind = TileKind::Empty;
-                    cell.items.clear();
-                }
+    // Set the fadeout animation on death
+    if player_was_alive && !state.player.alive() {
+        // NOTE: the lost game can't be continued
+        if settings.permadeath && !state.replay {
+            if let Err(error) = savegame::delete_autosaves() {
+                log::error!("Could not delete the autosaves: {:?}", error);
             }
         }
-    }
 
-    // Set the fadeout animation on death
-    if player_was_alive && !state.player.alive() {
         use crate::player::CauseOfDeath::*;
         let cause_of_death = formula::cause_of_death(&state.player);
         let fade_color = if cfg!(feature = "recording") {
@@ -539,8 +540,300 @@ fn process_game(
     RunningState::Running
 }
 
+/// Process one action point of the player and then one of all the
+/// monsters in the simulation area.
+///
+/// Returns `true` when everyone has run out of action points and the
+/// entire turn has ended.
+pub fn process_actions(state: &mut State, simulation_area: Rectangle) -> bool {
+    let monster_count = state.world.monsters(simulation_area).count();
+    let monster_with_ap_count = state
+        .world
+        .monsters(simulation_area)
+        .filter(|m| m.has_ap(1))
+        .count();
+    let monster_cumulative_ap: i32 = state
+        .world
+        .monsters(simulation_area)
+        .map(|m| m.ap.to_int())
+        .sum();
+    log::debug!(
+        "Player AP: {}, monsters: {}, active mon: {}, total mon AP: {}",
+        state.player.ap(),
+        monster_count,
+        monster_with_ap_count,
+        monster_cumulative_ap
+    );
+
+    // NOTE: Process 1 action point of the player and then 1 AP of
+    // all monsters. This means that their turns will alternate.
+    // E.g. if the player has 2 APs and they're close to a
+    // Depression, the player will move 1 turn first, then
+    // Depression 1 etc.
+
+    let player_ap = state.player.ap();
+    if state.player.has_ap(1) {
+        process_player(state, simulation_area);
+    }
+    let player_took_action = player_ap > state.player.ap();
+    let monsters_can_move = !state.player.has_ap(1) || player_took_action;
+
+    if state.explosion_animation.is_none() {
+        if monsters_can_move {
+            update_distance_maps(state, simulation_area);
+            let mut events = vec![];
+            process_monsters(
+                &mut state.world,
+                state
+                    .distance_maps
+                    .as_ref()
+                    .expect("The distance maps should be up to date."),
+                &mut state.player,
+                &mut state.companions,
+                simulation_area,
+                &mut state.rng,
+                &mut events,
+            );
+            for event in events {
+                state.log_event(event);
+            }
+        } else {
+            log::debug!("Monsters waiting for player.");
+        }
+    } else {
+        log::debug!("Monster's waiting for the explosion to end.");
+    }
+
+    // NOTE: the anxiety counter bar is hidden at the start, but
+    // we want to show it as soon as it increases.
+    if player_took_action && !state.player.anxiety_counter.is_min() {
+        state.show_anxiety_counter = true;
+    }
+
+    if player_took_action && state.player.mind.is_high() {
+        if let Some(victory_npc_id) = state.victory_npc_id.take() {
+            state.log_event(Event::VictoryNpcLeft);
+            let signpost = state
+                .world
+                .monster_definition_of_kind(monster::Kind::Signpost)
+                .clone();
+            if let Some(vnpc) = state.world.monster_mut(victory_npc_id) {
+                vnpc.id = signpost.id;
+                vnpc.kind = signpost.kind;
+                vnpc.color = signpost.color;
+                vnpc.behavior = signpost.behavior;
+                vnpc.ai_state = ai::AIState::NoOp
+            }
+        }
+    }
+
+    // Reset all action points only after everyone is at zero:
+    let player_turn_ended = !state.player.has_ap(1);
+    let monster_turn_ended = state
+        .world
+        .monsters(simulation_area)
+        .filter(|m| m.has_ap(1))
+        .count()
+        == 0;
+
+    player_turn_ended && monster_turn_ended
+}
+
+/// Wrap up the turn once everyone has spent their action points:
+/// check or log the verification, reset the action points and start
+/// the next turn.
+pub fn end_turn(state: &mut State, simulation_area: Rectangle, player_was_alive: bool) {
+    // Log or check verifications
+    if state.replay {
+        if let Some(expected) = state.verifications.pop_front() {
+            let actual = state.verification();
+            let mut divergences = replay::compare(&expected, &actual);
+
+            if player_was_alive && !state.player.alive() && !state.commands.is_empty() {
+                divergences.push(replay::Divergence::new(
+                    "queued commands after death",
+                    0,
+                    state.commands.len(),
+                ));
+            }
+
+            if !divergences.is_empty() {
+                replay::report_desync(state, &divergences);
+                state.paused = true;
+            }
+        } else {
+            // NOTE: no verifications were loaded. Probably
+            // replaying a release build.
+        }
+    } else if cfg!(feature = "verifications") {
+        let verification = state.verification();
+        replay::log_verification(&mut state.command_logger, &verification);
+    }
+
+    slow_monsters_near_companions(state, simulation_area);
+
+    // Reset the player & monster action points
+    // NOTE: doing this only after we've logged the validations. Actually maybe we want to do this
+    // before we start turn processing??
+    log::debug!("Starting new turn for player and monsters.");
+    state.player.new_turn();
+    for monster in state.world.monsters_mut(simulation_area) {
+        monster.new_turn();
+    }
+
+    log::debug!("Turn {} has ended.", state.turn);
+    state.turn += 1;
+
+    // NOTE: Load up new chunks if necessary
+    for pos in simulation_area.points() {
+        state.world.ensure_chunk_at_pos(pos);
+    }
+
+    if state.replay && state.turn % replay::SNAPSHOT_INTERVAL == 0 {
+        replay::save_snapshot(state);
+    }
+}
+
+/// Run one step of the game simulation without any rendering or
+/// input processing. The player's commands must already be queued
+/// up.
+///
+/// Any explosion animation is skipped. Its effects were applied when
+/// it was set off.
+pub fn simulate_step(state: &mut State) {
+    let player_was_alive = state.player.alive();
+    let simulation_area = Rectangle::center(state.player.pos, state.map_size);
+
+    if process_actions(state, simulation_area) {
+        end_turn(state, simulation_area, player_was_alive);
+    }
+
+    state.explosion_animation = None;
+}
+
+/// Move the replay to the start of the given turn without rendering
+/// the turns in between. Going back restores the nearest earlier
+/// snapshot and plays forward from there.
+pub fn seek_replay(state: &mut State, turn: i32) {
+    let turn = cmp::max(turn, 0);
+    if turn < state.turn && !replay::restore_snapshot(state, turn) {
+        log::warn!("Could not find a replay snapshot before turn {}.", turn);
+        return;
+    }
+
+    while state.turn < turn
+        && state.player.alive()
+        && state.side != Side::Victory
+        && !(state.player.has_ap(1) && state.commands.is_empty())
+    {
+        simulate_step(state);
+    }
+    log::info!("Replay moved to turn {}.", state.turn);
+
+    // NOTE: centre the screen on the player without any scrolling
+    state.explosion_animation = None;
+    state.screen_position_in_world = state.player.pos;
+    state.old_screen_pos = state.player.pos;
+    state.new_screen_pos = state.player.pos;
+    state.pos_timer = Timer::new(Duration::from_millis(0));
+    state.offset_px = Point::zero();
+}
+
+/// The monsters slowed by companions (e.g. Depression) lose an action
+/// point in the next turn when they're close to the player's
+/// companion.
+fn slow_monsters_near_companions(state: &mut State, simulation_area: Rectangle) {
+    let companions = state
+        .world
+        .monsters(simulation_area)
+        .filter(|m| m.accompanying_player)
+        .map(|m| m.position)
+        .collect::<Vec<_>>();
+    if companions.is_empty() {
+        return;
+    }
+    let slowed_ids = state
+        .world
+        .monster_definitions
+        .iter()
+        .filter(|definition| definition.slowed_by_companions)
+        .map(|definition| definition.id.clone())
+        .collect::<Vec<_>>();
+    let slowed_monsters = state
+        .world
+        .monsters_mut(simulation_area)
+        .filter(|m| slowed_ids.contains(&m.id));
+    for monster in slowed_monsters {
+        let near_companion = companions
+            .iter()
+            .any(|&pos| pos.tile_distance(monster.position) <= formula::COMPANION_SLOW_DISTANCE);
+        if near_companion {
+            // NOTE: `new_turn` counts this down to the one turn
+            // the monster is slowed for.
+            monster.status.add(status::Kind::Slow, 2);
+        }
+    }
+}
+
+/// Start the `explosion` animation and apply all its effects right
+/// away. The animation is only for show: the game must turn out the
+/// same no matter how long it plays (e.g. in the replays).
+fn set_off_explosion(
+    world: &mut World,
+    player_pos: Point,
+    explosion_animation: &mut Option<Box<dyn AreaOfEffect>>,
+    explosion: Box<dyn AreaOfEffect>,
+) {
+    apply_explosion_effects(world, player_pos, &*explosion);
+    *explosion_animation = Some(explosion);
+}
+
+/// Run the explosion effects on all the tiles the `explosion` covers
+/// from its start to its end.
+fn apply_explosion_effects(world: &mut World, player_pos: Point, explosion: &dyn AreaOfEffect) {
+    for (pos, effect) in explosion.all_tiles() {
+        if effect.contains(animation::TileEffect::KILL) {
+            kill_monster(pos, world);
+        }
+        if effect.contains(animation::TileEffect::SHATTER) {
+            if let Some(cell) = world.cell_mut(pos) {
+                if cell.tile.kind.is_wall() {
+                    cell.tile = Tile::new(TileKind::Rubble);
+                }
+                cell.items.clear();
+            }
+        }
+        if effect.contains(animation::TileEffect::PLANT_TREE) {
+            let occupied = pos == player_pos || world.monster_on_pos(pos).is_some();
+            if let Some(cell) = world.cell_mut(pos) {
+                if !occupied && cell.tile.kind == TileKind::Empty && cell.items.is_empty() {
+                    cell.tile = Tile::new(TileKind::Tree);
+                }
+            }
+        }
+        if effect.contains(animation::TileEffect::REVEAL) {
+            if let Some(cell) = world.cell_mut(pos) {
+                cell.explored = true;
+            }
+        }
+        if effect.contains(animation::TileEffect::STUN) {
+            if let Some(monster) = world.monster_on_pos(pos) {
+                // NOTE: the tiles can repeat, the stun must not
+                // stack up.
+                if !monster.status.has(status::Kind::Stun) {
+                    monster
+                        .status
+                        .add(status::Kind::Stun, formula::EXPLOSION_STUN_TURNS);
+                    monster.ap.set_to_min();
+                }
+            }
+        }
+    }
+}
+
 fn process_main_menu(
     state: &mut State,
+    settings: &Settings,
     window: &main_menu::Window,
     metrics: &dyn TextMetrics,
 ) -> RunningState {
@@ -558,6 +851,8 @@ fn process_main_menu(
             || state.mouse.right_clicked
         {
             option = Some(Resume);
+        } else if state.keys.matches_code(KeyCode::C) {
+            option = Some(Continue);
         } else if state.keys.matches_code(KeyCode::N) {
             option = Some(NewGame);
         } else if state.keys.matches_code(KeyCode::QuestionMark)
@@ -582,6 +877,16 @@ fn process_main_menu(
                 return RunningState::Running;
             }
 
+            Continue => {
+                match savegame::latest_autosave() {
+                    Some(slot) => load_saved_game(state, &slot.name, settings),
+                    None => state
+                        .window_stack
+                        .push(window::message_box("There is no game to continue.")),
+                }
+                return RunningState::Running;
+            }
+
             NewGame => {
                 // NOTE: When this is the first run, we resume the
                 // game that's already loaded in the background.
@@ -590,7 +895,7 @@ fn process_main_menu(
                     state.first_game_already_generated = false;
                     return RunningState::Running;
                 } else {
-                    return RunningState::NewGame(Box::new(create_new_game_state(state)));
+                    return RunningState::NewGame(Box::new(create_new_game_state(state, settings)));
                 }
             }
 
@@ -606,7 +911,11 @@ fn process_main_menu(
 
             SaveAndQuit => {
                 if !state.game_ended {
-                    match state.save_to_file() {
+                    let slot = state
+                        .save_slot
+                        .clone()
+                        .unwrap_or_else(savegame::new_slot_name);
+                    match savegame::save(state, &slot) {
                         Ok(()) => return RunningState::Stopped,
                         Err(error) => {
                             // NOTE: we couldn't save the game so we'll keep going
@@ -620,22 +929,12 @@ fn process_main_menu(
                 return RunningState::Running;
             }
 
-            Load => match State::load_from_file() {
-                Ok(new_state) => {
-                    *state = new_state;
-                    if state.window_stack.top() == Window::MainMenu {
-                        state.window_stack.pop();
-                    }
-                    return RunningState::Running;
-                }
-                Err(error) => {
-                    log::error!("Error loading the game: {:?}", error);
-                    state
-                        .window_stack
-                        .push(window::message_box("Error: could not load the game."));
-                    return RunningState::Running;
-                }
-            },
+            Load => {
+                state.save_slots = savegame::list_slots();
+                state.selected_save_slot = 0;
+                state.window_stack.push(Window::LoadGame);
+                return RunningState::Running;
+            }
 
             Quit => {
                 return RunningState::Stopped;
@@ -670,6 +969,20 @@ fn process_settings_window(
             option = Some(Fullscreen);
         } else if state.keys.matches_code(KeyCode::W) {
             option = Some(Window);
+        } else if state.keys.matches_code(KeyCode::D) {
+            option = Some(Permadeath);
+        } else if state.keys.matches_code(KeyCode::K) {
+            option = Some(KeepSaves);
+        } else if state.keys.matches_code(KeyCode::B) {
+            option = crate::settings::next_backend(&settings.backend).map(Backend);
+        } else if state.keys.matches_code(KeyCode::P) {
+            option = Some(Palette(settings.palette.next()));
+        } else if state.keys.matches_code(KeyCode::R) {
+            option = Some(RecordReplays);
+        } else if state.keys.matches_code(KeyCode::O) {
+            option = Some(NoReplays);
+        } else if state.keys.matches_code(KeyCode::C) {
+            option = Some(Controls);
         }
     }
 
@@ -709,6 +1022,36 @@ fn process_settings_window(
                 settings.font_size = font_size;
             }
 
+            Permadeath => {
+                settings.permadeath = true;
+            }
+
+            KeepSaves => {
+                settings.permadeath = false;
+            }
+
+            Backend(backend) => {
+                settings.backend = backend.into();
+            }
+
+            Palette(palette) => {
+                settings.palette = palette;
+            }
+
+            RecordReplays => {
+                settings.record_replays = true;
+            }
+
+            NoReplays => {
+                settings.record_replays = false;
+            }
+
+            Controls => {
+                state.selected_command = 0;
+                state.capturing_key = false;
+                state.window_stack.push(window::Window::Controls);
+            }
+
             Back => {
                 state.window_stack.pop();
             }
@@ -767,12 +1110,50 @@ fn process_help_window(
     RunningState::Running
 }
 
-fn process_endgame_window(
+fn load_saved_game(state: &mut State, name: &str, settings: &Settings) {
+    match savegame::load(name, settings.permadeath, settings.record_replays) {
+        Ok(new_state) => {
+            *state = new_state;
+            if state.window_stack.top() == Window::MainMenu {
+                state.window_stack.pop();
+            }
+        }
+        Err(error) => {
+            log::error!("Error loading the game: {:?}", error);
+            state
+                .window_stack
+                .push(window::message_box("Error: could not load the game."));
+        }
+    }
+}
+
+/// The game window is being closed. Save the game so the player can
+/// continue where they left off.
+///
+/// NOTE: only the window backends have a window to close.
+#[cfg(any(feature = "glutin-backend", feature = "sdl-backend"))]
+pub fn autosave_on_exit(state: &mut State) {
+    if !state.replay
+        && !state.game_ended
+        && !state.first_game_already_generated
+        && state.player.alive()
+    {
+        savegame::autosave(state);
+    }
+}
+
+fn process_load_game_window(
     state: &mut State,
-    window: &endgame::Window,
+    settings: &Settings,
+    window: &load_game::Window,
     metrics: &dyn TextMetrics,
 ) -> RunningState {
-    use crate::windows::endgame::Action::*;
+    use crate::windows::load_game::Action;
+
+    if state.keys.matches_code(KeyCode::Esc) || state.mouse.right_clicked {
+        state.window_stack.pop();
+        return RunningState::Running;
+    }
 
     let mut action = if state.mouse.left_clicked {
         window.hovered(&state, metrics)
@@ -780,51 +1161,426 @@ fn process_endgame_window(
         None
     };
 
+    let slot_count = state.save_slots.len();
+    if action.is_none() && slot_count > 0 {
+        if state.keys.matches_code(KeyCode::Up) {
+            state.selected_save_slot = state.selected_save_slot.saturating_sub(1);
+        } else if state.keys.matches_code(KeyCode::Down) {
+            state.selected_save_slot = cmp::min(state.selected_save_slot + 1, slot_count - 1);
+        } else if state.keys.matches_code(KeyCode::Enter) {
+            action = Some(Action::Load(state.selected_save_slot));
+        } else if state.keys.matches_code(KeyCode::D) {
+            action = Some(Action::Delete(state.selected_save_slot));
+        }
+    }
+
     if action.is_none() {
-        if state.keys.matches_code(KeyCode::N) {
-            action = Some(NewGame);
-        } else if state.keys.matches_code(KeyCode::Esc) {
-            action = Some(Menu);
-        } else if state.keys.matches_code(KeyCode::QuestionMark)
-            || state.keys.matches_code(KeyCode::H)
-        {
-            action = Some(Help);
+        let codes = [
+            KeyCode::D1,
+            KeyCode::D2,
+            KeyCode::D3,
+            KeyCode::D4,
+            KeyCode::D5,
+            KeyCode::D6,
+            KeyCode::D7,
+            KeyCode::D8,
+            KeyCode::D9,
+        ];
+        for (index, &code) in codes.iter().enumerate().take(slot_count) {
+            if state.keys.matches_code(code) {
+                action = Some(Action::Load(index));
+            }
         }
     }
 
     match action {
-        Some(NewGame) => RunningState::NewGame(Box::new(create_new_game_state(state))),
-        Some(Menu) => {
-            state.window_stack.push(Window::MainMenu);
-            RunningState::Running
-        }
-        Some(Help) => {
-            state.window_stack.push(Window::Help);
-            RunningState::Running
+        Some(Action::Load(index)) => {
+            let name = state.save_slots[index].name.clone();
+            load_saved_game(state, &name, settings);
         }
-        None => {
-            if state.keys.get().is_some() || state.mouse.right_clicked {
-                state.window_stack.pop();
+
+        Some(Action::Delete(index)) => {
+            let name = state.save_slots[index].name.clone();
+            if let Err(error) = savegame::delete(&name) {
+                log::error!("Error deleting the saved game {}: {:?}", name, error);
             }
-            RunningState::Running
+            state.save_slots = savegame::list_slots();
+            state.selected_save_slot = cmp::min(
+                state.selected_save_slot,
+                state.save_slots.len().saturating_sub(1),
+            );
+        }
+
+        Some(Action::Back) => {
+            state.window_stack.pop();
         }
+
+        None => {}
     }
+
+    RunningState::Running
 }
 
-fn process_message_window(state: &mut State) -> RunningState {
-    if state.keys.get().is_some() || state.mouse.left_clicked || state.mouse.right_clicked {
+fn process_controls_window(
+    state: &mut State,
+    settings: &mut Settings,
+    window: &controls::Window,
+    metrics: &dyn TextMetrics,
+) -> RunningState {
+    use crate::windows::controls::Action;
+
+    let actions = keymap::actions();
+
+    // NOTE: bind the first key pressed while capturing. Esc cancels.
+    if state.capturing_key {
+        if let Some(key) = state.keys.get() {
+            if key.code != KeyCode::Esc {
+                settings.keymap.bind(&actions[state.selected_command], key);
+            }
+            state.capturing_key = false;
+        } else if state.mouse.right_clicked {
+            state.capturing_key = false;
+        }
+        return RunningState::Running;
+    }
+
+    if state.keys.matches_code(KeyCode::Esc) || state.mouse.right_clicked {
         state.window_stack.pop();
         return RunningState::Running;
     }
 
-    RunningState::Running
-}
+    let mut action = if state.mouse.left_clicked {
+        window.hovered(&state, settings, metrics)
+    } else {
+        None
+    };
 
-fn process_monsters<R: Rng>(
-    world: &mut World,
+    if action.is_none() {
+        if state.keys.matches_code(KeyCode::Up) {
+            state.selected_command = state.selected_command.saturating_sub(1);
+        } else if state.keys.matches_code(KeyCode::Down) {
+            state.selected_command = cmp::min(state.selected_command + 1, actions.len() - 1);
+        } else if state.keys.matches_code(KeyCode::Enter) {
+            action = Some(Action::Capture(state.selected_command));
+        } else if state.keys.matches_code(KeyCode::C) {
+            action = Some(Action::Clear(state.selected_command));
+        } else if state.keys.matches_code(KeyCode::R) {
+            action = Some(Action::Preset(settings.keymap.preset));
+        } else if state.keys.matches_code(KeyCode::P) {
+            action = Some(Action::Preset(settings.keymap.preset.next()));
+        }
+    }
+
+    match action {
+        Some(Action::Capture(index)) => {
+            state.selected_command = index;
+            state.capturing_key = true;
+        }
+        Some(Action::Clear(index)) => {
+            settings.keymap.set_keys(&actions[index], vec![]);
+        }
+        Some(Action::Preset(preset)) => {
+            settings.keymap = Keymap::from_preset(preset);
+        }
+        Some(Action::Back) => {
+            state.window_stack.pop();
+        }
+        None => {}
+    }
+
+    RunningState::Running
+}
+
+fn process_endgame_window(
+    state: &mut State,
+    settings: &Settings,
+    window: &endgame::Window,
+    metrics: &dyn TextMetrics,
+) -> RunningState {
+    use crate::windows::endgame::Action::*;
+
+    let mut action = if state.mouse.left_clicked {
+        window.hovered(&state, metrics)
+    } else {
+        None
+    };
+
+    if action.is_none() {
+        if state.keys.matches_code(KeyCode::N) {
+            action = Some(NewGame);
+        } else if state.keys.matches_code(KeyCode::Esc) {
+            action = Some(Menu);
+        } else if state.keys.matches_code(KeyCode::QuestionMark)
+            || state.keys.matches_code(KeyCode::H)
+        {
+            action = Some(Help);
+        }
+    }
+
+    match action {
+        Some(NewGame) => RunningState::NewGame(Box::new(create_new_game_state(state, settings))),
+        Some(Menu) => {
+            state.window_stack.push(Window::MainMenu);
+            RunningState::Running
+        }
+        Some(Help) => {
+            state.window_stack.push(Window::Help);
+            RunningState::Running
+        }
+        None => {
+            if state.keys.get().is_some() || state.mouse.right_clicked {
+                state.window_stack.pop();
+            }
+            RunningState::Running
+        }
+    }
+}
+
+fn process_message_window(state: &mut State) -> RunningState {
+    if state.keys.get().is_some() || state.mouse.left_clicked || state.mouse.right_clicked {
+        state.window_stack.pop();
+        return RunningState::Running;
+    }
+
+    RunningState::Running
+}
+
+fn process_message_log_window(
+    state: &mut State,
+    window: &message_log::Window,
+    metrics: &dyn TextMetrics,
+) -> RunningState {
+    use self::message_log::Action;
+
+    let mut action = if state.mouse.left_clicked {
+        window.hovered(&state, metrics)
+    } else {
+        None
+    };
+
+    if action.is_none() {
+        if state.keys.matches_code(KeyCode::Esc)
+            || state.keys.matches_code(KeyCode::M)
+            || state.mouse.right_clicked
+        {
+            action = Some(Action::Back);
+        } else if state.keys.matches_code(KeyCode::Up) {
+            action = Some(Action::Older);
+        } else if state.keys.matches_code(KeyCode::Down) {
+            action = Some(Action::Newer);
+        }
+    }
+
+    match action {
+        Some(Action::Older) => {
+            let last = state.messages.len().saturating_sub(1);
+            state.message_log_scroll = cmp::min(state.message_log_scroll + 1, last);
+        }
+        Some(Action::Newer) => {
+            state.message_log_scroll = state.message_log_scroll.saturating_sub(1);
+        }
+        Some(Action::Back) => {
+            state.window_stack.pop();
+        }
+        None => {}
+    }
+
+    RunningState::Running
+}
+
+fn process_look_window(state: &mut State, settings: &Settings) -> RunningState {
+    if state.keys.matches_code(KeyCode::Esc)
+        || state.keys.matches_code(KeyCode::Enter)
+        || state.mouse.right_clicked
+    {
+        state.window_stack.pop();
+        return RunningState::Running;
+    }
+
+    follow_mouse_with_cursor(state);
+    while let Some(key) = state.keys.get() {
+        let direction = settings
+            .keymap
+            .command(key)
+            .and_then(|command| command.direction());
+        if let Some(direction) = direction {
+            move_cursor(state, direction);
+        }
+    }
+
+    RunningState::Running
+}
+
+/// Move the cursor of the look and throw windows to the tile under
+/// the mouse.
+///
+/// NOTE: the cursor follows the mouse only when it moves so it
+/// doesn't undo the keyboard movement.
+fn follow_mouse_with_cursor(state: &mut State) {
+    if state.mouse.tile_pos != state.cursor_mouse_pos {
+        state.cursor_mouse_pos = state.mouse.tile_pos;
+        let screen_left_top_corner = state.screen_position_in_world - (state.map_size / 2);
+        let map_area = Rectangle::from_point_and_size(screen_left_top_corner, state.map_size);
+        let pos = state.mouse.tile_pos + screen_left_top_corner;
+        if map_area.contains(pos) {
+            state.cursor = pos;
+        }
+    }
+}
+
+/// Move the cursor of the look and throw windows by one tile. It
+/// never leaves the displayed map.
+fn move_cursor(state: &mut State, direction: Point) {
+    let screen_left_top_corner = state.screen_position_in_world - (state.map_size / 2);
+    let map_area = Rectangle::from_point_and_size(screen_left_top_corner, state.map_size);
+    let pos = state.cursor + direction;
+    if map_area.contains(pos) {
+        state.cursor = pos;
+    }
+}
+
+/// Whether the player can drop or throw their items right now.
+fn can_handle_items(state: &State) -> bool {
+    !state.replay && !state.game_ended && state.player.alive() && state.side != Side::Victory
+}
+
+/// The item picked by clicking on it in the sidebar or pressing its
+/// key. Only the items the player carries count.
+fn selected_item(
+    state: &mut State,
+    keymap: &Keymap,
+    window: &sidebar::Window,
+    metrics: &dyn TextMetrics,
+) -> Option<String> {
+    let mut result = if state.mouse.left_clicked {
+        window
+            .hovered(&state, keymap, metrics)
+            .and_then(|action| action.item().map(String::from))
+    } else {
+        None
+    };
+    while let Some(key) = state.keys.get() {
+        if let Some(definition) = keymap::item_bound_to(&state.world.item_definitions, key) {
+            result = Some(definition.id.clone());
+        }
+    }
+    result.filter(|id| state.player.inventory.iter().any(|i| i.id == *id))
+}
+
+fn process_drop_window(
+    state: &mut State,
+    settings: &Settings,
+    window: &sidebar::Window,
+    metrics: &dyn TextMetrics,
+) -> RunningState {
+    if state.keys.matches_code(KeyCode::Esc)
+        || pressed(&mut state.keys, &settings.keymap, keymap::Action::Drop)
+        || state.mouse.right_clicked
+    {
+        state.window_stack.pop();
+        return RunningState::Running;
+    }
+
+    if let Some(id) = selected_item(state, &settings.keymap, window, metrics) {
+        state.commands.push_back(Command::Drop(id));
+        state.window_stack.pop();
+    }
+
+    RunningState::Running
+}
+
+fn process_throw_window(
+    state: &mut State,
+    settings: &Settings,
+    window: &sidebar::Window,
+    metrics: &dyn TextMetrics,
+) -> RunningState {
+    if state.keys.matches_code(KeyCode::Esc)
+        || pressed(&mut state.keys, &settings.keymap, keymap::Action::Throw)
+        || state.mouse.right_clicked
+    {
+        state.window_stack.pop();
+        return RunningState::Running;
+    }
+
+    follow_mouse_with_cursor(state);
+
+    let carried_dose = |state: &State, id: &str| {
+        state
+            .player
+            .inventory
+            .iter()
+            .any(|i| i.id == id && i.is_dose())
+    };
+
+    let mut throw = state.keys.matches_code(KeyCode::Enter);
+    if state.mouse.left_clicked {
+        match window
+            .hovered(&state, &settings.keymap, metrics)
+            .and_then(|action| action.item().map(String::from))
+        {
+            Some(id) if carried_dose(state, &id) => state.throw_item = Some(id),
+            Some(_) => {}
+            None => {
+                let map_rect = Rectangle::from_point_and_size(Point::zero(), state.map_size);
+                throw = map_rect.contains(state.mouse.tile_pos);
+            }
+        }
+    }
+
+    // NOTE: the movement keys move the cursor, the item keys pick
+    // a different dose
+    while let Some(key) = state.keys.get() {
+        if let Some(direction) = settings.keymap.command(key).and_then(|c| c.direction()) {
+            move_cursor(state, direction);
+        } else if let Some(definition) = keymap::item_bound_to(&state.world.item_definitions, key) {
+            let id = definition.id.clone();
+            if carried_dose(state, &id) {
+                state.throw_item = Some(id);
+            }
+        }
+    }
+
+    if let Some(id) = state.throw_item.clone() {
+        if throw && can_throw(&state.world, state.player.pos, state.cursor) {
+            state.commands.push_back(Command::Throw {
+                item: id,
+                target: state.cursor,
+            });
+            state.window_stack.pop();
+        }
+    }
+
+    RunningState::Running
+}
+
+/// Rebuild the distance maps when a new turn starts, when the player
+/// moves or their Will changes or when the world changes.
+fn update_distance_maps(state: &mut State, simulation_area: Rectangle) {
+    let (turn, pos, will) = (state.turn, state.player.pos, state.player.will.to_int());
+    let current = state
+        .distance_maps
+        .as_ref()
+        .map_or(false, |maps| maps.is_current(&state.world, turn, pos, will));
+    if !current {
+        state.distance_maps = Some(DistanceMaps::new(
+            &state.world,
+            simulation_area,
+            turn,
+            pos,
+            will,
+        ));
+    }
+}
+
+fn process_monsters<R: Rng>(
+    world: &mut World,
+    distance_maps: &DistanceMaps,
     player: &mut player::Player,
+    companions: &mut [Companion],
     area: Rectangle,
     rng: &mut R,
+    events: &mut Vec<Event>,
 ) {
     if !player.alive() {
         return;
@@ -844,6 +1600,7 @@ fn process_monsters<R: Rng>(
     // the same ordering.
     monster_positions_vec.sort_by_key(|&(ap, pos)| (ap, pos.x, pos.y));
     let mut monster_positions_to_process: VecDeque<_> = monster_positions_vec.into();
+    let mut search = pathfinding::SearchContext::default();
 
     while let Some((_, monster_position)) = monster_positions_to_process.pop_front() {
         let monster_readonly = world
@@ -851,7 +1608,7 @@ fn process_monsters<R: Rng>(
             .expect("Monster should exist on this position")
             .clone();
         let action = {
-            let (update, action) = monster_readonly.act(player.info(), world, rng);
+            let (update, action) = monster_readonly.act(player.info(), world, distance_maps, rng);
             if let Some(monster) = world.monster_on_pos(monster_position) {
                 monster.ai_state = update.ai_state;
                 monster.ap = Ranged::new(
@@ -888,7 +1645,9 @@ fn process_monsters<R: Rng>(
                         player.pos,
                     ) {
                     // Calculate a new path or recalculate the existing one.
-                    let mut path = pathfinding::Path::find(
+                    // NOTE: a path that only gets the monster closer
+                    // is better than standing still.
+                    let mut path = search.find_partial(
                         pos,
                         destination,
                         world,
@@ -904,18 +1663,39 @@ fn process_monsters<R: Rng>(
                     (monster_readonly.path[0], monster_readonly.path[1..].into())
                 };
 
+                let id = world.monster_id(pos);
                 world.move_monster(pos, newpos, player.pos);
+                // NOTE: the monster gets a new ID when it moves to
+                // another chunk.
+                if let Some(companion) = companions.iter_mut().find(|c| Some(c.id) == id) {
+                    if let Some(new_id) = world.monster_id(newpos) {
+                        companion.id = new_id;
+                    }
+                }
+                let wading = newpos != pos
+                    && world.cell(newpos).map(|cell| cell.tile.kind)
+                        == Some(TileKind::ShallowWater);
                 if let Some(monster) = world.monster_on_pos(newpos) {
                     monster.path = newpath;
                     if monster.has_ap(1) {
                         monster.trail = Some(newpos);
                     }
+                    if wading {
+                        // NOTE: `new_turn` counts this down to the one
+                        // turn the monster is slowed for.
+                        monster.status.add(status::Kind::Slow, 2);
+                    }
                 }
             }
 
             Action::Attack(target_pos, damage) => {
                 assert!(target_pos == player.pos);
                 player.take_effect(damage);
+                match damage {
+                    player::Modifier::Stun(turns) => events.push(Event::Stunned(turns)),
+                    player::Modifier::Panic(turns) => events.push(Event::Panicked(turns)),
+                    _ => {}
+                }
                 if monster_readonly.die_after_attack {
                     kill_monster(monster_readonly.position, world);
                 }
@@ -926,7 +1706,7 @@ fn process_monsters<R: Rng>(
                 }
             }
 
-            Action::Use(_) => unreachable!(),
+            Action::Use(_) | Action::Drop(_) | Action::Throw(..) => unreachable!(),
         }
     }
 }
@@ -936,11 +1716,13 @@ fn process_player_action<R, W>(
     player: &mut player::Player,
     commands: &mut VecDeque<Command>,
     world: &mut World,
-    simulation_area: Rectangle,
+    distance_maps: &DistanceMaps,
+    companions: &mut Vec<Companion>,
     explosion_animation: &mut Option<Box<dyn AreaOfEffect>>,
     rng: &mut R,
     command_logger: &mut W,
     window_stack: &mut crate::windows::Windows<Window>,
+    events: &mut Vec<Event>,
 ) where
     R: Rng,
     W: Write,
@@ -957,7 +1739,7 @@ fn process_player_action<R, W>(
         return;
     }
     if let Some(command) = commands.pop_front() {
-        state::log_command(command_logger, command.clone());
+        replay::log_command(command_logger, command.clone());
         let mut action = match command {
             Command::N => Action::Move(player.pos + (0, -1)),
             Command::S => Action::Move(player.pos + (0, 1)),
@@ -969,47 +1751,48 @@ fn process_player_action<R, W>(
             Command::SW => Action::Move(player.pos + (-1, 1)),
             Command::SE => Action::Move(player.pos + (1, 1)),
 
-            Command::UseFood => Action::Use(item::Kind::Food),
-            Command::UseDose => Action::Use(item::Kind::Dose),
-            Command::UseCardinalDose => Action::Use(item::Kind::CardinalDose),
-            Command::UseDiagonalDose => Action::Use(item::Kind::DiagonalDose),
-            Command::UseStrongDose => Action::Use(item::Kind::StrongDose),
+            Command::Use(id) => Action::Use(id),
 
             Command::ShowMessageBox { ttl, message } => {
                 window_stack.push(window::timed_message_box(message, ttl));
                 return;
             }
+
+            Command::Drop(id) => Action::Drop(id),
+            Command::Throw { item, target } => Action::Throw(item, target),
+
+            Command::Explore => match travel::explore_step(world, player) {
+                Ok(pos) => Action::Move(pos),
+                Err(stop) => {
+                    log::debug!("Can't explore: {:?}", stop);
+                    return;
+                }
+            },
         };
 
-        if player.stun.to_int() > 0 {
+        if player.status.has(status::Kind::Stun) {
             action = Action::Move(player.pos);
-        } else if player.panic.to_int() > 0 {
-            let new_pos =
-                world.random_neighbour_position(rng, player.pos, Blocker::WALL, player.pos);
+        } else if player.status.has(status::Kind::Panic) {
+            // NOTE: run from the monsters or stumble about when
+            // there's nowhere to run
+            let blockers = Blocker::WALL | Blocker::MONSTER;
+            let new_pos = distance_maps
+                .flee()
+                .downhill(player.pos, |pos| world.walkable(pos, blockers, player.pos))
+                .unwrap_or_else(|| {
+                    world.random_neighbour_position(rng, player.pos, Blocker::WALL, player.pos)
+                });
             action = Action::Move(new_pos);
-        } else if let Some((dose_pos, dose)) = world.nearest_dose(player.pos, 5) {
-            let resist_radius =
-                formula::player_resist_radius(dose.irresistible, player.will.to_int()) as usize;
-            if player.pos.tile_distance(dose_pos) < resist_radius as i32 {
-                let mut path = pathfinding::Path::find(
-                    player.pos,
-                    dose_pos,
-                    world,
-                    Blocker::WALL,
-                    player.pos,
-                    formula::PATHFINDING_DOSE_RESIST_LIMIT,
-                );
-
-                let new_pos_opt = if path.len() <= resist_radius {
-                    path.next()
-                } else {
-                    None
-                };
-
-                if let Some(new_pos) = new_pos_opt {
+        } else {
+            // NOTE: the dose map is below zero within the
+            // irresistible area of any dose
+            let doses = distance_maps.doses();
+            let craving = doses
+                .distance(player.pos)
+                .map_or(false, |distance| distance < 0);
+            if craving {
+                if let Some(new_pos) = doses.downhill(player.pos, |_| true) {
                     action = Action::Move(new_pos);
-                } else {
-                    // NOTE: no path leading to the irresistable dose
                 }
             }
         }
@@ -1023,9 +1806,9 @@ fn process_player_action<R, W>(
                 i.is_dose()
                     && formula::player_resist_radius(i.irresistible, player.will.to_int()) > 0
             })
-            .map(|i| i.kind);
-        if let Some(kind) = carried_irresistible_dose {
-            action = Action::Use(kind);
+            .map(|i| i.id.clone());
+        if let Some(id) = carried_irresistible_dose {
+            action = Action::Use(id);
         }
         match action {
             Action::Move(dest) => {
@@ -1035,51 +1818,35 @@ fn process_player_action<R, W>(
                 if bumping_into_monster {
                     player.spend_ap(1);
                     // info!("Player attacks {:?}", monster);
-                    if let Some(kind) = world.monster_on_pos(dest).map(|m| m.kind) {
-                        match kind {
-                            monster::Kind::Anxiety => {
-                                log::debug!(
-                                    "Bumped into anxiety! Current anxiety counter: {:?}",
-                                    player.anxiety_counter
-                                );
-                                let increment =
-                                    if player.bonuses.contains(&CompanionBonus::DoubleWillGrowth) {
-                                        2
-                                    } else {
-                                        1
-                                    };
-                                log::debug!("Anxiety increment: {:?}", increment);
-                                player.anxiety_counter += increment;
-                                log::debug!("New anxiety counter: {:?}", player.anxiety_counter);
-                                if player.anxiety_counter.is_max() {
-                                    log::info!("Increasing player's will");
-                                    player.will += 1;
-                                    player.anxiety_counter.set_to_min();
-                                }
+                    let bumped = world.monster_on_pos(dest).map(|m| (m.id.clone(), m.kind));
+                    if let Some((id, kind)) = bumped {
+                        if world.monster_definition(&id).grows_will {
+                            log::debug!(
+                                "Bumped into {}! Current anxiety counter: {:?}",
+                                id,
+                                player.anxiety_counter
+                            );
+                            let increment = formula::anxiety_increment(&player.bonuses);
+                            log::debug!("Anxiety increment: {:?}", increment);
+                            player.anxiety_counter += increment;
+                            log::debug!("New anxiety counter: {:?}", player.anxiety_counter);
+                            if player.anxiety_counter.is_max() {
+                                player.will += 1;
+                                player.anxiety_counter.set_to_min();
+                                events.push(Event::WillIncreased(player.will.to_int()));
                             }
+                        }
+                        match kind {
                             // NOTE: NPCs don't give bonuses or accompany the player when high.
                             monster::Kind::Npc if player.mind.is_sober() => {
                                 if let Some(monster) = world.monster_on_pos(dest) {
                                     log::info!("Bumped into NPC: {}", monster);
                                 }
-
-                                // Clear any existing monsters accompanying the player. The player
-                                // can have only one companion at a time right now.
-                                //
-                                // TODO: it also sounds like we could just track the followers in
-                                // the Player/State struct but that needs Monster IDs.
-                                let npcs = world
-                                    .monsters_mut(simulation_area)
-                                    .filter(|m| m.kind == monster::Kind::Npc);
-                                for npc in npcs {
-                                    if npc.position == dest {
-                                        log::info!("NPC {} accompanies the player.", npc);
-                                        npc.accompanying_player = true;
-                                        assert!(npc.companion_bonus.is_some());
-                                    } else if npc.accompanying_player {
-                                        log::info!("NPC {} leaves the player.", npc);
-                                        npc.accompanying_player = false;
-                                    }
+                                if let Some(line) = meet_npc(world, dest, companions, events) {
+                                    window_stack.push(window::timed_message_box(
+                                        line,
+                                        companion::LINE_DURATION,
+                                    ));
                                 }
                             }
 
@@ -1090,28 +1857,24 @@ fn process_player_action<R, W>(
                                         "\"I thought you were going to stay sober for good. I was wrong. Goodbye.\""));
                             }
 
-                            _ => {}
+                            monster::Kind::Regular | monster::Kind::Npc => {}
                         }
                         kill_monster(dest, world);
                     }
                 } else if dest_walkable {
-                    player.spend_ap(1);
+                    if dest == player.pos {
+                        player.spend_ap(1);
+                    } else {
+                        player.spend_ap_fractions(world.move_cost(dest));
+                    }
                     player.move_to(dest);
                     while let Some(item) = world.pickup_item(dest) {
-                        use crate::item::Kind::*;
-                        match item.kind {
-                            Food => player.inventory.push(item),
-                            Dose | StrongDose | CardinalDose | DiagonalDose => {
-                                let resist_radius = formula::player_resist_radius(
-                                    item.irresistible,
-                                    player.will.to_int(),
-                                );
-                                if resist_radius == 0 {
-                                    player.inventory.push(item);
-                                } else {
-                                    use_dose(player, explosion_animation, item);
-                                }
-                            }
+                        let resist_radius =
+                            formula::player_resist_radius(item.irresistible, player.will.to_int());
+                        if item.is_dose() && resist_radius > 0 {
+                            use_item(player, world, explosion_animation, item, events);
+                        } else {
+                            player.inventory.push(item);
                         }
                     }
                 } else {
@@ -1119,71 +1882,42 @@ fn process_player_action<R, W>(
                 }
             }
 
-            Action::Use(item::Kind::Food) => {
-                if let Some(food_idx) = player
-                    .inventory
-                    .iter()
-                    .position(|&i| i.kind == item::Kind::Food)
-                {
-                    player.spend_ap(1);
-                    let food = player.inventory.remove(food_idx);
-                    player.take_effect(food.modifier);
-                    let food_explosion_radius = 2;
-                    let animation = animation::SquareExplosion::new(
-                        player.pos,
-                        food_explosion_radius,
-                        1,
-                        color::explosion,
-                    );
-                    *explosion_animation = Some(Box::new(animation));
-                }
-            }
-
-            Action::Use(item::Kind::Dose) => {
-                if let Some(dose_index) = player
-                    .inventory
-                    .iter()
-                    .position(|&i| i.kind == item::Kind::Dose)
-                {
-                    player.spend_ap(1);
-                    let dose = player.inventory.remove(dose_index);
-                    use_dose(player, explosion_animation, dose);
-                }
-            }
-
-            Action::Use(item::Kind::StrongDose) => {
-                if let Some(dose_index) = player
-                    .inventory
-                    .iter()
-                    .position(|&i| i.kind == item::Kind::StrongDose)
-                {
-                    player.spend_ap(1);
-                    let dose = player.inventory.remove(dose_index);
-                    use_dose(player, explosion_animation, dose);
+            Action::Use(id) => {
+                let nauseous = player.status.has(status::Kind::Nausea);
+                if let Some(index) = player.inventory.iter().position(|i| i.id == id) {
+                    if player.inventory[index].kind == item::Kind::Food && nauseous {
+                        events.push(Event::TooNauseousToEat);
+                    } else {
+                        player.spend_ap(1);
+                        let item = player.inventory.remove(index);
+                        use_item(player, world, explosion_animation, item, events);
+                    }
                 }
             }
 
-            Action::Use(item::Kind::CardinalDose) => {
-                if let Some(dose_index) = player
-                    .inventory
-                    .iter()
-                    .position(|&i| i.kind == item::Kind::CardinalDose)
-                {
-                    player.spend_ap(1);
-                    let dose = player.inventory.remove(dose_index);
-                    use_dose(player, explosion_animation, dose);
+            Action::Drop(id) => {
+                if let Some(index) = player.inventory.iter().position(|i| i.id == id) {
+                    if let Some(cell) = world.cell_mut(player.pos) {
+                        player.spend_ap(1);
+                        let item = player.inventory.remove(index);
+                        cell.items.push(item);
+                    }
                 }
             }
 
-            Action::Use(item::Kind::DiagonalDose) => {
-                if let Some(dose_index) = player
+            Action::Throw(id, target) => {
+                let dose_index = player
                     .inventory
                     .iter()
-                    .position(|&i| i.kind == item::Kind::DiagonalDose)
-                {
-                    player.spend_ap(1);
-                    let dose = player.inventory.remove(dose_index);
-                    use_dose(player, explosion_animation, dose);
+                    .position(|i| i.id == id && i.is_dose());
+                if let Some(dose_index) = dose_index {
+                    if can_throw(world, player.pos, target) {
+                        player.spend_ap(1);
+                        let dose = player.inventory.remove(dose_index);
+                        log::debug!("Throwing {} at {}", dose.id, target);
+                        let explosion = world.item_definition(&dose.id).explosion.animation(target);
+                        set_off_explosion(world, player.pos, explosion_animation, explosion);
+                    }
                 }
             }
 
@@ -1195,47 +1929,38 @@ fn process_player_action<R, W>(
 }
 
 fn process_player(state: &mut State, simulation_area: Rectangle) {
-    {
-        // appease borrowck
-        let player = &mut state.player;
-
-        // NPCs should unfollow an intoxicated player:
-        if player.mind.is_high() {
-            let npcs = state.world.monsters_mut(simulation_area).filter(|m| {
-                m.kind == monster::Kind::Npc && m.accompanying_player && m.companion_bonus.is_some()
-            });
-            for npc in npcs {
-                log::info!("{:?} will not accompany an intoxicated player.", npc);
-                npc.accompanying_player = false;
-            }
-        }
-
-        let world = &state.world;
-
-        // NOTE: if the player manages to outrun the NPC (they follow
-        // the player but it still can happen), the bonus will
-        // disappear.
-        let npc_bonuses = world
-            .monsters(simulation_area)
-            .filter(|m| {
-                m.kind == monster::Kind::Npc && m.accompanying_player && m.companion_bonus.is_some()
-            })
-            .map(|m| m.companion_bonus.unwrap());
-        player.bonuses.clear();
-        player.bonuses.extend(npc_bonuses);
-    }
+    let mut events = vec![];
+    update_companions(state, simulation_area);
 
     let previous_action_points = state.player.ap();
+    let was_high = state.player.mind.is_high();
+    update_distance_maps(state, simulation_area);
     process_player_action(
         &mut state.player,
         &mut state.commands,
         &mut state.world,
-        simulation_area,
+        state
+            .distance_maps
+            .as_ref()
+            .expect("The distance maps should be up to date."),
+        &mut state.companions,
         &mut state.explosion_animation,
         &mut state.rng,
         &mut state.command_logger,
         &mut state.window_stack,
+        &mut events,
     );
+    if !was_high && state.player.mind.is_high() {
+        let line = companions_lose_patience(&mut state.world, &mut state.companions, &mut events);
+        if let Some(line) = line {
+            state
+                .window_stack
+                .push(window::timed_message_box(line, companion::LINE_DURATION));
+        }
+    }
+    for event in events {
+        state.log_event(event);
+    }
 
     let spent_ap_this_turn = previous_action_points > state.player.ap();
 
@@ -1272,116 +1997,143 @@ fn process_player(state: &mut State, simulation_area: Rectangle) {
     );
 }
 
-fn process_keys(keys: &mut Keys, commands: &mut VecDeque<Command>) {
-    use crate::keys::KeyCode::*;
-    while let Some(key) = keys.get() {
-        match key {
-            // Numpad (8246 for cardinal and 7193 for diagonal movement)
-            Key { code: NumPad8, .. } => commands.push_back(Command::N),
-            Key { code: NumPad2, .. } => commands.push_back(Command::S),
-            Key { code: NumPad4, .. } => commands.push_back(Command::W),
-            Key { code: NumPad6, .. } => commands.push_back(Command::E),
-            Key { code: NumPad7, .. } => commands.push_back(Command::NW),
-            Key { code: NumPad1, .. } => commands.push_back(Command::SW),
-            Key { code: NumPad9, .. } => commands.push_back(Command::NE),
-            Key { code: NumPad3, .. } => commands.push_back(Command::SE),
-
-            // NotEye (arrow keys plus Ctrl and Shift modifiers for
-            // horizontal movement)
-            Key { code: Up, .. } => commands.push_back(Command::N),
-            Key { code: Down, .. } => commands.push_back(Command::S),
-            Key {
-                code: Left,
-                ctrl: false,
-                shift: true,
-                ..
-            } => commands.push_back(Command::NW),
-            Key {
-                code: Left,
-                ctrl: true,
-                shift: false,
-                ..
-            } => commands.push_back(Command::SW),
-            Key { code: Left, .. } => commands.push_back(Command::W),
-            Key {
-                code: Right,
-                ctrl: false,
-                shift: true,
-                ..
-            } => commands.push_back(Command::NE),
-            Key {
-                code: Right,
-                ctrl: true,
-                shift: false,
-                ..
-            } => commands.push_back(Command::SE),
-            Key { code: Right, .. } => commands.push_back(Command::E),
-
-            // Vi keys (hjkl for cardinal and yubn for diagonal movement)
-            Key { code: K, .. } => commands.push_back(Command::N),
-            Key { code: J, .. } => commands.push_back(Command::S),
-            Key { code: H, .. } => commands.push_back(Command::W),
-            Key { code: L, .. } => commands.push_back(Command::E),
-            Key { code: Y, .. } => commands.push_back(Command::NW),
-            Key { code: B, .. } => commands.push_back(Command::SW),
-            Key { code: U, .. } => commands.push_back(Command::NE),
-            Key { code: N, .. } => commands.push_back(Command::SE),
-
-            // Non-movement commands
-            Key { code: E, .. } => {
-                commands.push_back(Command::UseFood);
+/// Queue up the next step of the player's travel once the previous
+/// one was processed.
+fn process_travel(state: &mut State, dt: Duration) {
+    if let Some(mut travel) = state.travel.take() {
+        travel.update(dt);
+        let waiting =
+            !state.commands.is_empty() || state.explosion_animation.is_some() || !travel.ready();
+        if waiting {
+            state.travel = Some(travel);
+            return;
+        }
+        match travel.next_command(state) {
+            Ok(command) => {
+                state.commands.push_back(command);
+                state.travel = Some(travel);
             }
+            Err(stop) => log::debug!("Travel ended: {:?}", stop),
+        }
+    }
+}
 
-            _ => {
-                if let Some(command) = inventory_commands(key) {
-                    commands.push_back(command)
-                }
+/// Take the first pressed key bound to the `action` out of the queue.
+fn pressed(keys: &mut Keys, keymap: &Keymap, action: keymap::Action) -> bool {
+    keys.matches(|key| keymap.action(key).as_ref() == Some(&action))
+}
+
+fn process_keys(
+    keys: &mut Keys,
+    commands: &mut VecDeque<Command>,
+    keymap: &Keymap,
+    item_definitions: &[item::Definition],
+) {
+    while let Some(key) = keys.get() {
+        let use_item =
+            || keymap::item_bound_to(item_definitions, key).map(|d| Command::Use(d.id.clone()));
+        if let Some(command) = keymap.command(key).or_else(use_item) {
+            commands.push_back(command);
+        }
+    }
+}
+
+/// Drop the companions who are gone. The rest follow the player and
+/// give them their bonuses unless the player is high.
+fn update_companions(state: &mut State, simulation_area: Rectangle) {
+    let world = &mut state.world;
+    state.companions.retain(|companion| {
+        world.monster(companion.id).map_or(false, |m| {
+            !m.dead && m.kind == monster::Kind::Npc && m.companion_bonus.is_some()
+        })
+    });
+
+    let player = &mut state.player;
+    let high = player.mind.is_high();
+    player.bonuses.clear();
+    for companion in &state.companions {
+        if let Some(npc) = world.monster_mut(companion.id) {
+            npc.accompanying_player = !high;
+            // NOTE: if the player manages to outrun the NPC (they
+            // follow the player but it still can happen), the bonus
+            // will disappear.
+            if !high && simulation_area.contains(npc.position) {
+                player.bonuses.push(companion.bonus);
             }
         }
     }
 }
 
-fn inventory_commands(key: Key) -> Option<Command> {
-    use crate::item::Kind;
-    use crate::keys::KeyCode::*;
-
-    for kind in Kind::iter() {
-        let num_key = match inventory_key(kind) {
-            1 => D1,
-            2 => D2,
-            3 => D3,
-            4 => D4,
-            5 => D5,
-            6 => D6,
-            7 => D7,
-            8 => D8,
-            9 => D9,
-            _ => unreachable!("There should only ever be 9 item kinds at most."),
-        };
+/// The player bumped into the NPC at `pos`. They join the player
+/// unless they're a companion already or they gave up on the player.
+/// The oldest companion leaves if there are too many. Returns what
+/// the NPC says.
+fn meet_npc(
+    world: &mut World,
+    pos: Point,
+    companions: &mut Vec<Companion>,
+    events: &mut Vec<Event>,
+) -> Option<&'static str> {
+    let id = world.monster_id(pos)?;
+    if companions.iter().any(|c| c.id == id) {
+        return Some(companion::GREETING_LINE);
+    }
+    let bonus = match world.monster(id).and_then(|m| m.companion_bonus) {
+        Some(bonus) => bonus,
+        None => return Some(companion::REFUSAL_LINE),
+    };
 
-        if key.code == num_key {
-            let command = match kind {
-                Kind::Food => Command::UseFood,
-                Kind::Dose => Command::UseDose,
-                Kind::CardinalDose => Command::UseCardinalDose,
-                Kind::DiagonalDose => Command::UseDiagonalDose,
-                Kind::StrongDose => Command::UseStrongDose,
-            };
-            return Some(command);
+    if companions.len() >= formula::MAX_COMPANIONS {
+        let oldest = companions.remove(0);
+        if let Some(npc) = world.monster_mut(oldest.id) {
+            log::info!("NPC {} leaves the player.", npc);
+            npc.accompanying_player = false;
         }
+        events.push(Event::CompanionLeft(oldest.bonus));
+    }
+
+    if let Some(npc) = world.monster_mut(id) {
+        log::info!("NPC {} accompanies the player.", npc);
+        npc.accompanying_player = true;
     }
-    None
+    companions.push(Companion::new(id, bonus));
+    events.push(Event::CompanionJoined(bonus));
+    companion::joined_line(bonus)
 }
 
-pub fn inventory_key(kind: item::Kind) -> u8 {
-    // NOTE: use the order defined in `Kind::iter` so the keys always
-    // correspond to the order we display the items in.
-    for (index, current_kind) in item::Kind::iter().enumerate() {
-        if current_kind == kind {
-            return (index + 1) as u8;
+/// The player just got high. The companions wait for them to sober
+/// up unless they ran out of patience. Those leave for good. Returns
+/// what the companions say.
+fn companions_lose_patience(
+    world: &mut World,
+    companions: &mut Vec<Companion>,
+    events: &mut Vec<Event>,
+) -> Option<&'static str> {
+    if companions.is_empty() {
+        return None;
+    }
+    let mut line = companion::WAITING_LINE;
+    for companion in companions.iter_mut() {
+        companion.patience -= 1;
+        let gave_up = companion.patience <= 0;
+        if let Some(npc) = world.monster_mut(companion.id) {
+            npc.accompanying_player = false;
+            if gave_up {
+                log::info!("NPC {} gave up on the player.", npc);
+                // NOTE: an NPC without a bonus won't join the player
+                // again.
+                npc.companion_bonus = None;
+            }
+        }
+        if gave_up {
+            events.push(Event::CompanionGaveUp(companion.bonus));
+            line = companion::GAVE_UP_LINE;
+        } else {
+            events.push(Event::CompanionLeftIntoxicated(companion.bonus));
         }
     }
-    unreachable!()
+    companions.retain(|c| c.patience > 0);
+    Some(line)
 }
 
 fn kill_monster(monster_position: Point, world: &mut World) {
@@ -1398,45 +2150,36 @@ fn kill_monster(monster_position: Point, world: &mut World) {
     }
 }
 
-fn use_dose(
+fn use_item(
     player: &mut player::Player,
+    world: &mut World,
     explosion_animation: &mut Option<Box<dyn AreaOfEffect>>,
     item: item::Item,
+    events: &mut Vec<Event>,
 ) {
-    use crate::item::Kind::*;
-    use crate::player::Modifier::*;
-    log::debug!("Using dose");
-    // TODO: do a different explosion animation for the cardinal dose
-    if let Intoxication { state_of_mind, .. } = item.modifier {
-        let radius = if state_of_mind <= 100 { 4 } else { 6 };
-        player.take_effect(item.modifier);
-        let animation: Box<dyn AreaOfEffect> = match item.kind {
-            Dose | StrongDose => Box::new(animation::SquareExplosion::new(
-                player.pos,
-                radius,
-                2,
-                color::explosion,
-            )),
-            CardinalDose => Box::new(animation::CardinalExplosion::new(
-                player.pos,
-                radius,
-                2,
-                color::explosion,
-                color::shattering_explosion,
-            )),
-            DiagonalDose => Box::new(animation::DiagonalExplosion::new(
-                player.pos,
-                radius,
-                2,
-                color::explosion,
-                color::shattering_explosion,
-            )),
-            Food => unreachable!(),
-        };
-        *explosion_animation = Some(animation);
-    } else {
-        unreachable!();
+    log::debug!("Using {}", item.id);
+    player.take_effect(item.modifier);
+    if item.is_dose() && player.alive() && formula::nearly_overdosed(player.mind) {
+        player
+            .status
+            .add(status::Kind::Nausea, formula::NAUSEA_TURNS);
+        events.push(Event::Nauseous(formula::NAUSEA_TURNS));
     }
+    let explosion = world
+        .item_definition(&item.id)
+        .explosion
+        .animation(player.pos);
+    set_off_explosion(world, player.pos, explosion_animation, explosion);
+}
+
+/// Whether a dose thrown from `from` can land on `target`: it's
+/// within the `THROW_RANGE` and there's no wall in the way.
+pub fn can_throw(world: &World, from: Point, target: Point) -> bool {
+    target != from
+        && from.tile_distance(target) <= formula::THROW_RANGE
+        && point::Line::new(from, target)
+            .skip(1)
+            .all(|pos| world.walkable(pos, Blocker::WALL, from))
 }
 
 fn show_exit_stats(stats: &Stats) {
@@ -1467,83 +2210,19 @@ fn show_exit_stats(stats: &Stats) {
     );
 }
 
-fn verify_states(expected: &state::Verification, actual: &state::Verification) {
-    if expected.chunk_count != actual.chunk_count {
-        log::error!(
-            "Expected chunks: {}, actual: {}",
-            expected.chunk_count,
-            actual.chunk_count
-        );
-    }
-    if expected.player_pos != actual.player_pos {
-        log::error!(
-            "Expected player position: {}, actual: {}",
-            expected.player_pos,
-            actual.player_pos
-        );
-    }
-    if expected.monsters.len() != actual.monsters.len() {
-        log::error!(
-            "Expected monster count: {}, actual: {}",
-            expected.monsters.len(),
-            actual.monsters.len()
-        );
-    }
-    if expected.monsters != actual.monsters {
-        let expected_monsters: HashMap<Point, (Point, monster::Kind)> = FromIterator::from_iter(
-            expected
-                .monsters
-                .iter()
-                .map(|&(pos, chunk_pos, monster)| (pos, (chunk_pos, monster))),
-        );
-        let actual_monsters: HashMap<Point, (Point, monster::Kind)> = FromIterator::from_iter(
-            actual
-                .monsters
-                .iter()
-                .map(|&(pos, chunk_pos, monster)| (pos, (chunk_pos, monster))),
-        );
-
-        for (pos, expected) in &expected_monsters {
-            match actual_monsters.get(pos) {
-                Some(actual) => {
-                    if expected != actual {
-                        log::error!(
-                            "Monster at {} differ. Expected: {:?}, \
-                             actual: {:?}",
-                            pos,
-                            expected,
-                            actual
-                        );
-                    }
-                }
-                None => {
-                    log::error!(
-                        "Monster expected at {}: {:?}, but it's not \
-                         there.",
-                        pos,
-                        expected
-                    );
-                }
-            }
-        }
-
-        for (pos, actual) in &actual_monsters {
-            if expected_monsters.get(pos).is_none() {
-                log::error!("There is an unexpected monster at: {}: {:?}.", pos, actual);
-            }
-        }
-    }
-    assert!(expected == actual, "Validation failed!");
-}
-
-fn create_new_game_state(state: &State) -> State {
+fn create_new_game_state(state: &State, settings: &Settings) -> State {
+    let replay_path = if settings.record_replays {
+        state::generate_replay_path()
+    } else {
+        None
+    };
     State::new_game(
         state.world_size,
         state.map_size.x,
         state.panel_width,
         state.display_size,
         state.exit_after,
-        state::generate_replay_path(),
+        replay_path,
         state.player.invincible,
     )
 }
@@ -1620,7 +2299,7 @@ fn place_victory_npc(state: &mut State) -> Point {
         let path_to_vnpc = pathfinding::Path::find(
             state.player.pos,
             vnpc_pos,
-            &mut state.world,
+            &state.world,
             blockers,
             state.player.pos,
             formula::PATHFINDING_VNPC_REACHABILITY_LIMIT,
@@ -1666,14 +2345,16 @@ fn place_victory_npc(state: &mut State) -> Point {
     state.world.explore(vnpc_pos, 5);
     state.world.always_visible(vnpc_pos, 2);
 
+    let npc = state.world.monster_definition_of_kind(monster::Kind::Npc);
+    let mut monster = monster::Monster::new(npc, vnpc_pos);
+    monster.companion_bonus = Some(CompanionBonus::Victory);
+    monster.color = color::victory_npc;
+    monster.ai_state = ai::AIState::NoOp;
     if let Some(chunk) = state.world.chunk_mut(vnpc_pos) {
-        let mut monster = monster::Monster::new(monster::Kind::Npc, vnpc_pos);
-        monster.companion_bonus = Some(CompanionBonus::Victory);
-        monster.color = color::victory_npc;
-        monster.ai_state = ai::AIState::NoOp;
         let id = chunk.add_monster(monster);
         state.victory_npc_id = Some(id);
     }
+    state.log_event(Event::VictoryNpcAppeared);
 
     vnpc_pos
 }
@@ -1695,3 +2376,265 @@ fn walkable_place_nearby(
     // Radius `2` means the central point and the eight surroinding ones.
     point::SquareArea::new(pos, 2).find(|&point| world.walkable(point, blockers, player_pos))
 }
+
+#[cfg(test)]
+mod test {
+    use super::{can_throw, process_keys, set_off_explosion, simulate_step};
+    use crate::{
+        animation::{self, Layer, Shape, TileEffect},
+        color, formula, item,
+        keymap::Keymap,
+        keys::{Key, KeyCode},
+        level::{Tile, TileKind},
+        monster::Monster,
+        player::Mind,
+        point::Point,
+        rect::Rectangle,
+        state::{Command, State},
+    };
+
+    /// Put an Anxiety on an empty tile at `pos`.
+    fn place_anxiety(state: &mut State, pos: Point) {
+        state.world.remove_monster(pos);
+        state.world.cell_mut(pos).unwrap().tile = Tile::new(TileKind::Empty);
+        let anxiety = Monster::new(state.world.monster_definition("Anxiety"), pos);
+        state.world.chunk_mut(pos).unwrap().add_monster(anxiety);
+    }
+
+    fn monster_alive(state: &State, pos: Point) -> bool {
+        state
+            .world
+            .monsters(Rectangle::center(pos, Point::zero()))
+            .any(|monster| monster.position == pos)
+    }
+
+    #[test]
+    fn test_explosion_kills_monsters() {
+        let mut state = State::new_test_game();
+        let neighbour = state.player.pos + (1, 0);
+        place_anxiety(&mut state, neighbour);
+        assert!(monster_alive(&state, neighbour));
+        let dose = state.world.item_definition("Dose").item();
+        state.player.inventory.push(dose);
+
+        state.run_command(Command::Use("Dose".into()));
+        assert!(state.player.inventory.is_empty());
+        assert!(!monster_alive(&state, neighbour));
+    }
+
+    /// Seeking forward and back in a replay ends up in the same state
+    /// as playing it straight to that turn.
+    #[test]
+    #[cfg(feature = "replay")]
+    fn test_seek_replay_back_and_forth() {
+        let replay_game = || {
+            let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests")
+                .join("golden-replays")
+                .join("replay-seed-1");
+            State::replay_game(
+                crate::WORLD_SIZE,
+                crate::DISPLAYED_MAP_SIZE,
+                crate::PANEL_WIDTH,
+                crate::DISPLAY_SIZE,
+                &path,
+                false, // cheating
+                false, // invincible
+                true,  // replay full speed
+                false, // exit after
+            )
+            .unwrap()
+        };
+
+        let mut straight = replay_game();
+        super::seek_replay(&mut straight, 10);
+        assert_eq!(straight.turn, 10);
+
+        let mut seeking = replay_game();
+        super::seek_replay(&mut seeking, 30);
+        assert_eq!(seeking.turn, 30);
+        super::seek_replay(&mut seeking, 10);
+        assert_eq!(seeking.turn, 10);
+        assert_eq!(seeking.verification(), straight.verification());
+        assert_eq!(seeking.verifications, straight.verifications);
+        assert_eq!(seeking.commands, straight.commands);
+    }
+
+    #[test]
+    fn test_planted_tree_looks_like_a_tree() {
+        let mut state = State::new_test_game();
+        let pos = state.player.pos + (2, 0);
+        state.world.remove_monster(pos);
+        let cell = state.world.cell_mut(pos).unwrap();
+        cell.tile = Tile::new(TileKind::Empty);
+        cell.items.clear();
+        let explosion = animation::Explosion::builder(pos)
+            .radius(0, 0)
+            .layer(Layer::fixed(
+                Shape::Square,
+                0,
+                color::BRIGHT_BLUE,
+                TileEffect::PLANT_TREE,
+            ))
+            .build();
+
+        let player_pos = state.player.pos;
+        set_off_explosion(
+            &mut state.world,
+            player_pos,
+            &mut state.explosion_animation,
+            Box::new(explosion),
+        );
+        let tile = state.world.cell(pos).unwrap().tile;
+        assert_eq!(tile.kind, TileKind::Tree);
+        assert_eq!(tile.fg_color, Tile::new(TileKind::Tree).fg_color);
+    }
+
+    #[test]
+    fn test_explosion_effects_apply_when_set_off() {
+        let mut state = State::new_test_game();
+        let player_pos = state.player.pos;
+        let outer_wave = player_pos + (3, 0);
+        for pos in &[player_pos, outer_wave] {
+            state.world.remove_monster(*pos);
+            let cell = state.world.cell_mut(*pos).unwrap();
+            cell.tile = Tile::new(TileKind::Empty);
+            cell.items.clear();
+        }
+        let explosion = animation::Explosion::builder(player_pos)
+            .radius(1, 3)
+            .layer(Layer::wave(
+                Shape::Cross,
+                color::BRIGHT_BLUE,
+                TileEffect::PLANT_TREE,
+            ))
+            .build();
+
+        // NOTE: the whole explosion takes effect before its
+        // animation reaches the outer wave
+        set_off_explosion(
+            &mut state.world,
+            player_pos,
+            &mut state.explosion_animation,
+            Box::new(explosion),
+        );
+        let tile_kind = |state: &State, pos| state.world.cell(pos).unwrap().tile.kind;
+        assert_eq!(tile_kind(&state, outer_wave), TileKind::Tree);
+        assert_eq!(tile_kind(&state, player_pos), TileKind::Empty);
+        assert!(state.explosion_animation.is_some());
+
+        // NOTE: moving during the animation doesn't change anything
+        state.player.pos = outer_wave - (1, 0);
+        simulate_step(&mut state);
+        assert!(state.explosion_animation.is_none());
+        assert_eq!(tile_kind(&state, player_pos), TileKind::Empty);
+    }
+
+    #[test]
+    fn test_drop_item() {
+        let mut state = State::new_test_game();
+        let pos = state.player.pos;
+        let items_on_tile = state.world.cell(pos).unwrap().items.len();
+        let food = state.world.item_definition("Food").item();
+        state.player.inventory.push(food);
+
+        state.run_command(Command::Drop("Food".into()));
+        assert!(state.player.inventory.is_empty());
+        let items = &state.world.cell(pos).unwrap().items;
+        assert_eq!(items.len(), items_on_tile + 1);
+        assert_eq!(items.last().map(|i| i.id.as_str()), Some("Food"));
+    }
+
+    #[test]
+    fn test_use_item_from_data() {
+        let mut state = State::new_test_game();
+        let mut definition = state.world.item_definition("Food").clone();
+        definition.id = "Bread".into();
+        definition.keys = vec![Key::plain(KeyCode::D6)];
+        state.world.item_definitions.push(definition.clone());
+        state.player.inventory.push(definition.item());
+
+        state.keys.push(Key::plain(KeyCode::D6));
+        process_keys(
+            &mut state.keys,
+            &mut state.commands,
+            &Keymap::default(),
+            &state.world.item_definitions,
+        );
+        assert_eq!(state.commands.front(), Some(&Command::Use("Bread".into())));
+        while !state.commands.is_empty() {
+            simulate_step(&mut state);
+        }
+        assert!(state.player.inventory.is_empty());
+    }
+
+    #[test]
+    fn test_throw_dose() {
+        let mut state = State::new_test_game();
+        let start = state.player.pos;
+        // NOTE: the player would use an irresistible dose right away
+        let dose = state.world.item_definition("CardinalDose").item();
+        state.player.inventory.push(item::Item {
+            irresistible: 0,
+            ..dose
+        });
+
+        let too_far = start + (formula::THROW_RANGE + 1, 0);
+        assert!(!can_throw(&state.world, start, too_far));
+        state.run_command(Command::Throw {
+            item: "CardinalDose".into(),
+            target: too_far,
+        });
+        assert_eq!(state.player.inventory.len(), 1);
+
+        let target = Rectangle::center(start, Point::from_i32(formula::THROW_RANGE))
+            .points()
+            .filter(|&pos| pos.tile_distance(start) > 1)
+            .find(|&pos| can_throw(&state.world, start, pos))
+            .expect("There should be a clear tile to throw at.");
+        // NOTE: past the target so it's not in the way of the throw
+        let behind_target =
+            target + Point::new((target.x - start.x).signum(), (target.y - start.y).signum());
+        place_anxiety(&mut state, behind_target);
+        state.run_command(Command::Throw {
+            item: "CardinalDose".into(),
+            target,
+        });
+        assert!(state.player.inventory.is_empty());
+        assert!(!monster_alive(&state, behind_target));
+        // NOTE: the dose explodes far away rather than intoxicating
+        // the player
+        match state.player.mind {
+            Mind::Withdrawal(_) => {}
+            mind => panic!("The thrown dose intoxicated the player: {:?}", mind),
+        }
+    }
+
+    #[test]
+    fn test_terrain_move_cost() {
+        let mut state = State::new_test_game();
+        let start = state.player.pos;
+        let terrain = [TileKind::Road, TileKind::Road, TileKind::ShallowWater];
+        for (x, &kind) in terrain.iter().enumerate() {
+            let cell = state.world.cell_mut(start + (x as i32 + 1, 0)).unwrap();
+            cell.tile = Tile::new(kind);
+            cell.items.clear();
+        }
+        let turn = state.turn;
+
+        // NOTE: two steps along the road take a single action point
+        state.run_command(Command::E);
+        assert_eq!(state.turn, turn);
+        state.run_command(Command::E);
+        assert_eq!(state.turn, turn + 1);
+
+        // NOTE: wading into the water takes the next turn as well
+        state.run_command(Command::E);
+        assert_eq!(state.player.pos, start + (3, 0));
+        assert!(!state.player.has_ap(1));
+        while !state.player.has_ap(1) {
+            simulate_step(&mut state);
+        }
+        assert_eq!(state.turn, turn + 3);
+    }
+}
@@ -1,5 +1,284 @@
-use crate::{item::Item, level::Tile, monster::Monster, point::Point};
+use crate::{
+    color,
+    item::{self, Item},
+    level::{Tile, TileKind},
+    monster::{self, Monster},
+    noise,
+    point::Point,
+    random::Random,
+};
 
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+pub mod buildings;
+pub mod city;
+pub mod fields;
 pub mod forrest;
 
 pub type GeneratedWorld = (Vec<(Point, Tile)>, Vec<Monster>, Vec<(Point, Item)>);
+
+/// The chunk a `WorldGenerator` is filling in.
+///
+/// The generated positions are relative to the chunk's top-left
+/// corner.
+pub struct ChunkInfo<'a> {
+    /// Anything built from the world coordinates (e.g. the `noise`)
+    /// should use this seed so it continues across the chunks.
+    pub world_seed: u32,
+    /// The world position of the chunk's top-left corner.
+    pub position: Point,
+    pub size: Point,
+    /// The world position the player starts at.
+    pub player_position: Point,
+    pub monster_definitions: &'a [monster::Definition],
+    pub item_definitions: &'a [item::Definition],
+}
+
+impl<'a> ChunkInfo<'a> {
+    /// Is the chunk position `pos` where the player starts the game?
+    pub fn is_player_start(&self, pos: Point) -> bool {
+        self.position + pos == self.player_position
+    }
+}
+
+pub trait WorldGenerator {
+    /// Generate the contents of a single chunk. The `rng` is seeded
+    /// by the world seed and the chunk position. Anything drawn from
+    /// the `throwaway_rng` must not affect the gameplay (e.g. the tile
+    /// colours).
+    fn generate(
+        &self,
+        chunk: &ChunkInfo<'_>,
+        rng: &mut Random,
+        throwaway_rng: &mut Random,
+    ) -> GeneratedWorld;
+}
+
+/// The world generators the game shipped with. The saved games and
+/// replays remember the one they were started with so the chunks
+/// generated later fit in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Generator {
+    /// Forest everywhere. The only one up to the 1.0.0 release.
+    Forrest,
+    /// Regions of forests, fields, city streets and abandoned
+    /// buildings.
+    Biomes,
+}
+
+impl Generator {
+    /// The generator used for the new games.
+    pub const CURRENT: Generator = Generator::Biomes;
+
+    pub fn world_generator(self) -> &'static dyn WorldGenerator {
+        match self {
+            Generator::Forrest => &forrest::Legacy,
+            Generator::Biomes => &Biomes,
+        }
+    }
+}
+
+/// NOTE: the games from before the world generator was recorded used
+/// the forest.
+impl Default for Generator {
+    fn default() -> Self {
+        Generator::Forrest
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Biome {
+    Fields,
+    Forrest,
+    City,
+    Buildings,
+}
+
+/// How many chunks does one cell of the biome noise span in each
+/// direction. The higher, the larger the biome regions.
+const BIOME_SCALE: f32 = 3.0;
+
+/// Added to the world seed so the biome noise doesn't follow any
+/// other noise built from the same seed.
+const BIOME_SEED_OFFSET: u32 = 0xB10E;
+
+/// The noise is offset by this for the unrelated uses of the same
+/// world seed, e.g. the terrain and the paths.
+pub const TERRAIN_SEED_OFFSET: u32 = 0x7E44;
+pub const PATH_SEED_OFFSET: u32 = 0x9A74;
+pub const TILE_SEED_OFFSET: u32 = 0x711E;
+
+/// Picks the biome of each chunk from a low-frequency noise map and
+/// lets it generate the chunk.
+pub struct Biomes;
+
+impl Biomes {
+    fn biome(&self, chunk: &ChunkInfo<'_>) -> Biome {
+        let x = (chunk.position.x / chunk.size.x) as f32 / BIOME_SCALE;
+        let y = (chunk.position.y / chunk.size.y) as f32 / BIOME_SCALE;
+        // NOTE: the biomes are ordered so the neighbouring regions
+        // make sense: the fields give way to the forest, the forest to
+        // the city with the abandoned buildings in its centre.
+        match noise::value(chunk.world_seed.wrapping_add(BIOME_SEED_OFFSET), x, y) {
+            n if n < 0.3 => Biome::Fields,
+            n if n < 0.6 => Biome::Forrest,
+            n if n < 0.8 => Biome::City,
+            _ => Biome::Buildings,
+        }
+    }
+}
+
+impl WorldGenerator for Biomes {
+    fn generate(
+        &self,
+        chunk: &ChunkInfo<'_>,
+        rng: &mut Random,
+        throwaway_rng: &mut Random,
+    ) -> GeneratedWorld {
+        let biome = self.biome(chunk);
+        log::debug!("Generating {:?} at {}", biome, chunk.position);
+        match biome {
+            Biome::Fields => fields::Fields.generate(chunk, rng, throwaway_rng),
+            Biome::Forrest => forrest::Forrest.generate(chunk, rng, throwaway_rng),
+            Biome::City => city::City.generate(chunk, rng, throwaway_rng),
+            Biome::Buildings => buildings::Buildings.generate(chunk, rng, throwaway_rng),
+        }
+    }
+}
+
+/// A tree with one of the tree colours.
+pub fn tree<R: Rng>(throwaway_rng: &mut R) -> Tile {
+    let mut tile = Tile::new(TileKind::Tree);
+    let options = [color::tree_1, color::tree_2, color::tree_3];
+    tile.fg_color = *options.choose(throwaway_rng).unwrap();
+    tile
+}
+
+/// Can a monster or an item be generated on the tile?
+fn is_open(tile: Tile) -> bool {
+    match tile.kind {
+        TileKind::Empty | TileKind::Road | TileKind::Rubble => true,
+        TileKind::Tree | TileKind::ShallowWater | TileKind::Building | TileKind::Door => false,
+    }
+}
+
+/// Place the monsters on the open tiles of the `map`.
+///
+/// `no_monster_weight` is the spawn weight of not generating any
+/// monster on a tile. The `spawn_weight` of each monster definition
+/// is relative to it.
+pub fn generate_monsters<R: Rng>(
+    rng: &mut R,
+    map: &[(Point, Tile)],
+    definitions: &[monster::Definition],
+    no_monster_weight: i32,
+) -> Vec<Monster> {
+    let mut options = vec![(None, no_monster_weight)];
+    options.extend(
+        definitions
+            .iter()
+            .filter(|definition| definition.spawn_weight > 0)
+            .map(|definition| (Some(definition), definition.spawn_weight)),
+    );
+
+    let mut result = vec![];
+    for &(pos, tile) in map.iter() {
+        if !is_open(tile) {
+            continue;
+        }
+        let definition = options
+            .choose_weighted(rng, |item| item.1)
+            .map(|result| result.0)
+            .unwrap_or(None);
+        if let Some(definition) = definition {
+            let mut monster = Monster::new(definition, pos);
+            if definition.kind == monster::Kind::Npc {
+                use crate::monster::CompanionBonus::*;
+                let bonus = rng.gen();
+                monster.companion_bonus = Some(bonus);
+                monster.color = match bonus {
+                    DoubleWillGrowth => color::npc_will,
+                    HalveExhaustion => color::npc_mind,
+                    ExtraActionPoint => color::npc_speed,
+                    Victory => unreachable!(),
+                };
+            };
+            result.push(monster);
+        }
+    }
+    result
+}
+
+/// Place the items on the open tiles of the `map`.
+///
+/// `no_item_weight` is the spawn weight of not generating any item on
+/// a tile. The `spawn_weight` of each item definition is relative to
+/// it.
+pub fn generate_items<R: Rng>(
+    rng: &mut R,
+    map: &[(Point, Tile)],
+    definitions: &[item::Definition],
+    no_item_weight: i32,
+) -> Vec<(Point, Item)> {
+    let mut options = vec![(None, no_item_weight)];
+    options.extend(
+        definitions
+            .iter()
+            .filter(|definition| definition.spawn_weight > 0)
+            .map(|definition| (Some(definition), definition.spawn_weight)),
+    );
+
+    let mut result = vec![];
+    for &(pos, tile) in map.iter() {
+        if !is_open(tile) {
+            // Occupied tile, do nothing.
+            continue;
+        }
+        let definition = options
+            .choose_weighted(rng, |item| item.1)
+            .map(|result| result.0)
+            .unwrap_or(None);
+        if let Some(definition) = definition {
+            result.push((pos, definition.random_item(rng)));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::{buildings, city, fields, forrest, is_open, ChunkInfo, WorldGenerator};
+    use crate::{item, monster, point::Point, random};
+
+    #[test]
+    fn test_biomes_fill_the_chunk() {
+        let monster_definitions = monster::built_in_definitions();
+        let item_definitions = item::built_in_definitions();
+        let generators: [&dyn WorldGenerator; 5] = [
+            &forrest::Legacy,
+            &forrest::Forrest,
+            &fields::Fields,
+            &city::City,
+            &buildings::Buildings,
+        ];
+        for generator in generators.iter() {
+            let chunk = ChunkInfo {
+                world_seed: 7,
+                position: Point::new(-32, 0),
+                size: Point::new(32, 32),
+                player_position: Point::new(-31, 1),
+                monster_definitions: &monster_definitions,
+                item_definitions: &item_definitions,
+            };
+            let mut rng = random::from_seed(1);
+            let mut throwaway_rng = random::from_seed(2);
+            let (map, monsters, items) = generator.generate(&chunk, &mut rng, &mut throwaway_rng);
+
+            assert_eq!(map.len(), 32 * 32);
+            let open = |pos: Point| map.iter().any(|&(p, tile)| p == pos && is_open(tile));
+            assert!(monsters.iter().all(|m| open(m.position)));
+            assert!(items.iter().all(|&(pos, _)| open(pos)));
+        }
+    }
+}
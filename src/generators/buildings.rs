@@ -0,0 +1,120 @@
+use crate::generators::{self, ChunkInfo, GeneratedWorld, WorldGenerator};
+
+use crate::level::{Tile, TileKind};
+use crate::noise;
+use crate::point::Point;
+use crate::random::Random;
+use crate::rect::Rectangle;
+
+use rand::Rng;
+
+/// How many times do we try to place a room into the chunk.
+const ROOM_ATTEMPTS: i32 = 12;
+const ROOM_SIZE: (i32, i32) = (4, 9);
+
+/// The share of the floor covered by the rubble from the crumbling
+/// walls.
+const RUBBLE_CHANCE: f32 = 0.08;
+
+/// Lots of dark corners for the monsters to hide in.
+const NO_MONSTER_WEIGHT: i32 = 950;
+
+/// People left a lot of stuff behind.
+const NO_ITEM_WEIGHT: i32 = 800;
+
+/// Abandoned buildings: rooms with doors connected by narrow
+/// corridors.
+///
+/// A corridor runs along the top and the left edge of every chunk and
+/// through its middle. This connects the rooms to each other and to
+/// the neighbouring chunks.
+pub struct Buildings;
+
+impl WorldGenerator for Buildings {
+    fn generate(
+        &self,
+        chunk: &ChunkInfo<'_>,
+        rng: &mut Random,
+        _throwaway_rng: &mut Random,
+    ) -> GeneratedWorld {
+        let size = chunk.size;
+        let middle = size / 2;
+        let mut open = vec![false; (size.x * size.y) as usize];
+        let mut carve = |area: Rectangle| {
+            for pos in area.points() {
+                if pos.x >= 0 && pos.y >= 0 && pos.x < size.x && pos.y < size.y {
+                    open[(pos.y * size.x + pos.x) as usize] = true;
+                }
+            }
+        };
+
+        // The corridors
+        carve(Rectangle::new((0, 0).into(), (size.x - 1, 0).into()));
+        carve(Rectangle::new((0, 0).into(), (0, size.y - 1).into()));
+        carve(Rectangle::new(
+            (0, middle.y).into(),
+            (size.x - 1, middle.y).into(),
+        ));
+        carve(Rectangle::new(
+            (middle.x, 0).into(),
+            (middle.x, size.y - 1).into(),
+        ));
+
+        // The rooms, each connected to the corridor in the middle
+        let mut rooms: Vec<Rectangle> = vec![];
+        let mut doors = vec![];
+        for _ in 0..ROOM_ATTEMPTS {
+            let room_size = Point::new(
+                rng.gen_range(ROOM_SIZE.0, ROOM_SIZE.1),
+                rng.gen_range(ROOM_SIZE.0, ROOM_SIZE.1),
+            );
+            let top_left = Point::new(
+                rng.gen_range(2, size.x - room_size.x - 1),
+                rng.gen_range(2, size.y - room_size.y - 1),
+            );
+            let room = Rectangle::from_point_and_size(top_left, room_size);
+            // NOTE: keep a wall between the rooms
+            let with_walls = Rectangle::new(room.top_left() - (1, 1), room.bottom_right() + (1, 1));
+            if rooms.iter().any(|&other| other.intersects(with_walls)) {
+                continue;
+            }
+            let centre = room.top_left() + room.size() / 2;
+            if middle.x < room.top_left().x {
+                doors.push(Point::new(room.top_left().x - 1, centre.y));
+            } else if middle.x > room.bottom_right().x {
+                doors.push(Point::new(room.bottom_right().x + 1, centre.y));
+            }
+            carve(room);
+            carve(Rectangle::new(
+                Point::new(centre.x.min(middle.x), centre.y),
+                Point::new(centre.x.max(middle.x), centre.y),
+            ));
+            rooms.push(room);
+        }
+
+        let tile_seed = chunk.world_seed.wrapping_add(generators::TILE_SEED_OFFSET);
+        let mut map = vec![];
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let pos = Point::new(x, y);
+                let world_pos = chunk.position + pos;
+                let tile = if chunk.is_player_start(pos) {
+                    Tile::new(TileKind::Empty)
+                } else if !open[(y * size.x + x) as usize] {
+                    Tile::new(TileKind::Building)
+                } else if doors.contains(&pos) {
+                    Tile::new(TileKind::Door)
+                } else if noise::lattice(tile_seed, world_pos.x, world_pos.y) < RUBBLE_CHANCE {
+                    Tile::new(TileKind::Rubble)
+                } else {
+                    Tile::new(TileKind::Empty)
+                };
+                map.push((pos, tile));
+            }
+        }
+        let monsters =
+            generators::generate_monsters(rng, &map, chunk.monster_definitions, NO_MONSTER_WEIGHT);
+        let items = generators::generate_items(rng, &map, chunk.item_definitions, NO_ITEM_WEIGHT);
+        (map, monsters, items)
+    }
+}
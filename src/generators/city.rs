@@ -0,0 +1,115 @@
+use crate::generators::{self, ChunkInfo, GeneratedWorld, WorldGenerator};
+
+use crate::level::{Tile, TileKind};
+use crate::noise;
+use crate::point::Point;
+use crate::random::Random;
+
+/// The streets repeat every this many tiles in both directions.
+const BLOCK_SIZE: i32 = 8;
+const STREET_WIDTH: i32 = 2;
+
+/// The share of the lots between the streets that are parks rather
+/// than buildings.
+const PARK_SHARE: f32 = 0.25;
+const PARK_TREE_CHANCE: f32 = 0.2;
+
+/// Added to the world seed for the noise picking the lots.
+const LOT_SEED_OFFSET: u32 = 0x107;
+
+/// The streets are busy.
+const NO_MONSTER_WEIGHT: i32 = 960;
+const NO_ITEM_WEIGHT: i32 = 1100;
+
+/// A grid of streets with small buildings and parks in between.
+///
+/// The streets follow the world coordinates so they line up with the
+/// ones in the neighbouring chunks.
+pub struct City;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Lot {
+    Park,
+    /// A building with the door on the given side (0 to 3: north,
+    /// east, south, west).
+    Building {
+        door: i32,
+    },
+}
+
+impl WorldGenerator for City {
+    fn generate(
+        &self,
+        chunk: &ChunkInfo<'_>,
+        rng: &mut Random,
+        throwaway_rng: &mut Random,
+    ) -> GeneratedWorld {
+        let lot_seed = chunk.world_seed.wrapping_add(LOT_SEED_OFFSET);
+        let tile_seed = chunk.world_seed.wrapping_add(generators::TILE_SEED_OFFSET);
+        let lot = |lot_x: i32, lot_y: i32| {
+            if noise::lattice(lot_seed, lot_x, lot_y) < PARK_SHARE {
+                Lot::Park
+            } else {
+                let door = (noise::lattice(lot_seed.wrapping_add(1), lot_x, lot_y) * 4.0) as i32;
+                Lot::Building { door }
+            }
+        };
+
+        let lot_size = BLOCK_SIZE - STREET_WIDTH;
+        let middle = lot_size / 2;
+        let mut map = vec![];
+        for y in 0..chunk.size.y {
+            for x in 0..chunk.size.x {
+                let pos = Point::new(x, y);
+                let world_pos = chunk.position + pos;
+                // NOTE: the position within the lot. It's negative on
+                // the street.
+                let lx = world_pos.x.rem_euclid(BLOCK_SIZE) - STREET_WIDTH;
+                let ly = world_pos.y.rem_euclid(BLOCK_SIZE) - STREET_WIDTH;
+                let on_street = lx < 0 || ly < 0;
+
+                let tile = if on_street {
+                    Tile::new(TileKind::Road)
+                } else if chunk.is_player_start(pos) {
+                    Tile::new(TileKind::Empty)
+                } else {
+                    let lot_x = world_pos.x.div_euclid(BLOCK_SIZE);
+                    let lot_y = world_pos.y.div_euclid(BLOCK_SIZE);
+                    match lot(lot_x, lot_y) {
+                        Lot::Park => {
+                            if noise::lattice(tile_seed, world_pos.x, world_pos.y)
+                                < PARK_TREE_CHANCE
+                            {
+                                generators::tree(throwaway_rng)
+                            } else {
+                                Tile::new(TileKind::Empty)
+                            }
+                        }
+                        Lot::Building { door } => {
+                            let wall =
+                                lx == 0 || ly == 0 || lx == lot_size - 1 || ly == lot_size - 1;
+                            let is_door = match door {
+                                0 => ly == 0 && lx == middle,
+                                1 => lx == lot_size - 1 && ly == middle,
+                                2 => ly == lot_size - 1 && lx == middle,
+                                _ => lx == 0 && ly == middle,
+                            };
+                            if is_door {
+                                Tile::new(TileKind::Door)
+                            } else if wall {
+                                Tile::new(TileKind::Building)
+                            } else {
+                                Tile::new(TileKind::Empty)
+                            }
+                        }
+                    }
+                };
+                map.push((pos, tile));
+            }
+        }
+        let monsters =
+            generators::generate_monsters(rng, &map, chunk.monster_definitions, NO_MONSTER_WEIGHT);
+        let items = generators::generate_items(rng, &map, chunk.item_definitions, NO_ITEM_WEIGHT);
+        (map, monsters, items)
+    }
+}
@@ -0,0 +1,61 @@
+use crate::generators::{self, ChunkInfo, GeneratedWorld, WorldGenerator};
+
+use crate::level::{Tile, TileKind};
+use crate::noise;
+use crate::point::Point;
+use crate::random::Random;
+
+const TREE_CHANCE: f32 = 0.07;
+
+/// The size (in tiles) of the ponds and the space between them.
+const POND_SCALE: f32 = 9.0;
+
+/// The pond noise values above this are water.
+const POND_LEVEL: f32 = 0.8;
+
+/// Added to the world seed for the pond noise.
+const POND_SEED_OFFSET: u32 = 0x90D;
+
+/// The fields are quiet: there are fewer monsters than in the forest.
+const NO_MONSTER_WEIGHT: i32 = 990;
+
+/// But there are fewer items to find, too.
+const NO_ITEM_WEIGHT: i32 = 1400;
+
+/// Open fields with a lone tree and a shallow pond here and there.
+pub struct Fields;
+
+impl WorldGenerator for Fields {
+    fn generate(
+        &self,
+        chunk: &ChunkInfo<'_>,
+        rng: &mut Random,
+        throwaway_rng: &mut Random,
+    ) -> GeneratedWorld {
+        let tile_seed = chunk.world_seed.wrapping_add(generators::TILE_SEED_OFFSET);
+        let pond_seed = chunk.world_seed.wrapping_add(POND_SEED_OFFSET);
+        let mut map = vec![];
+        for y in 0..chunk.size.y {
+            for x in 0..chunk.size.x {
+                let pos = Point::new(x, y);
+                let world_pos = chunk.position + pos;
+                let (wx, wy) = (world_pos.x as f32, world_pos.y as f32);
+                let pond = noise::value(pond_seed, wx / POND_SCALE, wy / POND_SCALE);
+                let tile = if chunk.is_player_start(pos) {
+                    Tile::new(TileKind::Empty)
+                } else if pond > POND_LEVEL {
+                    Tile::new(TileKind::ShallowWater)
+                } else if noise::lattice(tile_seed, world_pos.x, world_pos.y) < TREE_CHANCE {
+                    generators::tree(throwaway_rng)
+                } else {
+                    Tile::new(TileKind::Empty)
+                };
+                map.push((pos, tile));
+            }
+        }
+        let monsters =
+            generators::generate_monsters(rng, &map, chunk.monster_definitions, NO_MONSTER_WEIGHT);
+        let items = generators::generate_items(rng, &map, chunk.item_definitions, NO_ITEM_WEIGHT);
+        (map, monsters, items)
+    }
+}
@@ -1,19 +1,96 @@
-use crate::generators::GeneratedWorld;
+use crate::generators::{self, ChunkInfo, GeneratedWorld, WorldGenerator};
 
-use crate::color;
-use crate::formula;
-use crate::item::{self, Item};
 use crate::level::{Tile, TileKind};
-use crate::monster::{Kind, Monster};
-use crate::player::Modifier;
+use crate::noise;
 use crate::point::Point;
+use crate::random::Random;
 
 use rand::{seq::SliceRandom, Rng};
 
-// TODO: Instead of `map_size`, use a Rectangle with the world
-// positions here. We want to expose the non-world coordinates in as
-// few places as possible.
-fn generate_map<R: Rng, G: Rng>(
+/// The spawn weight of not generating any monster on an empty
+/// tile. The `spawn_weight` of each monster definition is relative to
+/// this.
+const NO_MONSTER_WEIGHT: i32 = 970;
+
+/// The spawn weight of not generating any item on an empty tile. The
+/// `spawn_weight` of each item definition is relative to this.
+const NO_ITEM_WEIGHT: i32 = 1000;
+
+/// The size (in tiles) of the thickets and clearings.
+const THICKET_SCALE: f32 = 14.0;
+
+/// How far apart (in tiles) do the paths wind.
+const PATH_SCALE: f32 = 24.0;
+
+/// How wide are the paths in the path noise values.
+const PATH_WIDTH: f32 = 0.025;
+
+/// Trees and clearings with a good number of monsters and items.
+///
+/// The thickets, clearings and paths are made from the noise over
+/// the world coordinates so they continue across the chunks.
+pub struct Forrest;
+
+/// The forest as generated up to the 1.0.0 release: every tile is
+/// picked at random on its own. The games started back then still
+/// use it.
+pub struct Legacy;
+
+/// The chance of a tree on the tile with the given thicket noise
+/// value.
+fn tree_chance(thicket: f32) -> f32 {
+    let chance = (thicket - 0.3) * 2.0;
+    chance.clamp(0.0, 0.9)
+}
+
+fn generate_map<G: Rng>(chunk: &ChunkInfo<'_>, throwavay_rng: &mut G) -> Vec<(Point, Tile)> {
+    let terrain_seed = chunk
+        .world_seed
+        .wrapping_add(generators::TERRAIN_SEED_OFFSET);
+    let path_seed = chunk.world_seed.wrapping_add(generators::PATH_SEED_OFFSET);
+    let tile_seed = chunk.world_seed.wrapping_add(generators::TILE_SEED_OFFSET);
+
+    let mut result = vec![];
+    for y in 0..chunk.size.y {
+        for x in 0..chunk.size.x {
+            let pos = Point::new(x, y);
+            let world_pos = chunk.position + pos;
+            let (wx, wy) = (world_pos.x as f32, world_pos.y as f32);
+
+            let thicket = noise::fractal(terrain_seed, wx / THICKET_SCALE, wy / THICKET_SCALE, 3);
+            let path = noise::value(path_seed, wx / PATH_SCALE, wy / PATH_SCALE);
+            let on_path = (path - 0.5).abs() < PATH_WIDTH;
+            let tree = !on_path
+                && !chunk.is_player_start(pos)
+                && noise::lattice(tile_seed, world_pos.x, world_pos.y) < tree_chance(thicket);
+
+            let tile = if tree {
+                generators::tree(throwavay_rng)
+            } else {
+                Tile::new(TileKind::Empty)
+            };
+            result.push((pos, tile));
+        }
+    }
+    result
+}
+
+impl WorldGenerator for Forrest {
+    fn generate(
+        &self,
+        chunk: &ChunkInfo<'_>,
+        rng: &mut Random,
+        throwaway_rng: &mut Random,
+    ) -> GeneratedWorld {
+        let map = generate_map(chunk, throwaway_rng);
+        let monsters =
+            generators::generate_monsters(rng, &map, chunk.monster_definitions, NO_MONSTER_WEIGHT);
+        let items = generators::generate_items(rng, &map, chunk.item_definitions, NO_ITEM_WEIGHT);
+        (map, monsters, items)
+    }
+}
+
+fn generate_legacy_map<R: Rng, G: Rng>(
     rng: &mut R,
     throwavay_rng: &mut G,
     map_size: Point,
@@ -25,8 +102,9 @@ fn generate_map<R: Rng, G: Rng>(
     // start at top left corner, moving to the right
     for y in 0..map_size.y {
         for x in 0..map_size.x {
-            // TODO: due to coordinate conversion, this is wrong for
-            // every chunk but the one the player is in.
+            // NOTE: `player_pos` is in the world coordinates, so this
+            // empties the top-left tile of every chunk. The old games
+            // depend on it.
             //
             // Player always starts at an empty space:
             let kind = if player_pos == (x, y) {
@@ -38,11 +116,11 @@ fn generate_map<R: Rng, G: Rng>(
                     .unwrap_or(TileKind::Empty)
             };
 
-            let mut tile = Tile::new(kind);
-            if tile.kind == TileKind::Tree {
-                let options = [color::tree_1, color::tree_2, color::tree_3];
-                tile.fg_color = *options.choose(throwavay_rng).unwrap();
-            }
+            let tile = if kind == TileKind::Tree {
+                generators::tree(throwavay_rng)
+            } else {
+                Tile::new(kind)
+            };
 
             result.push((Point::new(x, y), tile));
         }
@@ -50,139 +128,17 @@ fn generate_map<R: Rng, G: Rng>(
     result
 }
 
-fn generate_monsters<R: Rng>(rng: &mut R, map: &[(Point, Tile)]) -> Vec<Monster> {
-    let monster_count = 5;
-    let monster_chance = 30;
-    let options = [
-        (None, 1000 - monster_chance),
-        (Some(Kind::Anxiety), monster_chance / monster_count),
-        (Some(Kind::Depression), monster_chance / monster_count),
-        (Some(Kind::Hunger), monster_chance / monster_count),
-        (Some(Kind::Shadows), monster_chance / monster_count),
-        (Some(Kind::Voices), monster_chance / monster_count),
-        (Some(Kind::Npc), 2),
-    ];
-
-    let mut result = vec![];
-    for &(pos, tile) in map.iter() {
-        if tile.kind != TileKind::Empty {
-            continue;
-        }
-        let kind = options
-            .choose_weighted(rng, |item| item.1)
-            .map(|result| result.0)
-            .unwrap_or(None);
-        if let Some(kind) = kind {
-            let mut monster = Monster::new(kind, pos);
-            if kind == Kind::Npc {
-                use crate::color;
-                use crate::monster::CompanionBonus::*;
-                let bonus = rng.gen();
-                monster.companion_bonus = Some(bonus);
-                monster.color = match bonus {
-                    DoubleWillGrowth => color::npc_will,
-                    HalveExhaustion => color::npc_mind,
-                    ExtraActionPoint => color::npc_speed,
-                    Victory => unreachable!(),
-                };
-            };
-            result.push(monster);
-        }
-    }
-    result
-}
-
-fn new_item<R: Rng>(kind: item::Kind, rng: &mut R) -> Item {
-    use crate::item::Kind::*;
-    match kind {
-        Dose => {
-            let mut item = formula::DOSE_PREFAB;
-            if let Modifier::Intoxication {
-                ref mut state_of_mind,
-                ..
-            } = item.modifier
-            {
-                *state_of_mind += formula::DOSE_MIND_VARIANCE.random(rng);
-            };
-            item
-        }
-        StrongDose => {
-            let mut item = formula::STRONG_DOSE_PREFAB;
-            if let Modifier::Intoxication {
-                ref mut state_of_mind,
-                ..
-            } = item.modifier
-            {
-                *state_of_mind += formula::STRONG_DOSE_MIND_VARIANCE.random(rng);
-            };
-            item
-        }
-        CardinalDose => {
-            let mut item = formula::CARDINAL_DOSE_PREFAB;
-            if let Modifier::Intoxication {
-                ref mut state_of_mind,
-                ..
-            } = item.modifier
-            {
-                *state_of_mind += formula::CARDINAL_DOSE_MIND_VARIANCE.random(rng);
-            };
-            item
-        }
-        DiagonalDose => {
-            let mut item = formula::DIAGONAL_DOSE_PREFAB;
-            if let Modifier::Intoxication {
-                ref mut state_of_mind,
-                ..
-            } = item.modifier
-            {
-                *state_of_mind += formula::DIAGONAL_DOSE_MIND_VARIANCE.random(rng);
-            };
-            item
-        }
-        Food => formula::FOOD_PREFAB,
-    }
-}
-
-fn generate_items<R: Rng>(rng: &mut R, map: &[(Point, Tile)]) -> Vec<(Point, Item)> {
-    use crate::item::Kind::*;
-
-    let options = [
-        (None, 1000),
-        (Some(Dose), 8),
-        (Some(StrongDose), 3),
-        (Some(CardinalDose), 2),
-        (Some(DiagonalDose), 2),
-        (Some(Food), 5),
-    ];
-
-    let mut result = vec![];
-    for &(pos, tile) in map.iter() {
-        match tile.kind {
-            TileKind::Tree => {
-                // Occupied tile, do nothing.
-            }
-            TileKind::Empty => {
-                let kind = options
-                    .choose_weighted(rng, |item| item.1)
-                    .map(|result| result.0)
-                    .unwrap_or(None);
-                if let Some(kind) = kind {
-                    result.push((pos, new_item(kind, rng)));
-                }
-            }
-        }
+impl WorldGenerator for Legacy {
+    fn generate(
+        &self,
+        chunk: &ChunkInfo<'_>,
+        rng: &mut Random,
+        throwaway_rng: &mut Random,
+    ) -> GeneratedWorld {
+        let map = generate_legacy_map(rng, throwaway_rng, chunk.size, chunk.player_position);
+        let monsters =
+            generators::generate_monsters(rng, &map, chunk.monster_definitions, NO_MONSTER_WEIGHT);
+        let items = generators::generate_items(rng, &map, chunk.item_definitions, NO_ITEM_WEIGHT);
+        (map, monsters, items)
     }
-    result
-}
-
-pub fn generate<R: Rng, G: Rng>(
-    rng: &mut R,
-    throwavay_rng: &mut G,
-    size: Point,
-    player: Point,
-) -> GeneratedWorld {
-    let map = generate_map(rng, throwavay_rng, size, player);
-    let monsters = generate_monsters(rng, &map);
-    let items = generate_items(rng, &map);
-    (map, monsters, items)
 }
@@ -1,83 +1,334 @@
-use self::Kind::*;
-
 use crate::{
-    color::{self, Color},
+    animation::{self, AreaOfEffect},
+    color::Color,
+    keys::Key,
     player::Modifier,
+    point::Point,
+    ranged_int::InclusiveRange,
 };
 
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
+/// The kind decides the game rules that only apply to the food or to
+/// the doses. Everything else comes from the item's `Definition`.
 #[derive(Clone, Copy, PartialEq, Debug, Eq, Hash, Serialize, Deserialize)]
 pub enum Kind {
+    /// Can't be eaten while the player is nauseous.
     Food,
+    /// Intoxicates the player, can be thrown and can't be resisted
+    /// when it's close.
     Dose,
-    CardinalDose,
-    DiagonalDose,
-    StrongDose,
 }
 
-impl Kind {
-    pub fn iter() -> KindIterator {
-        KindIterator {
-            current: Some(self::Kind::Food),
-        }
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Item {
+    /// The `id` of the item's `Definition`.
+    pub id: String,
+    pub kind: Kind,
+    pub modifier: Modifier,
+    pub irresistible: i32,
+}
+
+impl Item {
+    pub fn is_dose(&self) -> bool {
+        self.kind == Kind::Dose
     }
 }
 
-pub struct KindIterator {
-    current: Option<Kind>,
+/// The area the item affects when it's used.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Explosion {
+    pub radius: i32,
+    /// The radius of the first wave of the explosion.
+    pub initial_radius: i32,
+    pub layers: Vec<animation::Layer>,
+    /// What the explosion does, as shown to the player.
+    pub description: String,
 }
 
-impl Iterator for KindIterator {
-    type Item = Kind;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        use self::Kind::*;
-        let current = self.current;
-        self.current = match current {
-            Some(Food) => Some(Dose),
-            Some(Dose) => Some(CardinalDose),
-            Some(CardinalDose) => Some(DiagonalDose),
-            Some(DiagonalDose) => Some(StrongDose),
-            Some(StrongDose) => None,
-            None => None,
-        };
-        current
+impl Explosion {
+    /// The explosion centred on `pos`.
+    pub fn animation(&self, pos: Point) -> Box<dyn AreaOfEffect> {
+        let mut builder =
+            animation::Explosion::builder(pos).radius(self.initial_radius, self.radius);
+        for layer in &self.layers {
+            builder = builder.layer(layer.clone());
+        }
+        Box::new(builder.build())
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
-pub struct Item {
+/// The items from before they were defined in the data file. Only
+/// used to read the saves of the 1.0.0 release.
+pub mod legacy {
+    use crate::player::Modifier;
+
+    use serde::Deserialize;
+
+    /// NOTE: the built-in definitions use these names as their ids.
+    #[derive(Copy, Clone, PartialEq, Debug, Deserialize)]
+    pub enum Kind {
+        Food,
+        Dose,
+        CardinalDose,
+        DiagonalDose,
+        StrongDose,
+    }
+
+    impl Kind {
+        fn id(self) -> String {
+            format!("{:?}", self)
+        }
+
+        fn kind(self) -> super::Kind {
+            match self {
+                Kind::Food => super::Kind::Food,
+                _ => super::Kind::Dose,
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct Item {
+        kind: Kind,
+        modifier: Modifier,
+        irresistible: i32,
+    }
+
+    impl From<Item> for super::Item {
+        fn from(old: Item) -> Self {
+            super::Item {
+                id: old.kind.id(),
+                kind: old.kind.kind(),
+                modifier: old.modifier,
+                irresistible: old.irresistible,
+            }
+        }
+    }
+}
+
+/// The name of the item definitions file. See the `data` module.
+const DEFINITIONS_FILE: &str = "items.json";
+
+const BUILT_IN_DEFINITIONS: &str = include_str!("../data/items.json");
+
+/// The stats of one item as written in the definitions file.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Definition {
+    /// Identifies the item in the saved games, replays and commands.
+    pub id: String,
     pub kind: Kind,
+    pub name: String,
+    pub glyph: char,
+    pub color: Color,
+    /// What happens to the player when they use the item.
     pub modifier: Modifier,
     pub irresistible: i32,
+    /// How much the intoxication of each generated item can differ
+    /// from the `modifier`.
+    pub mind_variance: Option<InclusiveRange>,
+    pub explosion: Explosion,
+    /// How likely is the item to be generated on an empty tile. See
+    /// `generators::forrest::NO_ITEM_WEIGHT`.
+    pub spawn_weight: i32,
+    /// The keys that use the item, e.g. `["1", "E"]`. Written the
+    /// same way as in the settings file.
+    #[serde(default, with = "crate::keys::names")]
+    pub keys: Vec<Key>,
+    /// The item can be generated close to where the player starts.
+    /// The first such food and dose are also placed there.
+    #[serde(default)]
+    pub near_start: bool,
 }
 
-impl Item {
-    pub fn is_dose(&self) -> bool {
-        match self.kind {
-            Dose | StrongDose | CardinalDose | DiagonalDose => true,
-            Food => false,
+impl Definition {
+    /// The item exactly as defined.
+    pub fn item(&self) -> Item {
+        Item {
+            id: self.id.clone(),
+            kind: self.kind,
+            modifier: self.modifier,
+            irresistible: self.irresistible,
+        }
+    }
+
+    /// The item with its intoxication randomised by the
+    /// `mind_variance`.
+    pub fn random_item<R: Rng>(&self, rng: &mut R) -> Item {
+        let mut item = self.item();
+        if let Modifier::Intoxication {
+            ref mut state_of_mind,
+            ..
+        } = item.modifier
+        {
+            if let Some(variance) = self.mind_variance {
+                *state_of_mind += variance.random(rng);
+            }
+        }
+        item
+    }
+
+    /// What the `item` does when used, e.g. `Intoxication +70,
+    /// Tolerance +4. Kills the monsters around you.`
+    pub fn item_description(&self, item: &Item) -> String {
+        format!("{}. {}", item.modifier, self.explosion.description)
+    }
+}
+
+/// The item definitions the game ships with.
+pub fn built_in_definitions() -> Vec<Definition> {
+    let mut definitions: Vec<Definition> =
+        crate::data::built_in(DEFINITIONS_FILE, BUILT_IN_DEFINITIONS);
+    // NOTE: the trailer needs weaker doses
+    if cfg!(feature = "recording") {
+        for definition in definitions.iter_mut().filter(|d| d.id == "Dose") {
+            if let Modifier::Intoxication {
+                ref mut state_of_mind,
+                ..
+            } = definition.modifier
+            {
+                *state_of_mind = 30;
+            }
         }
     }
+    definitions
+}
 
-    pub fn glyph(&self) -> char {
-        match self.kind {
-            Food => '%',
-            Dose => 'i',
-            StrongDose => 'I',
-            CardinalDose => '+',
-            DiagonalDose => 'x',
+/// Load the item definitions from the user's definitions file
+/// falling back to the built-in ones.
+///
+/// The game places food and a dose close to where the player starts
+/// so there must be a definition of both kinds that can be there.
+/// The missing ones are taken from the built-in definitions.
+pub fn load_definitions() -> Vec<Definition> {
+    let loaded: Vec<Definition> = crate::data::load(DEFINITIONS_FILE, BUILT_IN_DEFINITIONS);
+    let mut definitions: Vec<Definition> = vec![];
+    for definition in loaded {
+        if definitions.iter().any(|d| d.id == definition.id) {
+            log::warn!(
+                "The `{}` item is defined more than once. Using the first definition.",
+                definition.id
+            );
+        } else {
+            definitions.push(definition);
+        }
+    }
+    for &kind in &[Kind::Food, Kind::Dose] {
+        if !definitions.iter().any(|d| d.kind == kind && d.near_start) {
+            let built_in = built_in_definitions()
+                .into_iter()
+                .find(|d| d.kind == kind && d.near_start)
+                .unwrap_or_else(|| panic!("The built-in items have no starting `{:?}`.", kind));
+            log::warn!(
+                "There is no `{:?}` item near the start. Using the built-in `{}` definition.",
+                kind,
+                built_in.id
+            );
+            definitions.retain(|d| d.id != built_in.id);
+            definitions.push(built_in);
         }
     }
+    definitions
+}
+
+#[cfg(test)]
+mod test {
+    use super::{built_in_definitions, Definition, Kind};
+    use crate::{
+        animation::Shape,
+        keys::{Key, KeyCode},
+        player::Modifier,
+    };
+
+    #[test]
+    fn test_built_in_definitions() {
+        let definitions = built_in_definitions();
+        let ids = ["Food", "Dose", "CardinalDose", "DiagonalDose", "StrongDose"];
+        assert_eq!(definitions.len(), ids.len());
+        for id in &ids {
+            assert_eq!(definitions.iter().filter(|d| d.id == *id).count(), 1);
+        }
+
+        let definition = |id| definitions.iter().find(|d| d.id == id).unwrap();
+        let food = definition("Food");
+        assert_eq!(food.kind, Kind::Food);
+        assert_eq!(food.glyph, '%');
+        assert_eq!(food.mind_variance, None);
+        assert_eq!(
+            food.keys,
+            vec![Key::plain(KeyCode::D1), Key::plain(KeyCode::E)]
+        );
+        assert!(food.near_start);
+        assert_eq!(definition("StrongDose").kind, Kind::Dose);
+        assert_eq!(definition("StrongDose").irresistible, 4);
+        assert!(!definition("StrongDose").near_start);
+        let shapes = |id| {
+            definition(id)
+                .explosion
+                .layers
+                .iter()
+                .map(|layer| layer.shape)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(shapes("CardinalDose"), vec![Shape::Square, Shape::Cross]);
+        assert_eq!(shapes("DiagonalDose"), vec![Shape::Square, Shape::Diagonal]);
+    }
+
+    #[test]
+    fn test_new_item_from_data() {
+        let json = r#"{
+            "id": "Microdose",
+            "kind": "Dose",
+            "name": "Microdose",
+            "glyph": "j",
+            "color": { "r": 109, "g": 194, "b": 202 },
+            "modifier": { "Intoxication": { "state_of_mind": 40, "tolerance_increase": 2 } },
+            "irresistible": 1,
+            "mind_variance": null,
+            "explosion": {
+              "radius": 3,
+              "initial_radius": 1,
+              "layers": [
+                { "shape": "Square", "color": { "r": 109, "g": 194, "b": 202 }, "effect": ["KILL"] }
+              ],
+              "description": "Kills the monsters around you."
+            },
+            "spawn_weight": 4,
+            "keys": ["6", "Shift+J"]
+        }"#;
+        let definition: Definition = serde_json::from_str(json).unwrap();
+        let item = definition.item();
+        assert_eq!(item.id, "Microdose");
+        assert!(item.is_dose());
+        assert!(!definition.near_start);
+        assert_eq!(definition.keys[0], Key::plain(KeyCode::D6));
+        assert_eq!(
+            definition.keys[1],
+            Key {
+                shift: true,
+                ..Key::plain(KeyCode::J)
+            }
+        );
+    }
 
-    pub fn color(&self) -> Color {
-        match self.kind {
-            Food => color::food,
-            Dose => color::dose,
-            StrongDose => color::strong_dose,
-            CardinalDose => color::shattering_dose,
-            DiagonalDose => color::shattering_dose,
+    #[test]
+    fn test_random_item_variance() {
+        let definitions = built_in_definitions();
+        let dose = definitions.iter().find(|d| d.id == "Dose").unwrap();
+        let mut rng = crate::random::from_seed(0);
+        for _ in 0..20 {
+            let item = dose.random_item(&mut rng);
+            match (item.modifier, dose.modifier) {
+                (
+                    Modifier::Intoxication { state_of_mind, .. },
+                    Modifier::Intoxication {
+                        state_of_mind: base,
+                        ..
+                    },
+                ) => assert!((state_of_mind - base).abs() <= 5),
+                _ => panic!("A dose must intoxicate."),
+            }
         }
     }
 }
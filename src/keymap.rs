@@ -0,0 +1,448 @@
+//! Mapping of the pressed keys to the game commands.
+//!
+//! The player picks one of the `Preset`s and can then rebind the
+//! keys of any `Action` in the Controls window or in the settings
+//! file. The item keys come from the item definitions instead.
+
+use crate::{
+    item,
+    keys::{Key, KeyCode},
+    state::Command,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Keys the game window handles itself. Binding them to an action
+/// would make the action unreachable.
+pub const RESERVED_KEYS: [KeyCode; 2] = [KeyCode::Esc, KeyCode::QuestionMark];
+
+/// Anything the player can bind a key to: a game command or one of
+/// the windows opened from the game.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+    Command(Command),
+    Look,
+    MessageLog,
+    Drop,
+    Throw,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Preset {
+    /// Numpad, arrow keys and Vi keys all at once.
+    Default,
+    Numpad,
+    Vi,
+    /// Arrow keys with Shift and Ctrl for the diagonal movement (as
+    /// in NotEye).
+    Arrows,
+}
+
+impl Preset {
+    pub fn all() -> impl Iterator<Item = Preset> {
+        [Preset::Default, Preset::Numpad, Preset::Vi, Preset::Arrows]
+            .iter()
+            .cloned()
+    }
+
+    /// The name used in the settings file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Default => "default",
+            Preset::Numpad => "numpad",
+            Preset::Vi => "vi",
+            Preset::Arrows => "arrows",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Preset> {
+        Preset::all().find(|preset| preset.name() == name)
+    }
+
+    /// The preset following this one (wrapping around).
+    pub fn next(self) -> Preset {
+        match self {
+            Preset::Default => Preset::Numpad,
+            Preset::Numpad => Preset::Vi,
+            Preset::Vi => Preset::Arrows,
+            Preset::Arrows => Preset::Default,
+        }
+    }
+}
+
+/// Every action that can be bound to a key, in the order they're
+/// shown to the player.
+pub fn actions() -> Vec<Action> {
+    use crate::state::Command::*;
+    let commands = vec![NW, N, NE, W, E, SW, S, SE, Explore];
+    let mut actions: Vec<Action> = commands.into_iter().map(Action::Command).collect();
+    actions.extend(vec![
+        Action::Look,
+        Action::MessageLog,
+        Action::Drop,
+        Action::Throw,
+    ]);
+    actions
+}
+
+/// The name of the action in the settings file.
+pub fn action_name(action: &Action) -> &'static str {
+    match action {
+        Action::Command(command) => command_name(command),
+        Action::Look => "look",
+        Action::MessageLog => "message_log",
+        Action::Drop => "drop",
+        Action::Throw => "throw",
+    }
+}
+
+pub fn action_from_name(name: &str) -> Option<Action> {
+    actions()
+        .into_iter()
+        .find(|action| action_name(action) == name)
+}
+
+/// What the action does, as shown to the player.
+pub fn action_description(action: &Action) -> &'static str {
+    match action {
+        Action::Command(command) => command_description(command),
+        Action::Look => "Look around",
+        Action::MessageLog => "Message log",
+        Action::Drop => "Drop an item",
+        Action::Throw => "Throw a dose",
+    }
+}
+
+fn command_name(command: &Command) -> &'static str {
+    use crate::state::Command::*;
+    match command {
+        N => "north",
+        E => "east",
+        S => "south",
+        W => "west",
+        NE => "north_east",
+        NW => "north_west",
+        SE => "south_east",
+        SW => "south_west",
+        Use(_) => "use",
+        ShowMessageBox { .. } => "show_message_box",
+        Explore => "explore",
+        Drop(_) => "drop",
+        Throw { .. } => "throw",
+    }
+}
+
+fn command_description(command: &Command) -> &'static str {
+    use crate::state::Command::*;
+    match command {
+        N => "Move north",
+        E => "Move east",
+        S => "Move south",
+        W => "Move west",
+        NE => "Move north-east",
+        NW => "Move north-west",
+        SE => "Move south-east",
+        SW => "Move south-west",
+        Use(_) => "Use an item",
+        ShowMessageBox { .. } => "Show a message",
+        Explore => "Auto-explore",
+        Drop(_) => "Drop an item",
+        Throw { .. } => "Throw a dose",
+    }
+}
+
+/// The item whose definition binds the `key`. The keys match the
+/// same way as in `Keymap::command`.
+///
+/// NOTE: the item keys come from the item definitions rather than
+/// the keymap so every item the data file adds gets its own keys.
+pub fn item_bound_to(definitions: &[item::Definition], key: Key) -> Option<&item::Definition> {
+    let exact = definitions.iter().find(|d| d.keys.contains(&key));
+    let plain = || {
+        definitions
+            .iter()
+            .find(|d| d.keys.contains(&Key::plain(key.code)))
+    };
+    exact.or_else(plain)
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keymap {
+    pub preset: Preset,
+    /// The keys bound to each of the `actions()`.
+    bindings: Vec<(Action, Vec<Key>)>,
+}
+
+impl Keymap {
+    pub fn from_preset(preset: Preset) -> Self {
+        use crate::keys::KeyCode::*;
+
+        let plain = Key::plain;
+        let shift = |code| Key {
+            shift: true,
+            ..Key::plain(code)
+        };
+        let ctrl = |code| Key {
+            ctrl: true,
+            ..Key::plain(code)
+        };
+
+        let numpad = vec![
+            (Command::N, plain(NumPad8)),
+            (Command::S, plain(NumPad2)),
+            (Command::W, plain(NumPad4)),
+            (Command::E, plain(NumPad6)),
+            (Command::NW, plain(NumPad7)),
+            (Command::SW, plain(NumPad1)),
+            (Command::NE, plain(NumPad9)),
+            (Command::SE, plain(NumPad3)),
+        ];
+        let arrows = vec![
+            (Command::N, plain(Up)),
+            (Command::S, plain(Down)),
+            (Command::W, plain(Left)),
+            (Command::E, plain(Right)),
+            (Command::NW, shift(Left)),
+            (Command::SW, ctrl(Left)),
+            (Command::NE, shift(Right)),
+            (Command::SE, ctrl(Right)),
+        ];
+        let vi = vec![
+            (Command::N, plain(K)),
+            (Command::S, plain(J)),
+            (Command::W, plain(H)),
+            (Command::E, plain(L)),
+            (Command::NW, plain(Y)),
+            (Command::SW, plain(B)),
+            (Command::NE, plain(U)),
+            (Command::SE, plain(N)),
+        ];
+
+        let mut movement = vec![];
+        match preset {
+            Preset::Default => {
+                movement.extend(numpad);
+                movement.extend(arrows);
+                movement.extend(vi);
+            }
+            Preset::Numpad => movement.extend(numpad),
+            Preset::Vi => movement.extend(vi),
+            Preset::Arrows => movement.extend(arrows),
+        }
+
+        let mut keymap = Keymap {
+            preset,
+            bindings: actions()
+                .into_iter()
+                .map(|action| (action, vec![]))
+                .collect(),
+        };
+        for (command, key) in movement {
+            keymap.bind(&Action::Command(command), key);
+        }
+        let other = vec![
+            (Action::Command(Command::Explore), plain(X)),
+            (Action::Look, plain(Enter)),
+            (Action::MessageLog, plain(M)),
+            (Action::Drop, plain(D)),
+            (Action::Throw, plain(T)),
+        ];
+        for (action, key) in other {
+            keymap.bind(&action, key);
+        }
+        keymap
+    }
+
+    /// The action bound to the `key`.
+    ///
+    /// A key bound without any modifiers also matches when a
+    /// modifier is held unless that combination is bound to
+    /// something else. E.g. `Shift+K` moves north in the Vi preset,
+    /// but in the Arrows one `Shift+Left` moves north-west rather
+    /// than west.
+    pub fn action(&self, key: Key) -> Option<Action> {
+        let exact = self.bindings.iter().find(|(_, keys)| keys.contains(&key));
+        let plain = || {
+            self.bindings
+                .iter()
+                .find(|(_, keys)| keys.contains(&Key::plain(key.code)))
+        };
+        exact.or_else(plain).map(|(action, _)| action.clone())
+    }
+
+    /// The game command bound to the `key`.
+    pub fn command(&self, key: Key) -> Option<Command> {
+        match self.action(key) {
+            Some(Action::Command(command)) => Some(command),
+            _ => None,
+        }
+    }
+
+    /// All keys bound to the `action`.
+    pub fn keys(&self, action: &Action) -> &[Key] {
+        self.bindings
+            .iter()
+            .find(|(a, _)| a == action)
+            .map(|(_, keys)| &keys[..])
+            .unwrap_or(&[])
+    }
+
+    /// Add the `key` to the keys bound to the `action`.
+    pub fn bind(&mut self, action: &Action, key: Key) {
+        if let Some((_, keys)) = self.bindings.iter_mut().find(|(a, _)| a == action) {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+    }
+
+    /// Replace all keys bound to the `action`.
+    pub fn set_keys(&mut self, action: &Action, new_keys: Vec<Key>) {
+        if let Some((_, keys)) = self.bindings.iter_mut().find(|(a, _)| a == action) {
+            *keys = new_keys;
+        }
+    }
+
+    /// The actions whose keys differ from the preset.
+    pub fn customised(&self) -> Vec<(Action, Vec<Key>)> {
+        let preset = Keymap::from_preset(self.preset);
+        self.bindings
+            .iter()
+            .filter(|(action, keys)| preset.keys(action) != &keys[..])
+            .cloned()
+            .collect()
+    }
+
+    /// The actions the `key` is bound to.
+    pub fn actions_bound_to(&self, key: Key) -> Vec<Action> {
+        self.bindings
+            .iter()
+            .filter(|(_, keys)| keys.contains(&key))
+            .map(|(action, _)| action.clone())
+            .collect()
+    }
+
+    /// Returns `true` if the `key` is reserved by the game or bound
+    /// to more than one action or item.
+    pub fn is_conflicting(&self, key: Key, item_definitions: &[item::Definition]) -> bool {
+        let items = item_definitions
+            .iter()
+            .filter(|d| d.keys.contains(&key))
+            .count();
+        RESERVED_KEYS.contains(&key.code) || self.actions_bound_to(key).len() + items > 1
+    }
+
+    /// All keys that are reserved by the game or bound to more than
+    /// one action or item.
+    pub fn conflicts(&self, item_definitions: &[item::Definition]) -> Vec<Key> {
+        let mut result = vec![];
+        for (_, keys) in &self.bindings {
+            for &key in keys {
+                if self.is_conflicting(key, item_definitions) && !result.contains(&key) {
+                    result.push(key);
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::from_preset(Preset::Default)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keys::KeyCode::*;
+
+    fn shift(code: KeyCode) -> Key {
+        Key {
+            shift: true,
+            ..Key::plain(code)
+        }
+    }
+
+    #[test]
+    fn test_presets() {
+        let items = item::built_in_definitions();
+        let default = Keymap::default();
+        assert_eq!(default.command(Key::plain(NumPad8)), Some(Command::N));
+        assert_eq!(default.command(Key::plain(K)), Some(Command::N));
+        assert_eq!(default.command(shift(Left)), Some(Command::NW));
+        assert_eq!(default.command(Key::plain(X)), Some(Command::Explore));
+        assert_eq!(default.action(Key::plain(Enter)), Some(Action::Look));
+        assert_eq!(default.action(Key::plain(T)), Some(Action::Throw));
+        assert_eq!(default.command(Key::plain(T)), None);
+        assert!(default.conflicts(&items).is_empty());
+
+        let numpad = Keymap::from_preset(Preset::Numpad);
+        assert_eq!(numpad.command(Key::plain(NumPad8)), Some(Command::N));
+        assert_eq!(numpad.command(Key::plain(K)), None);
+        assert_eq!(numpad.command(Key::plain(Up)), None);
+        assert_eq!(numpad.action(Key::plain(M)), Some(Action::MessageLog));
+    }
+
+    #[test]
+    fn test_plain_key_matches_with_modifiers() {
+        let vi = Keymap::from_preset(Preset::Vi);
+        assert_eq!(vi.command(shift(K)), Some(Command::N));
+
+        let arrows = Keymap::from_preset(Preset::Arrows);
+        assert_eq!(arrows.command(shift(Left)), Some(Command::NW));
+        assert_eq!(arrows.command(shift(Up)), Some(Command::N));
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let items = item::built_in_definitions();
+        let explore = Action::Command(Command::Explore);
+        let mut keymap = Keymap::from_preset(Preset::Vi);
+        keymap.bind(&explore, Key::plain(N));
+        keymap.bind(&Action::Command(Command::N), Key::plain(Esc));
+        keymap.bind(&Action::Command(Command::S), Key::plain(Enter));
+        keymap.bind(&Action::Drop, Key::plain(E));
+        assert_eq!(
+            keymap.actions_bound_to(Key::plain(N)),
+            vec![Action::Command(Command::SE), explore.clone()]
+        );
+        assert!(keymap.is_conflicting(Key::plain(Enter), &items));
+        assert!(keymap.is_conflicting(Key::plain(E), &items));
+        assert!(!keymap.is_conflicting(Key::plain(D), &items));
+        assert_eq!(
+            keymap.conflicts(&items),
+            vec![
+                Key::plain(Esc),
+                Key::plain(Enter),
+                Key::plain(N),
+                Key::plain(E)
+            ]
+        );
+        assert_eq!(
+            keymap
+                .customised()
+                .into_iter()
+                .map(|(action, _)| action)
+                .collect::<Vec<_>>(),
+            vec![
+                Action::Command(Command::N),
+                Action::Command(Command::S),
+                explore,
+                Action::Drop
+            ]
+        );
+    }
+
+    #[test]
+    fn test_item_keys() {
+        let definitions = item::built_in_definitions();
+        let id = |key| item_bound_to(&definitions, key).map(|d| d.id.as_str());
+        assert_eq!(id(Key::plain(D1)), Some("Food"));
+        assert_eq!(id(Key::plain(E)), Some("Food"));
+        assert_eq!(id(shift(D2)), Some("Dose"));
+        assert_eq!(id(Key::plain(D5)), Some("StrongDose"));
+        assert_eq!(id(Key::plain(D9)), None);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{collections::VecDeque, iter::IntoIterator};
+use std::{collections::VecDeque, fmt, iter::IntoIterator, str::FromStr};
 
 use serde::{Deserialize, Serialize};
 
@@ -80,6 +80,174 @@ pub enum KeyCode {
     QuestionMark,
 }
 
+impl Key {
+    /// A key pressed without any modifiers.
+    pub fn plain(code: KeyCode) -> Self {
+        Key {
+            code,
+            alt: false,
+            ctrl: false,
+            shift: false,
+        }
+    }
+}
+
+/// Shows the key the way the player sees it, e.g. `Shift+Left`. This
+/// is also how the keys are written in the settings file.
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            f.write_str("Ctrl+")?;
+        }
+        if self.alt {
+            f.write_str("Alt+")?;
+        }
+        if self.shift {
+            f.write_str("Shift+")?;
+        }
+        f.write_str(&self.code.name())
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('+').map(str::trim).collect::<Vec<_>>();
+        let name = parts.pop().unwrap_or("");
+        let code = KeyCode::from_name(name).ok_or_else(|| format!("Unknown key: `{}`", name))?;
+        let mut key = Key::plain(code);
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "alt" => key.alt = true,
+                "ctrl" => key.ctrl = true,
+                "shift" => key.shift = true,
+                _ => return Err(format!("Unknown modifier: `{}`", modifier)),
+            }
+        }
+        Ok(key)
+    }
+}
+
+/// (De)serialise a list of keys as their names, e.g. `["1", "E"]`.
+pub mod names {
+    use super::Key;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(keys: &[Key], serializer: S) -> Result<S::Ok, S::Error> {
+        keys.iter()
+            .map(Key::to_string)
+            .collect::<Vec<_>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Key>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|name| name.parse().map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+const ALL_KEY_CODES: [KeyCode; 66] = [
+    KeyCode::D1,
+    KeyCode::D2,
+    KeyCode::D3,
+    KeyCode::D4,
+    KeyCode::D5,
+    KeyCode::D6,
+    KeyCode::D7,
+    KeyCode::D8,
+    KeyCode::D9,
+    KeyCode::D0,
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::NumPad0,
+    KeyCode::NumPad1,
+    KeyCode::NumPad2,
+    KeyCode::NumPad3,
+    KeyCode::NumPad4,
+    KeyCode::NumPad5,
+    KeyCode::NumPad6,
+    KeyCode::NumPad7,
+    KeyCode::NumPad8,
+    KeyCode::NumPad9,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Left,
+    KeyCode::Right,
+    KeyCode::Up,
+    KeyCode::Down,
+    KeyCode::Enter,
+    KeyCode::Space,
+    KeyCode::Esc,
+    KeyCode::QuestionMark,
+];
+
+impl KeyCode {
+    pub fn all() -> impl Iterator<Item = KeyCode> {
+        ALL_KEY_CODES.iter().cloned()
+    }
+
+    /// The name shown to the player and used in the settings file.
+    pub fn name(self) -> String {
+        use self::KeyCode::*;
+        match self {
+            D1 => "1".into(),
+            D2 => "2".into(),
+            D3 => "3".into(),
+            D4 => "4".into(),
+            D5 => "5".into(),
+            D6 => "6".into(),
+            D7 => "7".into(),
+            D8 => "8".into(),
+            D9 => "9".into(),
+            D0 => "0".into(),
+            QuestionMark => "?".into(),
+            _ => format!("{:?}", self),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<KeyCode> {
+        KeyCode::all().find(|code| code.name().eq_ignore_ascii_case(name))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Keys {
     keys: VecDeque<Key>,
@@ -29,10 +29,33 @@ pub struct Cell {
     pub always_visible: bool,
 }
 
-#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+// NOTE: the saved games store the tile kinds by their index. Add the
+// new ones at the end.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum TileKind {
     Empty,
     Tree,
+    /// Faster to walk along. See `formula::move_cost`.
+    Road,
+    /// Slows everyone wading through it.
+    ShallowWater,
+    /// The wall of a building.
+    Building,
+    /// Lets people into the buildings. See `Blocker::DOOR`.
+    Door,
+    /// What's left of a tree or a wall after `TileEffect::SHATTER`.
+    Rubble,
+}
+
+impl TileKind {
+    /// Nobody can walk through the walls. See `Blocker::WALL`.
+    pub fn is_wall(self) -> bool {
+        use self::TileKind::*;
+        match self {
+            Tree | Building => true,
+            Empty | Road | ShallowWater | Door | Rubble => false,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
@@ -46,6 +69,11 @@ impl Tile {
         let color = match kind {
             TileKind::Empty => color::empty_tile,
             TileKind::Tree => color::tree_1,
+            TileKind::Road => color::road,
+            TileKind::ShallowWater => color::shallow_water,
+            TileKind::Building => color::building,
+            TileKind::Door => color::door,
+            TileKind::Rubble => color::rubble,
         };
         Tile {
             kind,
@@ -58,6 +86,38 @@ impl Tile {
         match self.kind {
             Empty => '.',
             Tree => '#',
+            Road => '.',
+            ShallowWater => '~',
+            Building => '#',
+            Door => '+',
+            Rubble => ',',
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        use self::TileKind::*;
+        match self.kind {
+            Empty => "Empty ground",
+            Tree => "Tree",
+            Road => "Road",
+            ShallowWater => "Shallow water",
+            Building => "Wall",
+            Door => "Door",
+            Rubble => "Rubble",
+        }
+    }
+
+    /// What the tile means for the player.
+    pub fn description(self) -> &'static str {
+        use self::TileKind::*;
+        match self.kind {
+            Empty => "You can walk here.",
+            Tree => "Blocks the way. Some doses can destroy it.",
+            Road => "You can walk here faster.",
+            ShallowWater => "You can wade through, but it slows you down.",
+            Building => "Blocks the way. Some doses can destroy it.",
+            Door => "You can walk here. Some monsters can't open it.",
+            Rubble => "You can walk here, but it slows you down a little.",
         }
     }
 }
@@ -133,14 +193,15 @@ impl Level {
     }
 
     pub fn walkable(&self, pos: LevelPosition, blockers: blocker::Blocker) -> bool {
-        use self::TileKind::Empty;
         use crate::blocker::Blocker;
         // We don't have the player's position here so we can't check that here.
         assert!(!blockers.contains(Blocker::PLAYER));
-        let blocked_by_wall = blockers.contains(Blocker::WALL) && self.cell(pos).tile.kind != Empty;
+        let kind = self.cell(pos).tile.kind;
+        let blocked_by_wall = blockers.contains(Blocker::WALL) && kind.is_wall();
+        let blocked_by_door = blockers.contains(Blocker::DOOR) && kind == TileKind::Door;
         let blocked_by_monster =
             blockers.contains(Blocker::MONSTER) && self.monster_on_pos(pos).is_some();
-        !(blocked_by_wall || blocked_by_monster)
+        !(blocked_by_wall || blocked_by_door || blocked_by_monster)
     }
 
     #[allow(map_entry)]
@@ -188,3 +249,50 @@ impl<'a> Iterator for Cells<'a> {
         }
     }
 }
+
+/// The level as the 1.0.0 release saved it. Only used to read the
+/// old saves.
+pub mod legacy {
+    use super::{LevelPosition, Tile};
+    use crate::{item::legacy::Item, point::Point};
+
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Cell {
+        tile: Tile,
+        items: Vec<Item>,
+        explored: bool,
+        always_visible: bool,
+    }
+
+    impl From<Cell> for super::Cell {
+        fn from(old: Cell) -> Self {
+            super::Cell {
+                tile: old.tile,
+                items: old.items.into_iter().map(Into::into).collect(),
+                explored: old.explored,
+                always_visible: old.always_visible,
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Level {
+        dimensions: Point,
+        monsters: HashMap<LevelPosition, usize>,
+        map: Vec<Cell>,
+    }
+
+    impl From<Level> for super::Level {
+        fn from(old: Level) -> Self {
+            super::Level {
+                dimensions: old.dimensions,
+                monsters: old.monsters,
+                map: old.map.into_iter().map(Into::into).collect(),
+            }
+        }
+    }
+}
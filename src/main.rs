@@ -19,6 +19,9 @@ mod ai;
 mod animation;
 mod blocker;
 mod color;
+mod companion;
+mod data;
+mod distance_map;
 mod engine;
 #[macro_use]
 mod error;
@@ -27,10 +30,13 @@ mod game;
 mod generators;
 mod graphics;
 mod item;
+mod keymap;
 mod keys;
 mod level;
+mod message;
 mod metadata;
 mod monster;
+mod noise;
 mod palette;
 mod pathfinding;
 mod player;
@@ -39,9 +45,14 @@ mod random;
 mod ranged_int;
 mod rect;
 mod render;
+mod replay;
+mod savegame;
+mod settings;
 mod state;
 mod stats;
+mod status;
 mod timer;
+mod travel;
 mod ui;
 mod util;
 mod window;
@@ -74,6 +85,7 @@ fn run_glutin(
     default_background: color::Color,
     window_title: &str,
     state: state::State,
+    settings: engine::Settings,
     update: engine::UpdateFn,
 ) {
     log::info!("Using the glutin backend");
@@ -91,6 +103,7 @@ fn run_glutin(
         default_background,
         window_title,
         Box::new(state),
+        settings,
         update,
     );
 
@@ -104,6 +117,7 @@ fn run_sdl(
     default_background: color::Color,
     window_title: &str,
     state: state::State,
+    settings: engine::Settings,
     update: engine::UpdateFn,
 ) {
     log::info!("Using the sdl backend");
@@ -114,6 +128,7 @@ fn run_sdl(
         default_background,
         window_title,
         Box::new(state),
+        settings,
         update,
     );
 
@@ -127,6 +142,7 @@ fn run_remote(
     default_background: color::Color,
     window_title: &str,
     state: state::State,
+    settings: engine::Settings,
     update: engine::UpdateFn,
 ) {
     #[cfg(feature = "remote")]
@@ -135,6 +151,7 @@ fn run_remote(
         default_background,
         window_title,
         Box::new(state),
+        settings,
         update,
     );
 
@@ -142,6 +159,53 @@ fn run_remote(
     log::error!("The \"remote\" feature was not compiled in.");
 }
 
+#[cfg(feature = "cli")]
+#[allow(unused_variables)]
+fn run_headless(matches: &clap::ArgMatches<'_>) {
+    log::info!("Using the headless backend");
+
+    #[cfg(feature = "headless-backend")]
+    {
+        use crate::engine::headless::{Options, Source};
+
+        let source = if let Some(replay) = matches.value_of("replay") {
+            Source::Replay(replay.into())
+        } else if matches.value_of("agent") == Some("random") {
+            Source::RandomAgent
+        } else {
+            Source::Bot
+        };
+        let options = Options {
+            source,
+            games: matches
+                .value_of("games")
+                .and_then(|games| games.parse().ok())
+                .expect("The `games` option must be a positive number."),
+            seed: matches.value_of("seed").map(|seed| {
+                seed.parse()
+                    .expect("The `seed` option must be a positive number.")
+            }),
+            max_turns: matches
+                .value_of("max-turns")
+                .and_then(|turns| turns.parse().ok())
+                .expect("The `max-turns` option must be a number."),
+            replay_dir: matches.value_of("replay-dir").map(Into::into),
+            invincible: matches.is_present("invincible"),
+        };
+
+        engine::headless::run(
+            WORLD_SIZE,
+            DISPLAYED_MAP_SIZE,
+            PANEL_WIDTH,
+            DISPLAY_SIZE,
+            &options,
+        );
+    }
+
+    #[cfg(not(feature = "headless-backend"))]
+    log::error!("The \"headless-backend\" feature was not compiled in.");
+}
+
 #[cfg(feature = "cli")]
 fn process_cli_and_run_game() {
     use clap::{App, Arg, ArgGroup};
@@ -213,6 +277,53 @@ fn process_cli_and_run_game() {
         }
     }
 
+    if cfg!(feature = "headless-backend") {
+        app = app
+            .arg(Arg::with_name("headless").long("headless").help(
+                "Don't create a game window. Simulate the games and print \
+                 a JSON summary of each one to stdout.",
+            ))
+            .arg(
+                Arg::with_name("agent")
+                    .long("agent")
+                    .value_name("AGENT")
+                    .possible_values(&["random", "bot"])
+                    .default_value("bot")
+                    .help("Who plays the headless games (ignored when replaying)"),
+            )
+            .arg(
+                Arg::with_name("games")
+                    .long("games")
+                    .value_name("COUNT")
+                    .default_value("1")
+                    .help("Number of headless games to play"),
+            )
+            .arg(
+                Arg::with_name("seed")
+                    .long("seed")
+                    .value_name("SEED")
+                    .takes_value(true)
+                    .help("Seed of the first headless game. Every next game increments it."),
+            )
+            .arg(
+                Arg::with_name("max-turns")
+                    .long("max-turns")
+                    .value_name("TURNS")
+                    .default_value("10000")
+                    .help("End each headless game after this many turns"),
+            )
+            .arg(
+                Arg::with_name("replay-dir")
+                    .long("replay-dir")
+                    .value_name("DIR")
+                    .takes_value(true)
+                    .help("Record a replay of every headless game into this directory"),
+            );
+        if !crate::engine::AVAILABLE_BACKENDS.contains(&"headless") {
+            log::error!("The `headless` backend is enabled, but not set by the build script?");
+        }
+    }
+
     // Make sure only one of the backends can be set at a time
     app = app.group(ArgGroup::with_name("graphics").args(&crate::engine::AVAILABLE_BACKENDS));
 
@@ -231,6 +342,13 @@ fn process_cli_and_run_game() {
                     )
                     .takes_value(true),
             )
+            .arg(
+                Arg::with_name("replay-turn")
+                    .help("Fast-forward the replay to this turn and pause there")
+                    .long("replay-turn")
+                    .value_name("TURN")
+                    .takes_value(true),
+            )
             .arg(
                 Arg::with_name("replay-full-speed")
                     .help(
@@ -258,13 +376,6 @@ fn process_cli_and_run_game() {
 
     let matches = app.get_matches();
 
-    let default_graphics_backend = if crate::engine::AVAILABLE_BACKENDS.contains(&"glutin") {
-        "glutin"
-    } else {
-        crate::engine::AVAILABLE_BACKENDS[0]
-    };
-    assert!(crate::engine::AVAILABLE_BACKENDS.contains(&default_graphics_backend));
-
     let mut loggers = vec![];
 
     let log_level = if matches.is_present("debug") {
@@ -308,53 +419,30 @@ fn process_cli_and_run_game() {
         crate::engine::AVAILABLE_BACKENDS
     );
 
-    // NOTE: Generate the default settings file contents
-    let mut settings = String::with_capacity(1000);
-    settings.push_str("# Options: \"fullscreen\" or \"window\"\n");
-    settings.push_str("display = \"window\"\n\n");
-
-    let font_sizes_str = crate::engine::AVAILABLE_FONT_SIZES
-        .iter()
-        .map(|num| num.to_string())
-        .collect::<Vec<_>>()
-        .join(", ");
-    settings.push_str(&format!("# Options: {}\n", font_sizes_str));
-    settings.push_str(&format!("font_size = {}\n\n", crate::engine::TILESIZE));
-
-    let backends_str = crate::engine::AVAILABLE_BACKENDS
-        .iter()
-        .map(|b| format!("\"{}\"", b))
-        .collect::<Vec<_>>()
-        .join(", ");
-    settings.push_str(&format!("# Options: {}\n", backends_str));
-    settings.push_str(&format!("backend = \"{}\"\n", default_graphics_backend));
-    log::info!("Default settings:");
-    println!("{}", settings);
-
-    let loaded_settings = settings
-        .parse::<toml_edit::Document>()
-        .expect("Couldn't load settings.");
-    log::info!("Loaded settings:");
-    println!("{}", loaded_settings.to_string());
+    let settings = settings::load();
 
-    log::info!(
-        "display: {:?}",
-        loaded_settings["display"]
-            .as_str()
-            .expect("The `display` setting must be a string.")
-    );
-    log::info!(
-        "font size: {:?}",
-        loaded_settings["font_size"]
-            .as_integer()
-            .expect("The `font_size` setting must be an integer.")
-    );
-    log::info!(
-        "graphics backend: {:?}",
-        loaded_settings["backend"]
-            .as_str()
-            .expect("The `backend` setting must be a string.")
-    );
+    // NOTE: the backend passed on the command line takes precedence
+    // over the one in the settings file.
+    let backend = if matches.is_present("remote") {
+        "remote"
+    } else if matches.is_present("sdl") {
+        "sdl"
+    } else if matches.is_present("glutin") {
+        "glutin"
+    } else if matches.is_present("headless") {
+        "headless"
+    } else {
+        settings.backend.as_str()
+    };
+    log::info!("Graphics backend: {}", backend);
+
+    // NOTE: the headless backend doesn't need a game state or
+    // settings. It creates its own games and all it prints to stdout
+    // are the game summaries.
+    if backend == "headless" {
+        run_headless(&matches);
+        return;
+    }
 
     let state = if let Some(replay) = matches.value_of("replay") {
         if matches.is_present("replay-file") {
@@ -364,7 +452,7 @@ fn process_cli_and_run_game() {
             );
         }
         let replay_path = std::path::Path::new(replay);
-        state::State::replay_game(
+        let mut state = state::State::replay_game(
             WORLD_SIZE,
             DISPLAYED_MAP_SIZE,
             PANEL_WIDTH,
@@ -375,8 +463,22 @@ fn process_cli_and_run_game() {
             matches.is_present("replay-full-speed"),
             matches.is_present("exit-after"),
         )
-        .expect("Could not load the replay file")
+        .expect("Could not load the replay file");
+        if let Some(turn) = matches.value_of("replay-turn") {
+            let turn = turn
+                .parse()
+                .expect("The `replay-turn` option must be a number.");
+            game::seek_replay(&mut state, turn);
+            state.paused = true;
+        }
+        state
     } else {
+        if matches.is_present("replay-turn") {
+            panic!(
+                "The `replay-turn` option can only be used if the replay log \
+                 is passed."
+            );
+        }
         if matches.is_present("replay-full-speed") {
             panic!(
                 "The `full-replay-speed` option can only be used if the \
@@ -385,7 +487,8 @@ fn process_cli_and_run_game() {
         }
         let replay_file = match matches.value_of("replay-file") {
             Some(file) => Some(file.into()),
-            None => state::generate_replay_path(),
+            None if settings.record_replays => state::generate_replay_path(),
+            None => None,
         };
         let mut state = state::State::new_game(
             WORLD_SIZE,
@@ -406,20 +509,31 @@ fn process_cli_and_run_game() {
     let game_title = metadata::TITLE;
     let game_update = game::update;
 
-    let backend = if matches.is_present("remote") {
-        "remote"
-    } else if matches.is_present("sdl") {
-        "sdl"
-    } else if matches.is_present("glutin") {
-        "glutin"
-    } else {
-        default_graphics_backend
-    };
-
     match backend {
-        "remote" => run_remote(display_size, background, game_title, state, game_update),
-        "sdl" => run_sdl(display_size, background, game_title, state, game_update),
-        "glutin" => run_glutin(display_size, background, game_title, state, game_update),
+        "remote" => run_remote(
+            display_size,
+            background,
+            game_title,
+            state,
+            settings.clone(),
+            game_update,
+        ),
+        "sdl" => run_sdl(
+            display_size,
+            background,
+            game_title,
+            state,
+            settings.clone(),
+            game_update,
+        ),
+        "glutin" => run_glutin(
+            display_size,
+            background,
+            game_title,
+            state,
+            settings.clone(),
+            game_update,
+        ),
         _ => {
             log::error!("Unknown backend: {}", backend);
         }
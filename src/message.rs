@@ -0,0 +1,144 @@
+//! The log of the important things that happened in the game.
+//!
+//! Every message is a structured `Event` rather than plain text. The
+//! replays record the events too and the text shown to the player
+//! can change without touching the recordings.
+
+use crate::monster::CompanionBonus;
+
+use std::fmt::{Display, Error, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+/// The oldest messages are dropped once the log gets this long.
+pub const MAX_MESSAGES: usize = 1000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    /// Bumping into Anxieties increased the player's Will to this
+    /// value.
+    WillIncreased(i32),
+    CompanionJoined(CompanionBonus),
+    /// The companion left because the player picked another one.
+    CompanionLeft(CompanionBonus),
+    /// The companion won't follow the player while they're
+    /// intoxicated.
+    CompanionLeftIntoxicated(CompanionBonus),
+    Stunned(i32),
+    Panicked(i32),
+    VictoryNpcAppeared,
+    VictoryNpcLeft,
+    // NOTE: the saved games store the events by their index. Add the
+    // new ones at the end.
+    /// The player nearly overdosed and feels sick for this many
+    /// turns.
+    Nauseous(i32),
+    TooNauseousToEat,
+    /// The player got high too many times and the companion left
+    /// them for good.
+    CompanionGaveUp(CompanionBonus),
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        use self::Event::*;
+        match *self {
+            WillIncreased(will) => write!(f, "Facing your anxiety, your Will grows to {}.", will),
+            CompanionJoined(bonus) => write!(f, "A friend joins you ({}).", bonus),
+            CompanionLeft(bonus) => write!(f, "A friend leaves you ({}).", bonus),
+            CompanionLeftIntoxicated(bonus) => {
+                write!(
+                    f,
+                    "A friend won't follow you while you're high ({}).",
+                    bonus
+                )
+            }
+            Stunned(turns) => write!(f, "You're stunned for {} turns.", turns),
+            Panicked(turns) => write!(f, "You panic for {} turns.", turns),
+            Nauseous(turns) => write!(f, "That was close. You feel sick for {} turns.", turns),
+            TooNauseousToEat => f.write_str("You're too sick to eat."),
+            CompanionGaveUp(bonus) => write!(f, "A friend gave up on you ({}).", bonus),
+            VictoryNpcAppeared => f.write_str("Someone is waiting for you. Go find them!"),
+            VictoryNpcLeft => f.write_str("You got high. The one waiting for you is gone."),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub turn: i32,
+    pub event: Event,
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(f, "{}: {}", self.turn, self.event)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessageLog {
+    messages: Vec<Message>,
+}
+
+impl MessageLog {
+    pub fn push(&mut self, message: Message) {
+        if self.messages.len() >= MAX_MESSAGES {
+            self.messages.remove(0);
+        }
+        self.messages.push(message);
+    }
+
+    /// All the messages, the oldest first.
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// The last `count` messages, the oldest first.
+    pub fn recent(&self, count: usize) -> &[Message] {
+        let start = self.messages.len().saturating_sub(count);
+        &self.messages[start..]
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Event, Message, MessageLog, MAX_MESSAGES};
+
+    #[test]
+    fn test_recent_messages() {
+        let mut log = MessageLog::default();
+        assert!(log.recent(3).is_empty());
+
+        for turn in 0..5 {
+            log.push(Message {
+                turn,
+                event: Event::Stunned(turn),
+            });
+        }
+        let turns = log.recent(3).iter().map(|m| m.turn).collect::<Vec<_>>();
+        assert_eq!(turns, vec![2, 3, 4]);
+        assert_eq!(log.recent(10).len(), 5);
+    }
+
+    #[test]
+    fn test_drop_oldest_messages() {
+        let mut log = MessageLog::default();
+        for turn in 0..MAX_MESSAGES as i32 + 2 {
+            log.push(Message {
+                turn,
+                event: Event::VictoryNpcAppeared,
+            });
+        }
+        assert_eq!(log.len(), MAX_MESSAGES);
+        assert_eq!(log.messages()[0].turn, 2);
+    }
+}
@@ -1,13 +1,13 @@
-use self::Kind::*;
-
 use crate::{
     ai::{self, AIState, Behavior, Update},
     blocker::Blocker,
-    color::{self, Color},
+    color::Color,
+    distance_map::DistanceMaps,
     game::Action,
     player::{Modifier, PlayerInfo},
     point::Point,
     ranged_int::{InclusiveRange, Ranged},
+    status::StatusEffects,
     world::World,
 };
 
@@ -22,6 +22,8 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Monster {
+    /// The `Definition::id` of the monster.
+    pub id: String,
     pub kind: Kind,
     /// The *world position* of the monster
     pub position: Point,
@@ -38,22 +40,123 @@ pub struct Monster {
     pub accompanying_player: bool,
 
     pub ap: Ranged,
+    pub status: StatusEffects,
 }
 
-// TODO: we should make the various behaviours dependent on data
-// assigned to the entity, rather then matching over the entity type
-// every time.
-#[derive(Copy, Clone, PartialEq, Eq, Debug, PartialOrd, Ord, Serialize, Deserialize)]
+/// The kind decides the game rules that only apply to the NPCs and
+/// signposts. Everything else comes from the monster's `Definition`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum Kind {
-    Anxiety,
-    Depression,
-    Hunger,
-    Shadows,
-    Voices,
+    Regular,
+    /// Joins the player when they bump into it sober.
     Npc,
+    /// What's left of the Victory NPC when the player gets high.
     Signpost,
 }
 
+impl Default for Kind {
+    fn default() -> Self {
+        Kind::Regular
+    }
+}
+
+/// The name of the monster definitions file. See the `data` module.
+const DEFINITIONS_FILE: &str = "monsters.json";
+
+const BUILT_IN_DEFINITIONS: &str = include_str!("../data/monsters.json");
+
+/// The stats of one monster as written in the definitions file.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Definition {
+    /// Identifies the monster in the saved games and replays.
+    pub id: String,
+    #[serde(default)]
+    pub kind: Kind,
+    pub name: String,
+    pub glyph: char,
+    pub color: Color,
+    pub max_ap: i32,
+    pub behavior: Behavior,
+    #[serde(with = "crate::blocker::names")]
+    pub blockers: Blocker,
+    /// What happens to the player when the monster attacks them.
+    pub attack: Modifier,
+    pub die_after_attack: bool,
+    pub invincible: bool,
+    /// How likely is the monster to be generated on an empty tile.
+    /// See `generators::forrest::NO_MONSTER_WEIGHT`.
+    pub spawn_weight: i32,
+    /// Bumping into the monster counts towards the player's next
+    /// point of Will.
+    #[serde(default)]
+    pub grows_will: bool,
+    /// The monster loses an action point when it's close to the
+    /// player's companion.
+    #[serde(default)]
+    pub slowed_by_companions: bool,
+    /// The monster can be generated close to where the player
+    /// starts.
+    #[serde(default)]
+    pub near_start: bool,
+}
+
+impl Definition {
+    /// How much the monster's attack hurts the player.
+    pub fn danger(&self) -> &'static str {
+        use crate::player::Modifier::*;
+        match self.attack {
+            Death => "Deadly",
+            Attribute { will, .. } if will < 0 => "Very dangerous",
+            Attribute {
+                state_of_mind: 0, ..
+            } => "Harmless",
+            Attribute { .. } | Intoxication { .. } | Panic(_) | Stun(_) => "Dangerous",
+        }
+    }
+}
+
+/// The monster definitions the game ships with.
+pub fn built_in_definitions() -> Vec<Definition> {
+    crate::data::built_in(DEFINITIONS_FILE, BUILT_IN_DEFINITIONS)
+}
+
+/// Load the monster definitions from the user's definitions file
+/// falling back to the built-in ones.
+///
+/// The game places the NPCs and the signposts itself so there must
+/// be a definition of both kinds. The missing ones are taken from
+/// the built-in definitions.
+pub fn load_definitions() -> Vec<Definition> {
+    let loaded: Vec<Definition> = crate::data::load(DEFINITIONS_FILE, BUILT_IN_DEFINITIONS);
+    let mut definitions: Vec<Definition> = vec![];
+    for definition in loaded {
+        if definitions.iter().any(|d| d.id == definition.id) {
+            log::warn!(
+                "The `{}` monster is defined more than once. Using the first definition.",
+                definition.id
+            );
+        } else {
+            definitions.push(definition);
+        }
+    }
+    for &kind in &[Kind::Npc, Kind::Signpost] {
+        if !definitions.iter().any(|d| d.kind == kind) {
+            let built_in = built_in_definitions()
+                .into_iter()
+                .find(|d| d.kind == kind)
+                .unwrap_or_else(|| panic!("The built-in monsters have no `{:?}`.", kind));
+            log::warn!(
+                "There is no `{:?}` monster. Using the built-in `{}` definition.",
+                kind,
+                built_in.id
+            );
+            definitions.retain(|d| d.id != built_in.id);
+            definitions.push(built_in);
+        }
+    }
+    definitions
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum CompanionBonus {
     DoubleWillGrowth,
@@ -62,6 +165,18 @@ pub enum CompanionBonus {
     Victory,
 }
 
+impl CompanionBonus {
+    /// How many companions with this bonus can add up. The rest
+    /// don't give the player anything extra.
+    pub fn max_stack(self) -> i32 {
+        use self::CompanionBonus::*;
+        match self {
+            DoubleWillGrowth | HalveExhaustion => 2,
+            ExtraActionPoint | Victory => 1,
+        }
+    }
+}
+
 impl Display for CompanionBonus {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         use self::CompanionBonus::*;
@@ -89,85 +204,24 @@ impl Distribution<CompanionBonus> for Standard {
 }
 
 impl Monster {
-    pub fn new(kind: Kind, position: Point) -> Monster {
-        let die_after_attack = match kind {
-            Shadows | Voices => true,
-            Anxiety | Depression | Hunger | Npc | Signpost => false,
-        };
-
-        let max_ap = match kind {
-            Depression => 2,
-            Anxiety | Hunger | Shadows | Voices | Npc => 1,
-            Signpost => 0,
-        };
-
-        let behavior = match kind {
-            Depression => Behavior::LoneAttacker,
-            Anxiety => Behavior::LoneAttacker,
-            Hunger => Behavior::PackAttacker,
-            Shadows => Behavior::LoneAttacker,
-            Voices => Behavior::LoneAttacker,
-            Npc => Behavior::Friendly,
-            Signpost => Behavior::Immobile,
-        };
-
-        let invincible = match kind {
-            Npc | Signpost => true,
-            _ => false,
-        };
-
-        // NOTE: NPCs can't walk into the player, monsters can
-        let blockers = match kind {
-            Npc => Blocker::PLAYER | Blocker::WALL | Blocker::MONSTER,
-            _ => Blocker::WALL | Blocker::MONSTER,
-        };
-
-        let color = match kind {
-            Depression => color::depression,
-            Anxiety => color::anxiety,
-            Hunger => color::hunger,
-            Shadows => color::shadows,
-            Voices => color::voices,
-            Npc => color::npc_speed,
-            Signpost => color::signpost,
-        };
-
+    pub fn new(definition: &Definition, position: Point) -> Monster {
         Monster {
-            kind,
+            id: definition.id.clone(),
+            kind: definition.kind,
             position,
             dead: false,
-            die_after_attack,
-            invincible,
-            behavior,
+            die_after_attack: definition.die_after_attack,
+            invincible: definition.invincible,
+            behavior: definition.behavior,
             ai_state: AIState::Idle,
-            ap: Ranged::new_min(InclusiveRange(0, max_ap)),
-            blockers,
+            ap: Ranged::new_min(InclusiveRange(0, definition.max_ap)),
+            blockers: definition.blockers,
             path: vec![],
             trail: None,
-            color,
+            color: definition.color,
             companion_bonus: None,
             accompanying_player: false,
-        }
-    }
-
-    pub fn attack_damage(&self) -> Modifier {
-        use crate::player::Modifier::*;
-        match self.kind {
-            Anxiety => Attribute {
-                will: -1,
-                state_of_mind: 0,
-            },
-            Depression => Death,
-            Hunger => Attribute {
-                will: 0,
-                state_of_mind: -20,
-            },
-            Shadows => Panic(4),
-            Voices => Stun(4),
-            Npc | Signpost => Attribute {
-                will: 0,
-                state_of_mind: 0,
-            }, // NOTE: no-op
+            status: StatusEffects::default(),
         }
     }
 
@@ -175,16 +229,21 @@ impl Monster {
         &self,
         player_info: PlayerInfo,
         world: &mut World,
+        distance_maps: &DistanceMaps,
         rng: &mut R,
     ) -> (Update, Action) {
         if self.dead {
             panic!(format!("{:?} is dead, cannot run actions on it.", self));
         }
         match self.behavior {
-            Behavior::LoneAttacker => ai::lone_attacker_act(self, player_info, world, rng),
-            Behavior::PackAttacker => ai::pack_attacker_act(self, player_info, world, rng),
-            Behavior::Friendly => ai::friendly_act(self, player_info, world, rng),
-            Behavior::Immobile => ai::noop_act(self, player_info, world, rng),
+            Behavior::LoneAttacker => {
+                ai::lone_attacker_act(self, player_info, world, distance_maps, rng)
+            }
+            Behavior::PackAttacker => {
+                ai::pack_attacker_act(self, player_info, world, distance_maps, rng)
+            }
+            Behavior::Friendly => ai::friendly_act(self, player_info, world, distance_maps, rng),
+            Behavior::Immobile => ai::noop_act(self, player_info, world, distance_maps, rng),
         }
     }
 
@@ -200,7 +259,10 @@ impl Monster {
 
     pub fn new_turn(&mut self) {
         if !self.dead {
+            self.status.tick();
             self.ap.set_to_max();
+            let max_ap = self.ap.to_int();
+            self.ap -= max_ap - self.status.action_points(max_ap);
             self.trail = None;
         }
     }
@@ -209,27 +271,12 @@ impl Monster {
         !self.dead
     }
 
-    pub fn glyph(&self) -> char {
-        match self.kind {
-            Anxiety => 'a',
-            Depression => 'D',
-            Hunger => 'h',
-            Shadows => 'S',
-            Voices => 'v',
-            Npc => '@',
-            Signpost => '!',
-        }
-    }
-
-    pub fn name(&self) -> &str {
-        match self.kind {
-            Anxiety => "Anxiety",
-            Depression => "Depression",
-            Hunger => "Hunger",
-            Shadows => "Shadows",
-            Voices => "Voices",
-            Npc => "NPC",
-            Signpost => "signpost",
+    /// Whether the monster can harm the player. The NPCs and
+    /// signposts can't.
+    pub fn is_hostile(&self) -> bool {
+        match self.behavior {
+            Behavior::LoneAttacker | Behavior::PackAttacker => true,
+            Behavior::Friendly | Behavior::Immobile => false,
         }
     }
 }
@@ -237,7 +284,145 @@ impl Monster {
 impl std::fmt::Display for Monster {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         write!(f, "Monster {{")?;
-        write!(f, "name: {}, position: {:?}", self.name(), self.position)?;
+        write!(f, "id: {}, position: {:?}", self.id, self.position)?;
         write!(f, "}}")
     }
 }
+
+/// The monsters as the 1.0.0 release saved them. Only used to read
+/// its saved games.
+pub mod legacy {
+    use super::CompanionBonus;
+    use crate::{
+        ai::{AIState, Behavior},
+        blocker::Blocker,
+        color::Color,
+        point::Point,
+        ranged_int::Ranged,
+        status::StatusEffects,
+    };
+
+    use serde::Deserialize;
+
+    #[derive(Copy, Clone, Debug, Deserialize)]
+    pub enum Kind {
+        Anxiety,
+        Depression,
+        Hunger,
+        Shadows,
+        Voices,
+        Npc,
+        Signpost,
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    pub struct Monster {
+        kind: Kind,
+        position: Point,
+        dead: bool,
+        die_after_attack: bool,
+        invincible: bool,
+        behavior: Behavior,
+        ai_state: AIState,
+        blockers: Blocker,
+        path: Vec<Point>,
+        trail: Option<Point>,
+        color: Color,
+        companion_bonus: Option<CompanionBonus>,
+        accompanying_player: bool,
+        ap: Ranged,
+    }
+
+    impl From<Monster> for super::Monster {
+        fn from(old: Monster) -> Self {
+            // NOTE: the built-in definitions use the old kind names
+            // as their ids.
+            let kind = match old.kind {
+                Kind::Npc => super::Kind::Npc,
+                Kind::Signpost => super::Kind::Signpost,
+                _ => super::Kind::Regular,
+            };
+            super::Monster {
+                id: format!("{:?}", old.kind),
+                kind,
+                position: old.position,
+                dead: old.dead,
+                die_after_attack: old.die_after_attack,
+                invincible: old.invincible,
+                behavior: old.behavior,
+                ai_state: old.ai_state,
+                blockers: old.blockers,
+                path: old.path,
+                trail: old.trail,
+                color: old.color,
+                companion_bonus: old.companion_bonus,
+                accompanying_player: old.accompanying_player,
+                ap: old.ap,
+                status: StatusEffects::default(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{built_in_definitions, Definition, Kind, Monster};
+    use crate::{color, player::Modifier, point::Point};
+
+    #[test]
+    fn test_built_in_definitions() {
+        let definitions = built_in_definitions();
+        let definition = |id| definitions.iter().find(|d| d.id == id).unwrap();
+        for id in &[
+            "Anxiety",
+            "Depression",
+            "Hunger",
+            "Shadows",
+            "Voices",
+            "Npc",
+            "Signpost",
+        ] {
+            assert_eq!(definitions.iter().filter(|d| d.id == *id).count(), 1);
+        }
+
+        let depression = definition("Depression");
+        assert_eq!(depression.max_ap, 2);
+        assert_eq!(depression.attack, Modifier::Death);
+        assert!(depression.slowed_by_companions);
+        assert_eq!(definition("Anxiety").color, color::anxiety);
+        assert!(definition("Anxiety").grows_will);
+        assert!(definition("Voices").die_after_attack);
+        assert!(definition("Voices").near_start);
+        assert_eq!(definition("Npc").kind, Kind::Npc);
+        assert!(definition("Npc").invincible);
+        assert_eq!(definition("Signpost").kind, Kind::Signpost);
+        assert_eq!(definition("Signpost").spawn_weight, 0);
+    }
+
+    #[test]
+    fn test_new_monster_from_data() {
+        let definition: Definition = serde_json::from_str(
+            r#"{
+                "id": "Dread",
+                "name": "Dread",
+                "glyph": "d",
+                "color": { "r": 90, "g": 90, "b": 160 },
+                "max_ap": 1,
+                "behavior": "PackAttacker",
+                "blockers": ["WALL", "MONSTER"],
+                "attack": { "Panic": 2 },
+                "die_after_attack": false,
+                "invincible": false,
+                "spawn_weight": 3
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(definition.kind, Kind::Regular);
+        assert!(!definition.grows_will);
+
+        let monster = Monster::new(&definition, Point::new(3, 4));
+        assert_eq!(monster.id, "Dread");
+        assert_eq!(monster.kind, Kind::Regular);
+        assert_eq!(monster.color, definition.color);
+    }
+}
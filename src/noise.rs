@@ -0,0 +1,81 @@
+//! Seeded noise computed from the world coordinates.
+//!
+//! The same seed and position always give the same value no matter
+//! which chunk asks for it, so whatever is built on top of the noise
+//! continues seamlessly across the chunk boundaries.
+
+/// A pseudo-random value in `0.0..1.0` for the given integer
+/// coordinates.
+pub fn lattice(seed: u32, x: i32, y: i32) -> f32 {
+    // NOTE: the finaliser of the MurmurHash3's 64-bit hash function
+    let mut hash = u64::from(seed).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ u64::from(x as u32).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ u64::from(y as u32).wrapping_mul(0x1656_67B1_9E37_79F9);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xC4CE_B9FE_1A85_EC53);
+    hash ^= hash >> 33;
+    (hash >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Value noise in `0.0..1.0`: the `lattice` values smoothly
+/// interpolated between the integer coordinates.
+pub fn value(seed: u32, x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (smoothstep(x - x0), smoothstep(y - y0));
+    let (x0, y0) = (x0 as i32, y0 as i32);
+
+    let top = lerp(lattice(seed, x0, y0), lattice(seed, x0 + 1, y0), tx);
+    let bottom = lerp(lattice(seed, x0, y0 + 1), lattice(seed, x0 + 1, y0 + 1), tx);
+    lerp(top, bottom, ty)
+}
+
+/// Fractal noise in `0.0..1.0`: the sum of `octaves` layers of the
+/// `value` noise, each with double the frequency and half the
+/// amplitude of the previous one. This adds smaller details to the
+/// large shapes.
+pub fn fractal(seed: u32, x: f32, y: f32, octaves: u32) -> f32 {
+    let mut sum = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    let mut total_amplitude = 0.0;
+    for octave in 0..octaves {
+        sum += amplitude * value(seed.wrapping_add(octave), x * frequency, y * frequency);
+        total_amplitude += amplitude;
+        amplitude /= 2.0;
+        frequency *= 2.0;
+    }
+    sum / total_amplitude
+}
+
+fn smoothstep(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fractal, lattice, value};
+
+    #[test]
+    fn test_value_noise() {
+        for &(x, y) in &[(0, 0), (-3, 7), (1000, -1000)] {
+            let lattice_value = lattice(42, x, y);
+            assert!((0.0..1.0).contains(&lattice_value));
+            assert_eq!(value(42, x as f32, y as f32), lattice_value);
+        }
+        assert_ne!(lattice(1, 5, 5), lattice(2, 5, 5));
+
+        let between = value(42, 0.5, 0.0);
+        let (a, b) = (lattice(42, 0, 0), lattice(42, 1, 0));
+        assert!(between >= a.min(b) && between <= a.max(b));
+
+        assert_eq!(fractal(42, 3.25, -7.5, 1), value(42, 3.25, -7.5));
+        let detailed = fractal(42, 3.25, -7.5, 4);
+        assert!((0.0..1.0).contains(&detailed));
+    }
+}
@@ -1,3 +1,111 @@
-// pub mod original;
-// pub mod dawnbringer32;
 pub mod dawnbringer16;
+pub mod dawnbringer32;
+pub mod original;
+
+use crate::color::{Color, ColorAlpha};
+
+use serde::{Deserialize, Serialize};
+
+/// The colour palette the game is rendered with.
+///
+/// The game code uses the colours from `color.rs` which are always
+/// taken from the DawnBringer 16 palette. Any other palette is
+/// applied at the very end by replacing each base colour with its
+/// counterpart.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Palette {
+    Original,
+    DawnBringer16,
+    DawnBringer32,
+}
+
+macro_rules! base_colors {
+    ($palette:ident) => {
+        [
+            $palette::BLACK,
+            $palette::WHITE,
+            $palette::GREY,
+            $palette::DARK_GREY,
+            $palette::BLUE,
+            $palette::BRIGHT_BLUE,
+            $palette::DIM_BLUE,
+            $palette::BRIGHT_GREEN,
+            $palette::DIM_GREEN,
+            $palette::NATURAL_GREEN,
+            $palette::RED,
+            $palette::PURPLE,
+            $palette::BROWN,
+            $palette::FUNKY_BLUE,
+            $palette::FUNKY_RED,
+        ]
+    };
+}
+
+impl Palette {
+    pub fn all() -> impl Iterator<Item = Palette> {
+        [
+            Palette::Original,
+            Palette::DawnBringer16,
+            Palette::DawnBringer32,
+        ]
+        .iter()
+        .cloned()
+    }
+
+    /// The name used in the settings file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Palette::Original => "original",
+            Palette::DawnBringer16 => "dawnbringer16",
+            Palette::DawnBringer32 => "dawnbringer32",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Palette> {
+        Palette::all().find(|palette| palette.name() == name)
+    }
+
+    /// The palette following this one (wrapping around).
+    pub fn next(self) -> Palette {
+        match self {
+            Palette::Original => Palette::DawnBringer16,
+            Palette::DawnBringer16 => Palette::DawnBringer32,
+            Palette::DawnBringer32 => Palette::Original,
+        }
+    }
+
+    fn base_colors(self) -> [Color; 15] {
+        match self {
+            Palette::Original => base_colors!(original),
+            Palette::DawnBringer16 => base_colors!(dawnbringer16),
+            Palette::DawnBringer32 => base_colors!(dawnbringer32),
+        }
+    }
+
+    /// Replace a DawnBringer 16 base colour with the one from this
+    /// palette. Any other colour (e.g. an animation blending two base
+    /// colours) is returned unchanged.
+    pub fn remap(self, color: Color) -> Color {
+        if self == Palette::DawnBringer16 {
+            return color;
+        }
+        Palette::DawnBringer16
+            .base_colors()
+            .iter()
+            .position(|&base| base == color)
+            .map_or(color, |index| self.base_colors()[index])
+    }
+
+    pub fn remap_alpha(self, color: ColorAlpha) -> ColorAlpha {
+        ColorAlpha {
+            rgb: self.remap(color.rgb),
+            alpha: color.alpha,
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::DawnBringer16
+    }
+}
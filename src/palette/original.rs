@@ -1,4 +1,4 @@
-use color::Color;
+use crate::color::Color;
 
 pub const BLACK: Color = Color { r: 0, g: 0, b: 0 };
 pub const WHITE: Color = Color { r: 255, g: 255, b: 255 };
@@ -1,9 +1,8 @@
-use crate::{blocker, point::Point, world::World};
+use crate::{blocker, formula, point::Point, world::World};
 
 use std::{
     cmp::Ordering,
     collections::{BinaryHeap, HashMap},
-    f32,
 };
 
 #[derive(Debug)]
@@ -12,157 +11,258 @@ pub struct Path {
 }
 
 impl Path {
+    /// Find the cheapest path from `from` to `to`. See
+    /// `SearchContext::find`.
     pub fn find(
         from: Point,
         to: Point,
-        world: &mut World,
+        world: &World,
         blockers: blocker::Blocker,
         player_position: Point,
         calculation_limit: i32,
     ) -> Self {
+        SearchContext::default().find(
+            from,
+            to,
+            world,
+            blockers,
+            player_position,
+            calculation_limit,
+        )
+    }
+
+    /// The number of steps to necessary to reach the destination. If
+    /// no path was found, it is `0`.
+    pub fn len(&self) -> usize {
+        self.path.len()
+    }
+}
+
+impl Iterator for Path {
+    type Item = Point;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.path.pop()
+    }
+}
+
+/// The buffers used by the path search. Keep one around when finding
+/// a lot of paths (e.g. for all the monsters) so they don't have to
+/// be allocated every time.
+#[derive(Debug, Default)]
+pub struct SearchContext {
+    frontier: BinaryHeap<State>,
+    came_from: HashMap<Point, Point>,
+    cost_so_far: HashMap<Point, i32>,
+}
+
+impl SearchContext {
+    /// Find the cheapest path from `from` to `to` with the tiles
+    /// costing their `formula::move_cost`.
+    ///
+    /// The search gives up after looking at `calculation_limit`
+    /// tiles. The path is empty if it didn't reach the destination.
+    pub fn find(
+        &mut self,
+        from: Point,
+        to: Point,
+        world: &World,
+        blockers: blocker::Blocker,
+        player_position: Point,
+        calculation_limit: i32,
+    ) -> Path {
+        match self.search(
+            from,
+            to,
+            world,
+            blockers,
+            player_position,
+            calculation_limit,
+        ) {
+            Some(end) if end == to => self.path(from, end),
+            _ => Path { path: vec![] },
+        }
+    }
+
+    /// Like `find`, but when the destination can't be reached within
+    /// the `calculation_limit`, return the path to the tile closest to
+    /// it instead.
+    pub fn find_partial(
+        &mut self,
+        from: Point,
+        to: Point,
+        world: &World,
+        blockers: blocker::Blocker,
+        player_position: Point,
+        calculation_limit: i32,
+    ) -> Path {
+        match self.search(
+            from,
+            to,
+            world,
+            blockers,
+            player_position,
+            calculation_limit,
+        ) {
+            Some(end) => self.path(from, end),
+            None => Path { path: vec![] },
+        }
+    }
+
+    /// Run the A* search and return either `to` or the explored tile
+    /// closest to it.
+    fn search(
+        &mut self,
+        from: Point,
+        to: Point,
+        world: &World,
+        blockers: blocker::Blocker,
+        player_position: Point,
+        calculation_limit: i32,
+    ) -> Option<Point> {
+        self.frontier.clear();
+        self.came_from.clear();
+        self.cost_so_far.clear();
+
         if from == to {
-            return Path { path: vec![] };
+            return None;
         }
 
         if !world.walkable(to, blockers, player_position) {
-            return Path { path: vec![] };
+            return None;
         }
 
         if from.tile_distance(to) == 1 {
-            return Path { path: vec![to] };
+            self.came_from.insert(to, from);
+            return Some(to);
         }
 
-        let neighbors = |current: Point| {
-            assert!(world.within_bounds(current));
-            let dp: [Point; 9] = [
-                (-1, -1).into(),
-                (-1, 0).into(),
-                (-1, 1).into(),
-                (0, -1).into(),
-                (0, 0).into(),
-                (0, 1).into(),
-                (1, -1).into(),
-                (1, 0).into(),
-                (1, 1).into(),
-            ];
-            dp.iter()
-                .map(|&d| current + d)
-                .filter(|&point| {
-                    world.within_bounds(point) && world.walkable(point, blockers, player_position)
-                })
-                .collect::<Vec<_>>()
-        };
-
-        let cost = |current: Point, next: Point| -> f32 {
-            assert!((current.x - next.x).abs() <= 1);
-            assert!((current.y - next.y).abs() <= 1);
-            1.0
+        let walkable = |point: Point| {
+            world.within_bounds(point) && world.walkable(point, blockers, player_position)
         };
 
-        let heuristic = |destination: Point, next: Point| -> f32 {
-            ((destination.x - next.x).abs() + (destination.y - next.y).abs()) as f32
-        };
-
-        let mut frontier = BinaryHeap::new();
-        frontier.push(State {
+        self.frontier.push(State {
+            priority: formula::PATHFINDING_HEURISTIC_WEIGHT * heuristic(from, to),
+            estimate: heuristic(from, to),
+            manhattan_distance: manhattan_distance(from, to),
             position: from,
-            cost: 0.0,
         });
-        let mut came_from = HashMap::new();
-        let mut cost_so_far = HashMap::new();
-
-        came_from.insert(from, None);
-        cost_so_far.insert(from, 0.0);
+        self.cost_so_far.insert(from, 0);
+        // NOTE: the closest tile and its cost
+        let mut best = (from, 0);
 
         let mut calculation_steps = 0;
 
-        while let Some(current) = frontier.pop() {
+        while let Some(current) = self.frontier.pop() {
             if current.position == to {
-                break;
+                return Some(to);
+            }
+            let current_cost = self.cost_so_far[&current.position];
+            // NOTE: skip the tiles we've since found a cheaper way to
+            let stale = current.priority
+                > current_cost + formula::PATHFINDING_HEURISTIC_WEIGHT * current.estimate;
+            if stale {
+                continue;
             }
             if calculation_steps >= calculation_limit {
                 break;
             } else {
                 calculation_steps += 1;
             }
-            let neigh = neighbors(current.position);
-            for &next in &neigh {
-                let new_cost = cost_so_far[&current.position] + cost(current.position, next);
-                let val = cost_so_far.entry(next).or_insert(f32::MAX);
-                if new_cost < *val {
-                    *val = new_cost;
-                    let priority = new_cost + heuristic(to, next);
-                    frontier.push(State {
+            if (current.estimate, current_cost) < (heuristic(best.0, to), best.1) {
+                best = (current.position, current_cost);
+            }
+            for &offset in NEIGHBOURS.iter() {
+                let next = current.position + offset;
+                if !walkable(next) {
+                    continue;
+                }
+                let new_cost = current_cost + world.move_cost(next);
+                let cheaper = self
+                    .cost_so_far
+                    .get(&next)
+                    .map_or(true, |&cost| new_cost < cost);
+                if cheaper {
+                    self.cost_so_far.insert(next, new_cost);
+                    self.came_from.insert(next, current.position);
+                    let estimate = heuristic(next, to);
+                    self.frontier.push(State {
+                        priority: new_cost + formula::PATHFINDING_HEURISTIC_WEIGHT * estimate,
+                        estimate,
+                        manhattan_distance: manhattan_distance(next, to),
                         position: next,
-                        cost: priority,
                     });
-                    came_from.insert(next, Some(current.position));
                 }
             }
         }
 
-        let path = {
-            let mut current = to;
-            let mut path_buffer = vec![current];
-            while current != from {
-                match came_from.get(&current) {
-                    Some(&Some(new_current)) => {
-                        current = new_current;
-                        if current != from {
-                            path_buffer.push(current);
-                        }
-                    }
-                    Some(&None) => panic!(
-                        "Every point except for the initial \
-                         one (`from`) one should be some."
-                    ),
-                    None => {
-                        path_buffer = vec![];
-                        break;
-                    }
-                }
-            }
-            path_buffer
-        };
-
-        assert_eq!(None, path.iter().find(|&&p| p == from));
-        Path { path }
+        if best.0 == from {
+            None
+        } else {
+            Some(best.0)
+        }
     }
 
-    /// The number of steps to necessary to reach the destination. If
-    /// no path was found, it is `0`.
-    pub fn len(&self) -> usize {
-        self.path.len()
+    fn path(&self, from: Point, to: Point) -> Path {
+        let mut path = vec![];
+        let mut current = to;
+        while current != from {
+            path.push(current);
+            current = self.came_from[&current];
+        }
+        Path { path }
     }
 }
 
-impl Iterator for Path {
-    type Item = Point;
+/// The eight directions we can move in.
+pub const NEIGHBOURS: [Point; 8] = [
+    Point { x: -1, y: -1 },
+    Point { x: -1, y: 0 },
+    Point { x: -1, y: 1 },
+    Point { x: 0, y: -1 },
+    Point { x: 0, y: 1 },
+    Point { x: 1, y: -1 },
+    Point { x: 1, y: 0 },
+    Point { x: 1, y: 1 },
+];
+
+/// The lowest possible cost of getting from `from` to `to`.
+///
+/// The diagonal steps cost the same as the straight ones and no step
+/// costs less than `formula::MIN_MOVE_COST` so this never
+/// overestimates. It never drops by more than the cost of a step
+/// either so it's consistent.
+fn heuristic(from: Point, to: Point) -> i32 {
+    from.tile_distance(to) * formula::MIN_MOVE_COST
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.path.pop()
-    }
+fn manhattan_distance(from: Point, to: Point) -> i32 {
+    (from.x - to.x).abs() + (from.y - to.y).abs()
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 struct State {
-    cost: f32,
+    /// The cost so far plus the weighted estimate of the rest.
+    priority: i32,
+    /// The estimated cost of the rest of the path.
+    estimate: i32,
+    /// Breaks the ties between the equally good tiles so the path
+    /// goes straight rather than zig-zag.
+    manhattan_distance: i32,
     position: Point,
 }
 
-impl Eq for State {}
-
+/// NOTE: `BinaryHeap` is a max-heap so the lowest priority compares
+/// as the greatest. On a tie, the tile closer to the destination goes
+/// first.
 impl Ord for State {
     fn cmp(&self, other: &Self) -> Ordering {
-        assert!(self.cost.is_finite());
-        assert!(other.cost.is_finite());
-        if other.cost > self.cost {
-            Ordering::Greater
-        } else if other.cost < self.cost {
-            Ordering::Less
-        } else {
-            Ordering::Equal
-        }
+        other
+            .priority
+            .cmp(&self.priority)
+            .then(other.estimate.cmp(&self.estimate))
+            .then(other.manhattan_distance.cmp(&self.manhattan_distance))
+            .then((other.position.x, other.position.y).cmp(&(self.position.x, self.position.y)))
     }
 }
 
@@ -174,12 +274,14 @@ impl PartialOrd for State {
 
 #[cfg(test)]
 mod test {
-    use super::Path;
+    use super::{Path, SearchContext};
     use crate::blocker::Blocker;
     use crate::player::{Mind, PlayerInfo};
     use crate::point::Point;
     use crate::world::World;
 
+    const LIMIT: i32 = 100;
+
     struct Board {
         start: Point,
         destination: Point,
@@ -188,7 +290,7 @@ mod test {
 
     fn make_board(text: &str) -> Board {
         use crate::level::Tile;
-        use crate::level::TileKind::{Empty, Tree};
+        use crate::level::TileKind::{Empty, Road, ShallowWater, Tree};
         let mut start = Point { x: 0, y: 0 };
         let mut destination = Point { x: 0, y: 0 };
         let mut x = 0;
@@ -201,14 +303,25 @@ mod test {
         assert!(width > 0);
         assert!(lines.iter().all(|line| line.chars().count() == width));
 
-        let mut rng = rand::IsaacRng::new_from_u64(0);
+        let mut rng = crate::random::from_seed(0);
         let player_info = PlayerInfo {
             pos: Point::new(0, 0),
             mind: Mind::Sober(crate::ranged_int::Ranged::new_max(crate::formula::SOBER)),
             max_ap: 1,
             will: 3,
         };
-        let mut world = World::new(&mut rng, 0, 64, 32, player_info);
+        let monster_definitions = crate::monster::built_in_definitions();
+        let item_definitions = crate::item::built_in_definitions();
+        let mut world = World::new(
+            &mut rng,
+            0,
+            64,
+            32,
+            player_info,
+            crate::generators::Generator::Forrest,
+            monster_definitions,
+            item_definitions,
+        );
         // clear out the world
         for x in 0..16 {
             for y in 0..16 {
@@ -244,6 +357,8 @@ mod test {
                     's' => Empty,
                     'd' => Empty,
                     'x' => Tree,
+                    '=' => Road,
+                    '~' => ShallowWater,
                     _ => unreachable!(),
                 };
                 let pos = Point {
@@ -286,6 +401,7 @@ mod test {
             &mut board.world,
             Blocker::WALL,
             Point::new(0, 0),
+            LIMIT,
         );
         assert_eq!(1, path.len());
         let expected = [(2, 1)]
@@ -313,6 +429,7 @@ mod test {
             &mut board.world,
             Blocker::WALL,
             Point::new(0, 0),
+            LIMIT,
         );
         assert_eq!(0, path.len());
         let expected: Vec<Point> = vec![];
@@ -335,6 +452,7 @@ mod test {
             &mut board.world,
             Blocker::WALL,
             Point::new(0, 0),
+            LIMIT,
         );
         assert_eq!(7, path.len());
         let expected = [(2, 1), (3, 1), (4, 1), (5, 1), (6, 1), (7, 1), (8, 1)]
@@ -361,6 +479,7 @@ s..........
             &mut board.world,
             Blocker::WALL,
             Point::new(0, 0),
+            LIMIT,
         );
         assert_eq!(3, path.len());
         let expected = [(1, 1), (2, 2), (3, 3)]
@@ -387,6 +506,7 @@ xxxxx......
             &mut board.world,
             Blocker::WALL,
             Point::new(0, 0),
+            LIMIT,
         );
         assert_eq!(0, path.len());
     }
@@ -407,6 +527,7 @@ xxxxx......
             &mut board.world,
             Blocker::WALL,
             Point::new(0, 0),
+            LIMIT,
         );
         assert_eq!(7, path.len());
         let expected = [(2, 2), (3, 3), (4, 3), (5, 3), (6, 3), (7, 3), (8, 3)]
@@ -438,6 +559,7 @@ xxxxx......
             &mut board.world,
             Blocker::WALL,
             Point::new(0, 0),
+            LIMIT,
         );
         assert_eq!(9, path.len());
         let expected = [
@@ -457,4 +579,90 @@ xxxxx......
         .collect::<Vec<Point>>();
         assert_eq!(expected, path.collect::<Vec<_>>());
     }
+
+    #[test]
+    fn test_avoid_water() {
+        let board = make_board(
+            "
+..*****....
+.s~~~~~~d..
+...........
+...........
+",
+        );
+        let path: Path = Path::find(
+            board.start,
+            board.destination,
+            &board.world,
+            Blocker::WALL,
+            Point::new(0, 0),
+            LIMIT,
+        );
+        let expected = [(2, 0), (3, 0), (4, 0), (5, 0), (6, 0), (7, 0), (8, 1)]
+            .iter()
+            .cloned()
+            .map(Into::into)
+            .collect::<Vec<Point>>();
+        assert_eq!(expected, path.collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_prefer_road() {
+        let board = make_board(
+            "
+...........
+.s.......d.
+..=======..
+...........
+",
+        );
+        let path: Path = Path::find(
+            board.start,
+            board.destination,
+            &board.world,
+            Blocker::WALL,
+            Point::new(0, 0),
+            LIMIT,
+        );
+        let path = path.collect::<Vec<_>>();
+        assert_eq!(8, path.len());
+        assert!(path[..7].iter().all(|pos| pos.y == 2));
+    }
+
+    #[test]
+    fn test_partial_path() {
+        let board = make_board(
+            "
+.......x...
+.s.....x.d.
+.......x...
+.......x...
+",
+        );
+        let mut search = SearchContext::default();
+        let path = search.find(
+            board.start,
+            board.destination,
+            &board.world,
+            Blocker::WALL,
+            Point::new(0, 0),
+            5,
+        );
+        assert_eq!(0, path.len());
+
+        let path = search.find_partial(
+            board.start,
+            board.destination,
+            &board.world,
+            Blocker::WALL,
+            Point::new(0, 0),
+            5,
+        );
+        let path = path.collect::<Vec<_>>();
+        assert!(!path.is_empty());
+        let end = *path.last().unwrap();
+        assert!(
+            end.tile_distance(board.destination) < board.start.tile_distance(board.destination)
+        );
+    }
 }
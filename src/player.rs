@@ -5,9 +5,13 @@ use crate::{
     monster::{CompanionBonus, Monster},
     point::Point,
     ranged_int::Ranged,
+    status::{self, StatusEffects},
 };
 
-use std::fmt::{Display, Error, Formatter};
+use std::{
+    cmp,
+    fmt::{Display, Error, Formatter},
+};
 
 use serde::{Deserialize, Serialize};
 
@@ -51,6 +55,35 @@ impl Mind {
     }
 }
 
+/// Describes the effect, e.g. `Will -1`.
+impl Display for Modifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        use self::Modifier::*;
+        match *self {
+            Death => write!(f, "Death"),
+            Attribute {
+                will,
+                state_of_mind,
+            } => match (will, state_of_mind) {
+                (0, 0) => write!(f, "No effect"),
+                (will, 0) => write!(f, "Will {:+}", will),
+                (0, state_of_mind) => write!(f, "Mind {:+}", state_of_mind),
+                (will, state_of_mind) => write!(f, "Will {:+}, Mind {:+}", will, state_of_mind),
+            },
+            Intoxication {
+                state_of_mind,
+                tolerance_increase,
+            } => write!(
+                f,
+                "Intoxication {:+}, Tolerance {:+}",
+                state_of_mind, tolerance_increase
+            ),
+            Panic(turns) => write!(f, "Panic for {} turns", turns),
+            Stun(turns) => write!(f, "Stun for {} turns", turns),
+        }
+    }
+}
+
 impl Display for Mind {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         use self::Mind::*;
@@ -70,7 +103,7 @@ pub enum Bonus {
     UncoverMap,
 }
 
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum CauseOfDeath {
     Exhausted,
     Overdosed,
@@ -93,8 +126,7 @@ pub struct Player {
     pub mind: Mind,
     pub will: Ranged,
     pub tolerance: i32,
-    pub panic: Ranged,
-    pub stun: Ranged,
+    pub status: StatusEffects,
 
     pub pos: Point,
     pub inventory: Vec<Item>,
@@ -109,6 +141,9 @@ pub struct Player {
     pub invincible: bool,
     pub perpetrator: Option<Monster>,
 
+    /// The action points in `formula::AP_FRACTIONS` of a point. It
+    /// goes below zero when a move costs more than the player had
+    /// left and the rest is taken from the next turn.
     ap: i32,
 }
 
@@ -118,15 +153,14 @@ impl Player {
             mind: Mind::Withdrawal(Ranged::new_max(WITHDRAWAL)),
             will: Ranged::new(formula::PLAYER_STARTING_WILL, WILL),
             tolerance: 0,
-            panic: Ranged::new_min(formula::PANIC_TURNS),
-            stun: Ranged::new_min(formula::STUN_TURNS),
+            status: StatusEffects::default(),
             pos,
             inventory: vec![],
             anxiety_counter: Ranged::new_min(ANXIETIES_PER_WILL),
             dead: false,
             invincible,
             perpetrator: None,
-            ap: formula::PLAYER_BASE_AP,
+            ap: formula::PLAYER_BASE_AP * formula::AP_FRACTIONS,
             bonus: Bonus::None,
             bonuses: Vec::with_capacity(10),
             current_high_streak: 0,
@@ -147,27 +181,36 @@ impl Player {
         self.pos = new_position;
     }
 
+    /// The action points left in `formula::AP_FRACTIONS` of a point.
     pub fn ap(&self) -> i32 {
         self.ap
     }
 
     pub fn spend_ap(&mut self, count: i32) {
-        assert!(count <= self.ap);
-        self.ap -= count;
+        self.spend_ap_fractions(count * formula::AP_FRACTIONS);
+    }
+
+    /// Spend the action points given in `formula::AP_FRACTIONS` of a
+    /// point, e.g. the `formula::move_cost`.
+    pub fn spend_ap_fractions(&mut self, fractions: i32) {
+        assert!(self.has_ap(1));
+        self.ap -= fractions;
     }
 
+    /// Can the player act `count` more times? Any part of a point is
+    /// enough for the last action: what it costs over that is taken
+    /// from the next turn.
     pub fn has_ap(&self, count: i32) -> bool {
-        self.ap >= count
+        self.ap > (count - 1) * formula::AP_FRACTIONS
     }
 
     pub fn new_turn(&mut self) {
         if self.alive() {
-            self.stun -= 1;
-            self.panic -= 1;
+            self.status.tick();
 
             let mind_drop = formula::mind_drop_per_turn(&self.bonuses);
             self.mind = formula::mind_take_turn(self.mind, mind_drop);
-            self.ap = self.max_ap();
+            self.ap = cmp::min(self.ap, 0) + self.max_ap() * formula::AP_FRACTIONS;
         }
     }
 
@@ -198,10 +241,10 @@ impl Player {
                 self.tolerance += tolerance_increase;
             }
             Panic(turns) => {
-                self.panic += turns;
+                self.status.add(status::Kind::Panic, turns);
             }
             Stun(turns) => {
-                self.stun += turns;
+                self.status.add(status::Kind::Stun, turns);
             }
         }
 
@@ -229,3 +272,68 @@ impl Player {
         }
     }
 }
+
+/// The player as the 1.0.0 release saved them. Only used to read its
+/// saved games.
+pub mod legacy {
+    use super::{Bonus, Mind};
+    use crate::{
+        formula,
+        item::legacy::Item,
+        monster::{legacy::Monster, CompanionBonus},
+        point::Point,
+        ranged_int::Ranged,
+        status::{self, StatusEffects},
+    };
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Player {
+        mind: Mind,
+        will: Ranged,
+        tolerance: i32,
+        panic: Ranged,
+        stun: Ranged,
+        pos: Point,
+        inventory: Vec<Item>,
+        anxiety_counter: Ranged,
+        bonus: Bonus,
+        bonuses: Vec<CompanionBonus>,
+        current_high_streak: i32,
+        longest_high_streak: i32,
+        dead: bool,
+        invincible: bool,
+        perpetrator: Option<Monster>,
+        ap: i32,
+    }
+
+    impl From<Player> for super::Player {
+        fn from(old: Player) -> Self {
+            // NOTE: the stun and panic were counted down in their own
+            // fields rather than in the `status`.
+            let mut effects = StatusEffects::default();
+            effects.add(status::Kind::Panic, old.panic.to_int());
+            effects.add(status::Kind::Stun, old.stun.to_int());
+            super::Player {
+                mind: old.mind,
+                will: old.will,
+                tolerance: old.tolerance,
+                status: effects,
+                pos: old.pos,
+                inventory: old.inventory.into_iter().map(Into::into).collect(),
+                anxiety_counter: old.anxiety_counter,
+                bonus: old.bonus,
+                bonuses: old.bonuses,
+                current_high_streak: old.current_high_streak,
+                longest_high_streak: old.longest_high_streak,
+                dead: old.dead,
+                invincible: old.invincible,
+                perpetrator: old.perpetrator.map(Into::into),
+                // NOTE: the whole action points weren't split into
+                // `formula::AP_FRACTIONS`.
+                ap: old.ap * formula::AP_FRACTIONS,
+            }
+        }
+    }
+}
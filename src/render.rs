@@ -1,14 +1,19 @@
 use crate::{
     color,
     engine::{Display, Settings, TextMetrics, TextOptions},
-    formula, graphics, monster,
+    formula, graphics,
+    keymap::{Action, Keymap},
+    monster,
     player::Bonus,
     point::{Point, SquareArea},
     rect::Rectangle,
-    state::State,
+    state::{Command, State},
     util,
     window::Window,
-    windows::{endgame, help, main_menu, settings, sidebar},
+    windows::{
+        controls, drop_item, endgame, help, load_game, look, main_menu, message_log, settings,
+        sidebar, throw,
+    },
     world::Chunk,
 };
 
@@ -33,13 +38,19 @@ pub fn render(
                 render_main_menu(state, &main_menu::Window, metrics, display);
             }
             Window::Game => {
-                render_game(state, &sidebar::Window, metrics, dt, fps, display);
+                render_game(state, settings, &sidebar::Window, metrics, dt, fps, display);
             }
             Window::Settings => {
                 render_settings(state, settings, &settings::Window, metrics, display);
             }
             Window::Help => {
-                render_help_screen(state, &help::Window, metrics, display);
+                render_help_screen(state, settings, &help::Window, metrics, display);
+            }
+            Window::LoadGame => {
+                render_load_game(state, &load_game::Window, metrics, display);
+            }
+            Window::Controls => {
+                render_controls(state, settings, &controls::Window, metrics, display);
             }
             Window::Endgame => {
                 render_endgame_screen(state, &endgame::Window, metrics, display);
@@ -47,6 +58,18 @@ pub fn render(
             Window::Message { ref message, .. } => {
                 render_message(state, message, metrics, display);
             }
+            Window::Look => {
+                render_look(state, &look::Window, metrics, display);
+            }
+            Window::MessageLog => {
+                render_message_log(state, &message_log::Window, metrics, display);
+            }
+            Window::Drop => {
+                render_drop_item(state, &drop_item::Window, metrics, display);
+            }
+            Window::Throw => {
+                render_throw(state, &throw::Window, metrics, display);
+            }
         }
     }
 
@@ -69,6 +92,7 @@ pub fn render(
 
 pub fn render_game(
     state: &State,
+    settings: &Settings,
     sidebar_window: &sidebar::Window,
     metrics: &dyn TextMetrics,
     dt: Duration,
@@ -193,7 +217,8 @@ pub fn render_game(
             || state.uncovered_map
         {
             for item in &cell.items {
-                display.set_glyph(display_pos, item.glyph(), item.color());
+                let definition = state.world.item_definition(&item.id);
+                display.set_glyph(display_pos, definition.glyph, definition.color);
             }
         }
     }
@@ -268,13 +293,16 @@ pub fn render_game(
             //     }
             // }
 
-            let glyph = monster.glyph();
+            let glyph = state.world.monster_definition(&monster.id).glyph;
             let color = if monster.kind == monster::Kind::Npc && state.player.mind.is_high() {
                 color::npc_dim
             } else {
                 monster.color
             };
             display.set_glyph(display_pos, glyph, color);
+            if let Some(effect) = monster.status.iter().next() {
+                display.set_background(display_pos, effect.kind.color());
+            }
         }
     }
 
@@ -282,11 +310,20 @@ pub fn render_game(
     {
         let display_pos = screen_coords_from_world(state.player.pos);
         display.set_glyph(display_pos, state.player.glyph(), state.player.color());
+        if state.player.alive() {
+            if let Some(effect) = state.player.status.iter().next() {
+                display.set_background(display_pos, effect.kind.color());
+            }
+        }
     }
 
-    sidebar_window.render(state, metrics, dt, fps, display);
+    sidebar_window.render(state, &settings.keymap, metrics, dt, fps, display);
     if state.show_keboard_movement_hints && !state.game_ended {
-        render_controls_help(state.map_size, metrics, display);
+        render_controls_help(state.map_size, &settings.keymap, metrics, display);
+    }
+
+    if state.replay {
+        render_replay_hud(state, display);
     }
 
     let mouse_inside_map = state.mouse.tile_pos >= (0, 0) && state.mouse.tile_pos < state.map_size;
@@ -295,6 +332,35 @@ pub fn render_game(
     }
 }
 
+/// Show the replay progress in the bottom-left corner of the map.
+fn render_replay_hud(state: &State, display: &mut Display) {
+    let command_count = state.replay_controller.command_count;
+    let commands_processed = command_count.saturating_sub(state.commands.len());
+    let mut lines = vec![
+        format!("Turn: {}", state.turn),
+        format!("Command: {}/{}", commands_processed, command_count),
+    ];
+    if state.paused {
+        lines.push("Paused".into());
+    }
+
+    let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as i32;
+    let height = lines.len() as i32;
+    let start = Point::new(0, state.map_size.y - height);
+    display.draw_rectangle(
+        Rectangle::from_point_and_size(start, Point::new(width, height)),
+        color::dim_background,
+    );
+    for (index, line) in lines.iter().enumerate() {
+        display.draw_text(
+            start + Point::new(0, index as i32),
+            line,
+            color::gui_text,
+            Default::default(),
+        );
+    }
+}
+
 fn render_main_menu(
     state: &State,
     window: &main_menu::Window,
@@ -322,9 +388,83 @@ fn render_settings(
 
 fn render_help_screen(
     state: &State,
+    settings: &Settings,
     window: &help::Window,
     metrics: &dyn TextMetrics,
     display: &mut Display,
+) {
+    window.render(state, &settings.keymap, metrics, display);
+
+    // Clear any fade set by the gameplay rendering
+    display.fade = color::invisible;
+}
+
+fn render_load_game(
+    state: &State,
+    window: &load_game::Window,
+    metrics: &dyn TextMetrics,
+    display: &mut Display,
+) {
+    window.render(state, metrics, display);
+
+    // Clear any fade set by the gameplay rendering
+    display.fade = color::invisible;
+}
+
+fn render_controls(
+    state: &State,
+    settings: &Settings,
+    window: &controls::Window,
+    metrics: &dyn TextMetrics,
+    display: &mut Display,
+) {
+    window.render(state, settings, metrics, display);
+
+    // Clear any fade set by the gameplay rendering
+    display.fade = color::invisible;
+}
+
+fn render_look(
+    state: &State,
+    window: &look::Window,
+    metrics: &dyn TextMetrics,
+    display: &mut Display,
+) {
+    // NOTE: clear the fade first so the description is readable
+    display.fade = color::invisible;
+
+    window.render(state, metrics, display);
+}
+
+fn render_drop_item(
+    state: &State,
+    window: &drop_item::Window,
+    metrics: &dyn TextMetrics,
+    display: &mut Display,
+) {
+    // NOTE: clear the fade first so the prompt is readable
+    display.fade = color::invisible;
+
+    window.render(state, metrics, display);
+}
+
+fn render_throw(
+    state: &State,
+    window: &throw::Window,
+    metrics: &dyn TextMetrics,
+    display: &mut Display,
+) {
+    // NOTE: clear the fade first so the prompt is readable
+    display.fade = color::invisible;
+
+    window.render(state, metrics, display);
+}
+
+fn render_message_log(
+    state: &State,
+    window: &message_log::Window,
+    metrics: &dyn TextMetrics,
+    display: &mut Display,
 ) {
     window.render(state, metrics, display);
 
@@ -426,22 +566,27 @@ fn render_monster_info(state: &State, display: &mut Display) {
     }
 }
 
-fn render_controls_help(map_size: Point, metrics: &dyn TextMetrics, display: &mut Display) {
-    let rect_dim = |lines: &[&str]| {
+fn render_controls_help(
+    map_size: Point,
+    keymap: &Keymap,
+    metrics: &dyn TextMetrics,
+    display: &mut Display,
+) {
+    let rect_dim = |lines: &[String]| {
         let longest_line = lines
             .iter()
             .map(|l| metrics.get_text_width(l, Default::default()))
             .max()
-            .unwrap();
+            .unwrap_or(0);
         (longest_line, lines.len() as i32)
     };
 
-    fn draw_rect(lines: &[&'static str], start: Point, w: i32, h: i32, display: &mut Display) {
+    fn draw_rect(lines: &[String], start: Point, w: i32, h: i32, display: &mut Display) {
         display.draw_rectangle(
             Rectangle::from_point_and_size(start, Point::new(w, h)),
             color::dim_background,
         );
-        for (index, &line) in lines.iter().enumerate() {
+        for (index, line) in lines.iter().enumerate() {
             display.draw_text(
                 start + Point::new(0, index as i32),
                 line,
@@ -449,71 +594,51 @@ fn render_controls_help(map_size: Point, metrics: &dyn TextMetrics, display: &mu
                 Default::default(),
             );
         }
-    };
+    }
 
     let padding = 1;
 
-    let lines = &["Up", "Num 8", "or: K"];
-    let (width, height) = rect_dim(lines);
-    let start = Point {
-        x: (map_size.x - width) / 2,
-        y: padding,
-    };
-    draw_rect(lines, start, width, height, display);
-
-    let lines = &["Down", "Num 2", "or: J"];
-    let (width, height) = rect_dim(lines);
-    let start = Point {
-        x: (map_size.x - width) / 2,
-        y: map_size.y - height - padding,
-    };
-    draw_rect(lines, start, width, height, display);
-
-    let lines = &["Left", "Num 4", "or: H"];
-    let (width, height) = rect_dim(lines);
-    let start = Point {
-        x: padding,
-        y: (map_size.y - height) / 2,
-    };
-    draw_rect(lines, start, width, height, display);
-
-    let lines = &["Right", "Num 6", "or: L"];
-    let (width, height) = rect_dim(lines);
-    let start = Point {
-        x: map_size.x - width - padding,
-        y: (map_size.y - height) / 2,
-    };
-    draw_rect(lines, start, width, height, display);
-
-    let lines = &["Shift+Left", "Num 7", "or: Y"];
-    let (width, height) = rect_dim(lines);
-    let start = Point {
-        x: padding,
-        y: padding,
-    };
-    draw_rect(lines, start, width, height, display);
-
-    let lines = &["Shift+Right", "Num 9", "or: U"];
-    let (width, height) = rect_dim(lines);
-    let start = Point {
-        x: map_size.x - width - padding,
-        y: padding,
-    };
-    draw_rect(lines, start, width, height, display);
-
-    let lines = &["Ctrl+Left", "Num 1", "or: B"];
-    let (width, height) = rect_dim(lines);
-    let start = Point {
-        x: padding,
-        y: map_size.y - height - padding,
-    };
-    draw_rect(lines, start, width, height, display);
-
-    let lines = &["Ctrl+Right", "Num 3", "or: N"];
-    let (width, height) = rect_dim(lines);
-    let start = Point {
-        x: map_size.x - width - padding,
-        y: map_size.y - height - padding,
-    };
-    draw_rect(lines, start, width, height, display);
+    // NOTE: where to show the keys of each direction. `-1` means the
+    // left or top edge of the map, `0` the middle and `1` the right
+    // or bottom edge.
+    let hints = [
+        (Action::Command(Command::N), 0, -1),
+        (Action::Command(Command::S), 0, 1),
+        (Action::Command(Command::W), -1, 0),
+        (Action::Command(Command::E), 1, 0),
+        (Action::Command(Command::NW), -1, -1),
+        (Action::Command(Command::NE), 1, -1),
+        (Action::Command(Command::SW), -1, 1),
+        (Action::Command(Command::SE), 1, 1),
+    ];
+
+    for (action, horizontal, vertical) in hints.iter() {
+        let lines = keymap
+            .keys(action)
+            .iter()
+            .enumerate()
+            .map(|(index, key)| {
+                if index == 0 {
+                    key.to_string()
+                } else {
+                    format!("or: {}", key)
+                }
+            })
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            continue;
+        }
+        let (width, height) = rect_dim(&lines);
+        let x = match horizontal {
+            -1 => padding,
+            0 => (map_size.x - width) / 2,
+            _ => map_size.x - width - padding,
+        };
+        let y = match vertical {
+            -1 => padding,
+            0 => (map_size.y - height) / 2,
+            _ => map_size.y - height - padding,
+        };
+        draw_rect(&lines, Point { x, y }, width, height, display);
+    }
 }
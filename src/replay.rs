@@ -0,0 +1,700 @@
+//! The replay log format.
+//!
+//! A replay file starts with a single line containing the JSON
+//! `Header`. Every following line is a JSON `Record`: a player
+//! command, a verification of the game state, a message shown to the
+//! player or an annotation.
+//!
+//! The original format (format version 1) had three bare lines with
+//! the seed, game version and git hash followed by untagged commands
+//! and verifications. We can still read it and we migrate it to the
+//! current format while loading.
+
+use crate::{
+    generators::Generator,
+    item,
+    message::Message,
+    monster,
+    point::Point,
+    state::{Command, State, Verification},
+};
+
+use std::{collections::HashMap, error::Error, fmt::Debug, fs::File, io::Write, mem};
+
+use serde::{Deserialize, Serialize};
+
+/// Increment this whenever the replay format changes in a way the
+/// previous versions can't read.
+pub const FORMAT_VERSION: u32 = 4;
+
+/// How often (in turns) we store a snapshot of the game state while
+/// replaying. The snapshots let us rewind the replay.
+pub const SNAPSHOT_INTERVAL: i32 = 100;
+
+/// Where we write the details of the first replay desync.
+pub const DESYNC_REPORT_PATH: &str = "replay-desync-report.txt";
+
+/// Cargo features that change the gameplay. A replay recorded with a
+/// different set of these will not play back the same way.
+#[cfg(feature = "replay")]
+const GAMEPLAY_FEATURES: &[&str] = &["recording"];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+    pub format_version: u32,
+    pub seed: u32,
+    pub game_version: String,
+    pub git_hash: String,
+    /// Local time the game started in the RFC 3339 format. Not
+    /// available in replays converted from the old format.
+    pub start_time: Option<String>,
+    /// The Cargo features the game was compiled with.
+    pub features: Vec<String>,
+    pub invincible: bool,
+    pub world_size: Point,
+    pub world_generator: Generator,
+    /// The monsters the game was played with.
+    pub monsters: Vec<monster::Definition>,
+    /// The items the game was played with.
+    pub items: Vec<item::Definition>,
+}
+
+impl Header {
+    pub fn new(
+        seed: u32,
+        invincible: bool,
+        world_size: Point,
+        world_generator: Generator,
+        monsters: Vec<monster::Definition>,
+        items: Vec<item::Definition>,
+    ) -> Self {
+        Header {
+            format_version: FORMAT_VERSION,
+            seed,
+            game_version: crate::metadata::VERSION.into(),
+            git_hash: crate::metadata::GIT_HASH.into(),
+            start_time: start_time(),
+            features: enabled_features(),
+            invincible,
+            world_size,
+            world_generator,
+            monsters,
+            items,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Record {
+    Command(Command),
+    Verification(Verification),
+    /// A free-form note. It has no effect on the game.
+    Annotation(String),
+    /// A message from the player's message log. The replayed game
+    /// produces its own messages so this is only informative.
+    Message(Message),
+}
+
+/// The full contents of a loaded replay file.
+#[cfg(feature = "replay")]
+#[derive(Debug)]
+pub struct Replay {
+    pub header: Header,
+    pub records: Vec<Record>,
+}
+
+/// A serialised copy of the game state at the start of a turn.
+#[derive(Debug)]
+struct Snapshot {
+    turn: i32,
+    state: Vec<u8>,
+    verifications_remaining: usize,
+}
+
+/// Keeps track of the replay as a whole so we can show the progress
+/// and move back and forth in it.
+#[derive(Debug, Default)]
+pub struct Controller {
+    /// Total number of commands in the replay.
+    pub command_count: usize,
+    /// All the verifications in the replay. The game state only holds
+    /// the ones that haven't been checked yet.
+    verifications: Vec<Verification>,
+    snapshots: Vec<Snapshot>,
+    /// The turn where the replayed game first diverged from the
+    /// recording.
+    pub desync_turn: Option<i32>,
+    /// All the differences found in the `desync_turn`.
+    pub divergences: Vec<Divergence>,
+}
+
+impl Controller {
+    #[cfg(feature = "replay")]
+    pub fn new(command_count: usize, verifications: Vec<Verification>) -> Self {
+        Controller {
+            command_count,
+            verifications,
+            snapshots: vec![],
+            desync_turn: None,
+            divergences: vec![],
+        }
+    }
+}
+
+/// Store the current state of the replayed game so we can return to
+/// it later. Does nothing if we already have a snapshot of this turn.
+pub fn save_snapshot(state: &mut State) {
+    let turn = state.turn;
+    if state
+        .replay_controller
+        .snapshots
+        .iter()
+        .any(|s| s.turn == turn)
+    {
+        return;
+    }
+    match bincode::serialize(&*state) {
+        Ok(data) => {
+            log::debug!("Saving a replay snapshot of turn {}.", turn);
+            let snapshots = &mut state.replay_controller.snapshots;
+            snapshots.push(Snapshot {
+                turn,
+                state: data,
+                verifications_remaining: state.verifications.len(),
+            });
+            snapshots.sort_by_key(|s| s.turn);
+        }
+        Err(err) => log::error!("Could not save the replay snapshot: {}", err),
+    }
+}
+
+/// Replace the `state` with the latest snapshot taken at or before
+/// `turn`. Returns `false` if there's no such snapshot.
+pub fn restore_snapshot(state: &mut State, turn: i32) -> bool {
+    let index = match state
+        .replay_controller
+        .snapshots
+        .iter()
+        .rposition(|s| s.turn <= turn)
+    {
+        Some(index) => index,
+        None => return false,
+    };
+
+    let mut restored: State =
+        match bincode::deserialize(&state.replay_controller.snapshots[index].state) {
+            Ok(restored) => restored,
+            Err(err) => {
+                log::error!("Could not restore the replay snapshot: {}", err);
+                return false;
+            }
+        };
+    log::debug!("Restored the replay snapshot of turn {}.", restored.turn);
+
+    let controller = mem::replace(&mut state.replay_controller, Default::default());
+    let skip = controller.verifications.len() - controller.snapshots[index].verifications_remaining;
+    restored.verifications = controller.verifications[skip..].iter().cloned().collect();
+    restored.replay_controller = controller;
+
+    // NOTE: keep the settings of the current replay session
+    restored.stats = mem::replace(&mut state.stats, Default::default());
+    restored.cheating = state.cheating;
+    restored.paused = state.paused;
+    restored.replay_full_speed = state.replay_full_speed;
+    restored.exit_after = state.exit_after;
+    restored.window_stack = mem::replace(
+        &mut state.window_stack,
+        crate::windows::Windows::new(crate::window::Window::Game),
+    );
+
+    *state = restored;
+    true
+}
+
+/// A single difference between the recorded and the replayed game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+    pub field: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl Divergence {
+    pub fn new<T: Debug>(field: &str, expected: T, actual: T) -> Self {
+        Divergence {
+            field: field.into(),
+            expected: format!("{:?}", expected),
+            actual: format!("{:?}", actual),
+        }
+    }
+}
+
+/// List all the differences between the expected (recorded) and the
+/// actual (replayed) verification. The more fundamental fields
+/// (player, rng) come first so the first entry is usually the
+/// culprit.
+pub fn compare(expected: &Verification, actual: &Verification) -> Vec<Divergence> {
+    let mut result = vec![];
+
+    fn check<T: Debug + PartialEq>(
+        result: &mut Vec<Divergence>,
+        field: &str,
+        expected: T,
+        actual: T,
+    ) {
+        if expected != actual {
+            result.push(Divergence::new(field, expected, actual));
+        }
+    }
+
+    // NOTE: skip the fields the recorded verification doesn't have
+    fn check_optional<T: Debug + PartialEq>(
+        result: &mut Vec<Divergence>,
+        field: &str,
+        expected: &Option<T>,
+        actual: &Option<T>,
+    ) {
+        if let (Some(expected), Some(actual)) = (expected, actual) {
+            check(result, field, expected, actual);
+        }
+    }
+
+    check(&mut result, "turn", expected.turn, actual.turn);
+    check(
+        &mut result,
+        "player_pos",
+        expected.player_pos,
+        actual.player_pos,
+    );
+    check_optional(
+        &mut result,
+        "player_mind",
+        &expected.player_mind,
+        &actual.player_mind,
+    );
+    check_optional(
+        &mut result,
+        "player_will",
+        &expected.player_will,
+        &actual.player_will,
+    );
+    check_optional(
+        &mut result,
+        "player_tolerance",
+        &expected.player_tolerance,
+        &actual.player_tolerance,
+    );
+    check_optional(
+        &mut result,
+        "player_ap",
+        &expected.player_ap,
+        &actual.player_ap,
+    );
+    check_optional(
+        &mut result,
+        "player_inventory",
+        &expected.player_inventory,
+        &actual.player_inventory,
+    );
+    check_optional(
+        &mut result,
+        "rng_hash",
+        &expected.rng_hash,
+        &actual.rng_hash,
+    );
+    check(
+        &mut result,
+        "chunk_count",
+        expected.chunk_count,
+        actual.chunk_count,
+    );
+
+    if expected.monsters != actual.monsters {
+        let expected_monsters: HashMap<_, _> = expected
+            .monsters
+            .iter()
+            .map(|(pos, chunk_pos, id)| (*pos, (*chunk_pos, id)))
+            .collect();
+        let actual_monsters: HashMap<_, _> = actual
+            .monsters
+            .iter()
+            .map(|(pos, chunk_pos, id)| (*pos, (*chunk_pos, id)))
+            .collect();
+
+        check(
+            &mut result,
+            "monster count",
+            expected.monsters.len(),
+            actual.monsters.len(),
+        );
+        for (pos, chunk_pos, id) in &expected.monsters {
+            let field = format!("monster at {}", pos);
+            check(
+                &mut result,
+                &field,
+                Some(&(*chunk_pos, id)),
+                actual_monsters.get(pos),
+            );
+        }
+        for (pos, chunk_pos, id) in &actual.monsters {
+            if !expected_monsters.contains_key(pos) {
+                let field = format!("monster at {}", pos);
+                check(&mut result, &field, None, Some(&(*chunk_pos, id)));
+            }
+        }
+    }
+
+    if let (Some(expected), Some(actual)) = (&expected.chunk_digests, &actual.chunk_digests) {
+        let actual: HashMap<_, _> = actual.iter().cloned().collect();
+        for &(pos, digest) in expected {
+            let field = format!("chunk {} digest", pos);
+            check(&mut result, &field, Some(&digest), actual.get(&pos));
+        }
+    }
+
+    result
+}
+
+/// Write a human-readable description of the replay desync.
+pub fn write_desync_report<W: Write>(
+    writer: &mut W,
+    turn: i32,
+    divergences: &[Divergence],
+) -> Result<(), Box<dyn Error>> {
+    writeln!(writer, "Replay desync at turn {}.", turn)?;
+    if let Some(first) = divergences.first() {
+        writeln!(writer)?;
+        writeln!(writer, "First diverging field: {}", first.field)?;
+        writeln!(writer, "  expected: {}", first.expected)?;
+        writeln!(writer, "  actual:   {}", first.actual)?;
+    }
+    writeln!(writer)?;
+    writeln!(writer, "All differences in this turn:")?;
+    for divergence in divergences {
+        writeln!(
+            writer,
+            "  {}: expected {}, actual {}",
+            divergence.field, divergence.expected, divergence.actual
+        )?;
+    }
+    Ok(())
+}
+
+/// Record that the replayed game diverged from the recording.
+///
+/// Only the first desync is reported in detail (and written to
+/// `DESYNC_REPORT_PATH`), everything after it is most likely its
+/// consequence.
+pub fn report_desync(state: &mut State, divergences: &[Divergence]) {
+    if state.replay_controller.desync_turn.is_some() {
+        log::debug!(
+            "The replay is still out of sync at turn {}: {} differences.",
+            state.turn,
+            divergences.len()
+        );
+        return;
+    }
+
+    state.replay_controller.desync_turn = Some(state.turn);
+    state.replay_controller.divergences = divergences.to_vec();
+    log::error!("The replay got out of sync at turn {}!", state.turn);
+    for divergence in divergences {
+        log::error!(
+            "{}: expected {}, actual {}",
+            divergence.field,
+            divergence.expected,
+            divergence.actual
+        );
+    }
+
+    let result = File::create(DESYNC_REPORT_PATH)
+        .map_err(Into::into)
+        .and_then(|mut file| write_desync_report(&mut file, state.turn, divergences));
+    match result {
+        Ok(()) => log::info!("Desync report written to '{}'.", DESYNC_REPORT_PATH),
+        Err(err) => log::error!("Could not write the desync report: {}", err),
+    }
+}
+
+#[cfg(feature = "replay")]
+fn start_time() -> Option<String> {
+    Some(chrono::Local::now().to_rfc3339())
+}
+
+#[cfg(not(feature = "replay"))]
+fn start_time() -> Option<String> {
+    None
+}
+
+pub fn enabled_features() -> Vec<String> {
+    let mut features = vec![];
+    if cfg!(feature = "recording") {
+        features.push("recording".into());
+    }
+    if cfg!(feature = "cheating") {
+        features.push("cheating".into());
+    }
+    if cfg!(feature = "verifications") {
+        features.push("verifications".into());
+    }
+    features
+}
+
+pub fn log_header<W: Write>(writer: &mut W, header: &Header) {
+    let json =
+        serde_json::to_string(header).expect(&format!("Could not serialise {:?} to json.", header));
+    writeln!(writer, "{}", json).unwrap();
+}
+
+fn log_record<W: Write>(writer: &mut W, record: &Record) {
+    let json =
+        serde_json::to_string(record).expect(&format!("Could not serialise {:?} to json.", record));
+    writeln!(writer, "{}", json).expect(&format!(
+        "Could not write the record: '{}' to the replay log.",
+        json
+    ));
+}
+
+pub fn log_command<W: Write>(writer: &mut W, command: Command) {
+    log_record(writer, &Record::Command(command));
+}
+
+pub fn log_verification<W: Write>(writer: &mut W, verification: &Verification) {
+    log_record(writer, &Record::Verification(verification.clone()));
+}
+
+pub fn log_annotation<W: Write>(writer: &mut W, annotation: &str) {
+    log_record(writer, &Record::Annotation(annotation.into()));
+}
+
+pub fn log_message<W: Write>(writer: &mut W, message: Message) {
+    log_record(writer, &Record::Message(message));
+}
+
+/// Read the replay from its textual representation.
+///
+/// Replays in the original format are migrated. Replays with a
+/// different format version or recorded with different gameplay
+/// features are rejected.
+#[cfg(feature = "replay")]
+pub fn parse(contents: &str, world_size: Point) -> Result<Replay, Box<dyn Error>> {
+    let mut lines = contents.lines();
+    let first_line = match lines.next() {
+        Some(line) => line,
+        None => error!("The replay file is empty."),
+    };
+
+    let replay = if let Ok(seed) = first_line.trim().parse::<u32>() {
+        parse_version_1(seed, lines, world_size)?
+    } else {
+        let header: Header = serde_json::from_str(first_line)?;
+        if header.format_version != FORMAT_VERSION {
+            error!(&format!(
+                "The replay format version is {}, but this program only supports \
+                 version {}.",
+                header.format_version, FORMAT_VERSION
+            ));
+        }
+        let mut records = vec![];
+        for line in lines {
+            if !line.trim().is_empty() {
+                records.push(serde_json::from_str(line)?);
+            }
+        }
+        Replay { header, records }
+    };
+
+    for &feature in GAMEPLAY_FEATURES {
+        let in_replay = replay.header.features.iter().any(|f| f == feature);
+        let in_program = enabled_features().iter().any(|f| f == feature);
+        if in_replay != in_program {
+            error!(&format!(
+                "The replay was recorded {} the `{}` feature, but this program was \
+                 built {} it.",
+                if in_replay { "with" } else { "without" },
+                feature,
+                if in_program { "with" } else { "without" },
+            ));
+        }
+    }
+
+    if replay.header.game_version != crate::metadata::VERSION {
+        log::warn!(
+            "The replay file's version is: {}, but the program is: {}",
+            replay.header.game_version,
+            crate::metadata::VERSION
+        );
+    }
+
+    if replay.header.git_hash != crate::metadata::GIT_HASH {
+        log::warn!(
+            "The replay file's commit is: {}, but the program is: {}.",
+            replay.header.git_hash,
+            crate::metadata::GIT_HASH
+        );
+    }
+
+    Ok(replay)
+}
+
+/// Migrate the original replay format: seed, version and commit on
+/// the first three lines followed by untagged commands and
+/// verifications.
+#[cfg(feature = "replay")]
+fn parse_version_1<'a, I>(
+    seed: u32,
+    mut lines: I,
+    world_size: Point,
+) -> Result<Replay, Box<dyn Error>>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let game_version = match lines.next() {
+        Some(version) => version.into(),
+        None => error!("The replay file is missing the version."),
+    };
+
+    let git_hash = match lines.next() {
+        Some(commit) => commit.into(),
+        None => error!("The replay file is missing the commit hash."),
+    };
+
+    let mut records = vec![];
+    for line in lines {
+        // Try parsing it as a command, otherwise it's a verification
+        let mut value: serde_json::Value = serde_json::from_str(line)?;
+        migrate_use_command(&mut value);
+        if let Ok(command) = serde_json::from_value(value.clone()) {
+            records.push(Record::Command(command));
+        } else {
+            records.push(Record::Verification(serde_json::from_value(value)?));
+        }
+    }
+
+    log::info!("Migrating the replay from format version 1.");
+    let header = Header {
+        format_version: 1,
+        seed,
+        game_version,
+        git_hash,
+        start_time: None,
+        // NOTE: the old format didn't record the features. Assume
+        // it's compatible and let the verifications catch any issues.
+        features: enabled_features(),
+        invincible: false,
+        world_size,
+        world_generator: Generator::Forrest,
+        monsters: monster::built_in_definitions(),
+        items: item::built_in_definitions(),
+    };
+
+    Ok(Replay { header, records })
+}
+
+/// Convert the commands from before the items were defined in the
+/// data file, e.g. `"UseFood"` to `{"Use": "Food"}`. The built-in
+/// items use the old names as their ids.
+#[cfg(feature = "replay")]
+fn migrate_use_command(command: &mut serde_json::Value) {
+    let id = match command.as_str() {
+        Some(name) if name.starts_with("Use") => name["Use".len()..].to_string(),
+        _ => return,
+    };
+    *command = serde_json::json!({ "Use": id });
+}
+
+#[cfg(test)]
+mod test {
+    use super::{compare, parse, Header, Record, FORMAT_VERSION};
+    use crate::{
+        generators::Generator,
+        item, monster,
+        point::Point,
+        state::{Command, Verification},
+    };
+
+    const WORLD_SIZE: Point = Point { x: 100, y: 100 };
+
+    #[test]
+    fn test_current_format() {
+        let header = Header::new(
+            42,
+            true,
+            WORLD_SIZE,
+            Generator::CURRENT,
+            monster::built_in_definitions(),
+            item::built_in_definitions(),
+        );
+        let contents = format!(
+            "{}\n{}\n{}\n",
+            serde_json::to_string(&header).unwrap(),
+            r#"{"Command":"N"}"#,
+            r#"{"Annotation":"hello"}"#
+        );
+        let replay = parse(&contents, Point::new(1, 1)).unwrap();
+        assert_eq!(replay.header, header);
+        assert_eq!(replay.records.len(), 2);
+        match replay.records[0] {
+            Record::Command(Command::N) => {}
+            ref record => panic!("Unexpected record: {:?}", record),
+        }
+    }
+
+    #[test]
+    fn test_migrate_version_1() {
+        let contents = "42\n1.0.0\nabcdef\n\"N\"\n\"UseFood\"\n\
+                        {\"turn\":0,\"chunk_count\":4,\"player_pos\":{\"x\":0,\"y\":0},\"monsters\":[]}\n";
+        let replay = parse(contents, WORLD_SIZE).unwrap();
+        assert_eq!(replay.header.format_version, 1);
+        assert_eq!(replay.header.seed, 42);
+        assert_eq!(replay.header.world_size, WORLD_SIZE);
+        assert_eq!(replay.header.world_generator, Generator::Forrest);
+        assert_eq!(replay.records.len(), 3);
+        match (&replay.records[0], &replay.records[2]) {
+            (Record::Command(Command::N), Record::Verification(_)) => {}
+            records => panic!("Unexpected records: {:?}", records),
+        }
+        match replay.records[1] {
+            Record::Command(Command::Use(ref id)) => assert_eq!(id, "Food"),
+            ref record => panic!("Unexpected record: {:?}", record),
+        }
+    }
+
+    #[test]
+    fn test_reject_other_formats() {
+        let mut header = Header::new(
+            42,
+            false,
+            WORLD_SIZE,
+            Generator::CURRENT,
+            monster::built_in_definitions(),
+            item::built_in_definitions(),
+        );
+        for &version in &[FORMAT_VERSION - 1, FORMAT_VERSION + 1] {
+            header.format_version = version;
+            let contents = serde_json::to_string(&header).unwrap();
+            assert!(parse(&contents, WORLD_SIZE).is_err());
+        }
+    }
+
+    #[test]
+    fn test_compare_finds_first_divergence() {
+        let contents =
+            "{\"turn\":7,\"chunk_count\":4,\"player_pos\":{\"x\":3,\"y\":3},\"monsters\":[]}";
+        let expected: Verification = serde_json::from_str(contents).unwrap();
+        let mut actual: Verification = serde_json::from_str(contents).unwrap();
+        assert!(compare(&expected, &actual).is_empty());
+
+        // NOTE: the old verifications don't have the player's will so
+        // we shouldn't complain about it.
+        actual.player_will = Some(2);
+        assert!(compare(&expected, &actual).is_empty());
+
+        actual.player_pos = Point::new(3, 4);
+        actual.chunk_count = 5;
+        let divergences = compare(&expected, &actual);
+        assert_eq!(divergences.len(), 2);
+        assert_eq!(divergences[0].field, "player_pos");
+        assert_eq!(divergences[1].field, "chunk_count");
+    }
+}
@@ -0,0 +1,670 @@
+//! Saved games.
+//!
+//! Every saved game lives in its own slot: a file in the per-user
+//! data directory (e.g. `~/.local/share/dose-response/saves` on
+//! Linux). The file starts with a magic number and a `Header` holding
+//! the save format version, the slot `Metadata` and a checksum. The
+//! rest of the file is the compressed, serialised `State`. This lets
+//! us list the saved games without having to load them.
+//!
+//! The 1.0.0 release (save format version 1) wrote the game version,
+//! git hash and the uncompressed `State` into `SAVEDGAME.sav` in the
+//! current directory. We import that file into a slot the first time
+//! we look for the saved games.
+
+use crate::{companion, item, monster, player::Mind, point::Point, state::State};
+
+use std::{
+    cmp,
+    error::Error,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression, Crc};
+use serde::{Deserialize, Serialize};
+
+/// Increment this when a release changes the serialised `State`
+/// layout and convert the previous one in `deserialize_state`.
+///
+/// The 1.0.0 release had no header; its saves count as version 1.
+/// See `read_legacy_save`.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Every save file (other than the ones from the 1.0.0 release)
+/// starts with these bytes.
+const MAGIC: &[u8; 8] = b"DOSESAVE";
+
+const EXTENSION: &str = "sav";
+
+/// The replay log recorded up to the save is stored next to it with
+/// this extension. When the game is loaded, the recording continues
+/// from there.
+const REPLAY_EXTENSION: &str = "replay";
+
+/// The save file written by the 1.0.0 release.
+const LEGACY_SAVE_PATH: &str = "SAVEDGAME.sav";
+
+/// How often (in turns) we save the game automatically.
+pub const AUTOSAVE_INTERVAL: i32 = 50;
+
+/// We rotate the autosaves between this many slots so a save written
+/// during a crash doesn't take the only good one down with it.
+const AUTOSAVE_SLOTS: i32 = 3;
+
+const AUTOSAVE_PREFIX: &str = "autosave-";
+
+/// Information about a saved game shown in the load game window.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub seed: u32,
+    pub turn: i32,
+    pub mind: Mind,
+    pub will: i32,
+    /// The real time spent playing the game.
+    pub time_played: Duration,
+    /// When was the game saved (in seconds since the Unix epoch).
+    pub saved_at: u64,
+}
+
+impl Metadata {
+    pub fn new(state: &State) -> Self {
+        Metadata {
+            seed: state.seed,
+            turn: state.turn,
+            mind: state.player.mind,
+            will: state.player.will.to_int(),
+            time_played: state.clock,
+            saved_at: now(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Slot {
+    pub name: String,
+    pub metadata: Metadata,
+}
+
+/// The directory holding all the save slots.
+pub fn directory() -> PathBuf {
+    match dirs::data_dir() {
+        Some(dir) => dir.join("dose-response").join("saves"),
+        None => {
+            log::warn!("Could not find the user data directory. Saving into the current one.");
+            PathBuf::from("saves")
+        }
+    }
+}
+
+fn slot_path(name: &str) -> PathBuf {
+    directory().join(format!("{}.{}", name, EXTENSION))
+}
+
+fn slot_replay_path(name: &str) -> PathBuf {
+    directory().join(format!("{}.{}", name, REPLAY_EXTENSION))
+}
+
+pub fn is_autosave(name: &str) -> bool {
+    name.starts_with(AUTOSAVE_PREFIX)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Pick a name that's not used by any existing slot.
+pub fn new_slot_name() -> String {
+    (1..)
+        .map(|index| format!("save-{}", index))
+        .find(|name| !slot_path(name).exists())
+        .unwrap()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    format_version: u32,
+    game_version: String,
+    git_hash: String,
+    metadata: Metadata,
+    /// CRC32 of the compressed state following the header.
+    checksum: u32,
+}
+
+/// All the saved games, the most recent first.
+pub fn list_slots() -> Vec<Slot> {
+    import_legacy_save();
+
+    let entries = match fs::read_dir(directory()) {
+        Ok(entries) => entries,
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => return vec![],
+        Err(error) => {
+            log::error!("Could not read the saved games directory: {}", error);
+            return vec![];
+        }
+    };
+
+    let mut slots = vec![];
+    for entry in entries.filter_map(Result::ok) {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some(EXTENSION) {
+            continue;
+        }
+        let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        match File::open(&path)
+            .map_err(Into::into)
+            .and_then(|file| read_header(&mut BufReader::new(file)))
+        {
+            Ok(header) => slots.push(Slot {
+                name,
+                metadata: header.metadata,
+            }),
+            Err(error) => log::warn!("Skipping the saved game {}: {}", path.display(), error),
+        }
+    }
+
+    slots.sort_by_key(|slot| cmp::Reverse(slot.metadata.saved_at));
+    slots
+}
+
+/// Read the magic number and the header of a saved game.
+fn read_header<R: Read>(reader: &mut R) -> Result<Header, Box<dyn Error>> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        error!("This is not a Dose Response saved game.");
+    }
+
+    let header: Header = bincode::deserialize_from(&mut *reader)?;
+    if header.format_version > FORMAT_VERSION {
+        error!(&format!(
+            "The game was saved by a newer release ({}, save format {}). This release only understands save format {} and older.",
+            header.game_version, header.format_version, FORMAT_VERSION
+        ));
+    }
+    if header.game_version != crate::metadata::VERSION {
+        log::info!(
+            "The game was saved in a different version: {}. This release has version: {}.",
+            header.game_version,
+            crate::metadata::VERSION
+        );
+    }
+    if header.git_hash != crate::metadata::GIT_HASH {
+        log::info!(
+            "The game was saved in a different commit: {}. This release has commit: {}.",
+            header.git_hash,
+            crate::metadata::GIT_HASH
+        );
+    }
+    Ok(header)
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+fn write_save<W: Write>(writer: &mut W, state: &State) -> Result<(), Box<dyn Error>> {
+    let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+    bincode::serialize_into(&mut encoder, state)?;
+    let compressed = encoder.finish()?;
+
+    let header = Header {
+        format_version: FORMAT_VERSION,
+        game_version: crate::metadata::VERSION.into(),
+        git_hash: crate::metadata::GIT_HASH.into(),
+        metadata: Metadata::new(state),
+        checksum: checksum(&compressed),
+    };
+
+    writer.write_all(MAGIC)?;
+    bincode::serialize_into(&mut *writer, &header)?;
+    writer.write_all(&compressed)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn read_save<R: Read>(reader: &mut R) -> Result<State, Box<dyn Error>> {
+    let header = read_header(reader)?;
+    log::info!(
+        "Loading a game saved at turn {} (save format {}).",
+        header.metadata.turn,
+        header.format_version
+    );
+
+    let mut compressed = vec![];
+    reader.read_to_end(&mut compressed)?;
+    if checksum(&compressed) != header.checksum {
+        error!("The saved game is corrupted (checksum mismatch).");
+    }
+
+    let mut data = vec![];
+    DeflateDecoder::new(&compressed[..]).read_to_end(&mut data)?;
+    deserialize_state(header.format_version, &data)
+}
+
+/// Read the save file of the 1.0.0 release.
+fn read_legacy_save<R: Read>(reader: &mut R) -> Result<State, Box<dyn Error>> {
+    let version: String = bincode::deserialize_from(&mut *reader)?;
+    let commit: String = bincode::deserialize_from(&mut *reader)?;
+    log::info!("Legacy savefile version {}, commit {}", version, commit);
+
+    // NOTE: the 1.0.0 release didn't store the definitions or the
+    // companions. Rebuild them from the world and the defaults.
+    let state: legacy::State = bincode::deserialize_from(&mut *reader)?;
+    let mut state = State::from(state);
+    state.world.monster_definitions = monster::built_in_definitions();
+    state.world.item_definitions = item::built_in_definitions();
+    state.companions = companion::from_world(&state.world);
+    Ok(state)
+}
+
+/// The `State` as the 1.0.0 release saved it.
+///
+/// NOTE: bincode doesn't store the field names so this has to list
+/// all the fields the 1.0.0 release saved in the same order.
+mod legacy {
+    use crate::{
+        animation::ScreenFade,
+        engine::Mouse,
+        keys::Keys,
+        player::legacy::Player,
+        point::Point,
+        random::Random,
+        state::Side,
+        timer::Timer,
+        window::Window,
+        windows,
+        world::{legacy::World, MonsterId},
+    };
+
+    use std::{collections::VecDeque, time::Duration};
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct State {
+        pub player: Player,
+        pub world_size: Point,
+        pub chunk_size: i32,
+        pub world: World,
+        pub map_size: Point,
+        pub panel_width: i32,
+        pub display_size: Point,
+        pub screen_position_in_world: Point,
+        pub seed: u32,
+        pub rng: Random,
+        pub keys: Keys,
+        pub mouse: Mouse,
+        pub commands: VecDeque<Command>,
+        pub side: Side,
+        pub turn: i32,
+        pub cheating: bool,
+        pub replay: bool,
+        pub replay_full_speed: bool,
+        pub exit_after: bool,
+        pub clock: Duration,
+        pub replay_step: Duration,
+        pub pos_timer: Timer,
+        pub paused: bool,
+        pub old_screen_pos: Point,
+        pub new_screen_pos: Point,
+        pub screen_fading: Option<ScreenFade>,
+        pub offset_px: Point,
+        pub game_ended: bool,
+        pub victory_npc_id: Option<MonsterId>,
+        pub window_stack: windows::Windows<Window>,
+        pub first_game_already_generated: bool,
+        pub show_keboard_movement_hints: bool,
+        pub show_anxiety_counter: bool,
+        pub current_help_window: windows::help::Page,
+        pub show_endscreen_and_uncover_map_during_fadein: bool,
+        pub uncovered_map: bool,
+    }
+
+    /// NOTE: the items used to have a command each. Their ids are
+    /// the old names.
+    #[derive(Deserialize)]
+    pub enum Command {
+        N,
+        E,
+        S,
+        W,
+        NE,
+        NW,
+        SE,
+        SW,
+        UseFood,
+        UseDose,
+        UseCardinalDose,
+        UseDiagonalDose,
+        UseStrongDose,
+        ShowMessageBox { ttl: Duration, message: String },
+    }
+
+    impl From<Command> for crate::state::Command {
+        fn from(old: Command) -> Self {
+            use crate::state::Command as New;
+            match old {
+                Command::N => New::N,
+                Command::E => New::E,
+                Command::S => New::S,
+                Command::W => New::W,
+                Command::NE => New::NE,
+                Command::NW => New::NW,
+                Command::SE => New::SE,
+                Command::SW => New::SW,
+                Command::UseFood => New::Use("Food".into()),
+                Command::UseDose => New::Use("Dose".into()),
+                Command::UseCardinalDose => New::Use("CardinalDose".into()),
+                Command::UseDiagonalDose => New::Use("DiagonalDose".into()),
+                Command::UseStrongDose => New::Use("StrongDose".into()),
+                Command::ShowMessageBox { ttl, message } => New::ShowMessageBox { ttl, message },
+            }
+        }
+    }
+}
+
+impl From<legacy::State> for State {
+    fn from(old: legacy::State) -> Self {
+        State {
+            player: old.player.into(),
+            explosion_animation: None,
+            world_size: old.world_size,
+            chunk_size: old.chunk_size,
+            world: old.world.into(),
+            map_size: old.map_size,
+            panel_width: old.panel_width,
+            display_size: old.display_size,
+            screen_position_in_world: old.screen_position_in_world,
+            seed: old.seed,
+            rng: old.rng,
+            keys: old.keys,
+            mouse: old.mouse,
+            commands: old.commands.into_iter().map(Into::into).collect(),
+            verifications: Default::default(),
+            command_logger: Box::new(io::sink()),
+            replay_path: None,
+            side: old.side,
+            turn: old.turn,
+            cheating: old.cheating,
+            replay: old.replay,
+            replay_full_speed: old.replay_full_speed,
+            exit_after: old.exit_after,
+            clock: old.clock,
+            replay_step: old.replay_step,
+            replay_controller: Default::default(),
+            stats: Default::default(),
+            pos_timer: old.pos_timer,
+            paused: old.paused,
+            old_screen_pos: old.old_screen_pos,
+            new_screen_pos: old.new_screen_pos,
+            screen_fading: old.screen_fading,
+            offset_px: old.offset_px,
+            game_ended: old.game_ended,
+            victory_npc_id: old.victory_npc_id,
+            companions: vec![],
+            distance_maps: None,
+            window_stack: old.window_stack,
+            first_game_already_generated: old.first_game_already_generated,
+            show_keboard_movement_hints: old.show_keboard_movement_hints,
+            show_anxiety_counter: old.show_anxiety_counter,
+            current_help_window: old.current_help_window,
+            show_endscreen_and_uncover_map_during_fadein: old
+                .show_endscreen_and_uncover_map_during_fadein,
+            uncovered_map: old.uncovered_map,
+            save_slot: None,
+            save_slots: vec![],
+            selected_save_slot: 0,
+            selected_command: 0,
+            capturing_key: false,
+            travel: None,
+            cursor: Point::zero(),
+            cursor_mouse_pos: Point::zero(),
+            throw_item: None,
+            messages: Default::default(),
+            message_log_scroll: 0,
+        }
+    }
+}
+
+/// Deserialise the `State` stored in the current save format.
+fn deserialize_state(format_version: u32, data: &[u8]) -> Result<State, Box<dyn Error>> {
+    if format_version != FORMAT_VERSION {
+        error!(&format!("Unknown save format version: {}.", format_version));
+    }
+    let state = bincode::deserialize(data)?;
+    Ok(state)
+}
+
+/// Move the saved game from the 1.0.0 release into a save slot.
+fn import_legacy_save() {
+    let legacy_path = Path::new(LEGACY_SAVE_PATH);
+    if !legacy_path.exists() {
+        return;
+    }
+
+    let result = File::open(legacy_path)
+        .map_err(Into::into)
+        .and_then(|file| read_legacy_save(&mut BufReader::new(file)))
+        .and_then(|mut state| save(&mut state, &new_slot_name()));
+    match result {
+        Ok(()) => {
+            log::info!("Imported the saved game from {}.", LEGACY_SAVE_PATH);
+            if let Err(error) = fs::remove_file(legacy_path) {
+                log::error!("Could not remove {}: {}", LEGACY_SAVE_PATH, error);
+            }
+        }
+        Err(error) => log::error!("Could not import {}: {}", LEGACY_SAVE_PATH, error),
+    }
+}
+
+/// Save the game into the given slot, replacing whatever was there.
+pub fn save(state: &mut State, name: &str) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(directory())?;
+    let path = slot_path(name);
+
+    // NOTE: write into a temporary file first so a failed save
+    // doesn't destroy the previous one.
+    let temp_path = path.with_extension("tmp");
+    write_save(&mut BufWriter::new(File::create(&temp_path)?), state)?;
+    fs::rename(&temp_path, &path)?;
+    log::info!("Saved the game to {}", path.display());
+
+    let replay_path = slot_replay_path(name);
+    match state.replay_path {
+        Some(ref current_replay_path) => {
+            state.command_logger.flush()?;
+            fs::copy(current_replay_path, &replay_path)?;
+        }
+        None => {
+            if replay_path.exists() {
+                fs::remove_file(&replay_path)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Load the game from the given slot.
+///
+/// With `delete_after_load`, the slot is removed once the game is
+/// loaded so it can't be used to undo a death. For autosaves, this
+/// removes all of them. With `record_replay`, the replay log saved
+/// with the game is copied and the recording continues from there.
+pub fn load(
+    name: &str,
+    delete_after_load: bool,
+    record_replay: bool,
+) -> Result<State, Box<dyn Error>> {
+    let path = slot_path(name);
+    log::info!("Loading {}", path.display());
+    let mut state = read_save(&mut BufReader::new(File::open(&path)?))?;
+    if !is_autosave(name) {
+        state.save_slot = Some(name.to_string());
+    }
+
+    // NOTE: continue recording into a copy of the replay log saved
+    // with the game
+    let saved_replay_path = slot_replay_path(name);
+    if record_replay && saved_replay_path.exists() {
+        if let Some(replay_path) = crate::state::generate_replay_path() {
+            fs::copy(&saved_replay_path, &replay_path)?;
+            let file = OpenOptions::new().append(true).open(&replay_path)?;
+            log::info!("Recording the gameplay to '{}'", replay_path.display());
+            state.command_logger = Box::new(file);
+            state.replay_path = Some(replay_path);
+        }
+    }
+
+    if delete_after_load {
+        let result = if is_autosave(name) {
+            delete_autosaves()
+        } else {
+            delete(name)
+        };
+        if let Err(error) = result {
+            log::error!(
+                "Failed to delete the successfully loaded savegame. Error: {:?}",
+                error
+            );
+        }
+    }
+
+    Ok(state)
+}
+
+pub fn delete(name: &str) -> Result<(), Box<dyn Error>> {
+    fs::remove_file(slot_path(name))?;
+    let replay_path = slot_replay_path(name);
+    if replay_path.exists() {
+        fs::remove_file(replay_path)?;
+    }
+    Ok(())
+}
+
+/// Save the game into the next autosave slot.
+pub fn autosave(state: &mut State) {
+    let index = (state.turn / AUTOSAVE_INTERVAL) % AUTOSAVE_SLOTS + 1;
+    let name = format!("{}{}", AUTOSAVE_PREFIX, index);
+    if let Err(error) = save(state, &name) {
+        log::error!("Could not autosave the game: {:?}", error);
+    }
+}
+
+/// The most recent autosave, if there is any.
+pub fn latest_autosave() -> Option<Slot> {
+    // NOTE: the slots are sorted from the most recent one
+    list_slots()
+        .into_iter()
+        .find(|slot| is_autosave(&slot.name))
+}
+
+pub fn delete_autosaves() -> Result<(), Box<dyn Error>> {
+    for index in 1..=AUTOSAVE_SLOTS {
+        let name = format!("{}{}", AUTOSAVE_PREFIX, index);
+        if slot_path(&name).exists() {
+            delete(&name)?;
+        }
+    }
+    Ok(())
+}
+
+/// Describe how long ago was the game saved, e.g. "5 minutes ago".
+pub fn saved_ago(metadata: &Metadata) -> String {
+    let seconds = now().saturating_sub(metadata.saved_at);
+    if seconds < 60 {
+        return "just now".into();
+    }
+    let (value, unit) = if seconds < 60 * 60 {
+        (seconds / 60, "minute")
+    } else if seconds < 24 * 60 * 60 {
+        (seconds / (60 * 60), "hour")
+    } else {
+        (seconds / (24 * 60 * 60), "day")
+    };
+    let plural = if value == 1 { "" } else { "s" };
+    format!("{} {}{} ago", value, unit, plural)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_legacy_save, read_save, write_save};
+    use crate::{point::Point, state::State, status};
+
+    use std::{fs, path::Path};
+
+    #[test]
+    fn test_save_and_load() {
+        let mut state = State::new_test_game();
+        state.log_event(crate::message::Event::VictoryNpcAppeared);
+        state.player.status.add(status::Kind::Nausea, 4);
+        let slowed = state.world.all_monsters().next().unwrap().position;
+        let monster = state.world.monster_on_pos(slowed).unwrap();
+        monster.status.add(status::Kind::Slow, 2);
+        let mut data = vec![];
+        write_save(&mut data, &state).unwrap();
+        let mut loaded = read_save(&mut &data[..]).unwrap();
+        assert_eq!(loaded.seed, 42);
+        assert_eq!(loaded.player.pos, state.player.pos);
+        assert_eq!(loaded.player.status, state.player.status);
+        let monster = loaded.world.monster_on_pos(slowed).unwrap();
+        assert_eq!(monster.status.turns(status::Kind::Slow), 2);
+        assert_eq!(loaded.messages, state.messages);
+        assert_eq!(
+            loaded.world.monster_definitions,
+            state.world.monster_definitions
+        );
+        assert_eq!(loaded.world.item_definitions, state.world.item_definitions);
+        assert_eq!(loaded.world.generator, state.world.generator);
+    }
+
+    #[test]
+    fn test_detect_corrupted_save() {
+        let mut data = vec![];
+        write_save(&mut data, &State::new_test_game()).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        let error = read_save(&mut &data[..]).err().unwrap();
+        assert!(error.to_string().contains("corrupted"));
+    }
+
+    #[test]
+    fn test_load_legacy_save() {
+        // NOTE: saved by the 1.0.0 release with seed 42 at turn 17.
+        // The player had a dose and a food, 3 turns of panic and 2 of
+        // stun.
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("saves")
+            .join("SAVEDGAME-1.0.0.sav");
+        let data = fs::read(path).unwrap();
+        let loaded = read_legacy_save(&mut &data[..]).unwrap();
+        assert_eq!(loaded.seed, 42);
+        assert_eq!(loaded.turn, 17);
+        assert_eq!(loaded.player.pos, Point::zero());
+        assert_eq!(loaded.player.inventory.len(), 2);
+        assert_eq!(loaded.player.status.turns(status::Kind::Panic), 3);
+        assert_eq!(loaded.player.status.turns(status::Kind::Stun), 2);
+        assert!(loaded.world.all_monsters().count() > 0);
+
+        // NOTE: the migrated game saves and loads in the current
+        // format.
+        let mut data = vec![];
+        write_save(&mut data, &loaded).unwrap();
+        let reloaded = read_save(&mut &data[..]).unwrap();
+        assert_eq!(reloaded.player.pos, loaded.player.pos);
+        assert_eq!(reloaded.player.ap(), loaded.player.ap());
+        assert_eq!(reloaded.player.status, loaded.player.status);
+    }
+}
@@ -0,0 +1,365 @@
+//! The user settings file.
+//!
+//! The settings live in `settings.toml` in the per-user config
+//! directory (e.g. `~/.config/dose-response/settings.toml` on Linux).
+//! We edit the file through `toml_edit` rather than serialising
+//! `engine::Settings` directly so any comments or formatting the
+//! player added are kept when we write the changes back.
+
+use crate::{
+    engine::Settings,
+    keymap::{self, Keymap, Preset},
+    keys::Key,
+    palette::Palette,
+};
+
+use std::{error::Error, fs, path::PathBuf};
+
+use toml_edit::{value, Array, Document};
+
+/// The settings file path.
+pub fn path() -> PathBuf {
+    match dirs::config_dir() {
+        Some(dir) => dir.join("dose-response").join("settings.toml"),
+        None => {
+            log::warn!("Could not find the user config directory. Using the current one.");
+            PathBuf::from("settings.toml")
+        }
+    }
+}
+
+/// The graphics backends the player can choose from. The headless
+/// one only makes sense on the command line.
+pub fn graphics_backends() -> impl Iterator<Item = &'static str> {
+    crate::engine::AVAILABLE_BACKENDS
+        .iter()
+        .cloned()
+        .filter(|&backend| backend != "headless")
+}
+
+/// The graphics backend following `current` (wrapping around).
+pub fn next_backend(current: &str) -> Option<&'static str> {
+    let backends = graphics_backends().collect::<Vec<_>>();
+    let index = backends.iter().position(|&b| b == current).unwrap_or(0);
+    backends.get((index + 1) % backends.len().max(1)).cloned()
+}
+
+/// Generate the contents of a new settings file, documenting every
+/// option.
+fn default_contents() -> String {
+    let defaults = Settings::default();
+    let mut contents = String::with_capacity(1000);
+
+    contents.push_str("# Options: \"fullscreen\" or \"window\"\n");
+    contents.push_str("display = \"window\"\n\n");
+
+    let font_sizes_str = crate::engine::AVAILABLE_FONT_SIZES
+        .iter()
+        .map(|num| num.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    contents.push_str(&format!("# Options: {}\n", font_sizes_str));
+    contents.push_str(&format!("font_size = {}\n\n", defaults.font_size));
+
+    let backends_str = graphics_backends()
+        .map(|b| format!("\"{}\"", b))
+        .collect::<Vec<_>>()
+        .join(", ");
+    contents.push_str(&format!("# Options: {}\n", backends_str));
+    contents.push_str(&format!("backend = \"{}\"\n\n", defaults.backend));
+
+    let palettes_str = Palette::all()
+        .map(|p| format!("\"{}\"", p.name()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    contents.push_str(&format!("# Options: {}\n", palettes_str));
+    contents.push_str(&format!("palette = \"{}\"\n\n", defaults.palette.name()));
+
+    contents.push_str("# Delete a saved game as soon as it's loaded\n");
+    contents.push_str(&format!("permadeath = {}\n\n", defaults.permadeath));
+
+    contents.push_str("# Record a replay of every new game\n");
+    contents.push_str(&format!("record_replays = {}\n\n", defaults.record_replays));
+
+    let presets_str = Preset::all()
+        .map(|p| format!("\"{}\"", p.name()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    contents.push_str(&format!("# Options: {}\n", presets_str));
+    contents.push_str(&format!(
+        "keymap = \"{}\"\n\n",
+        defaults.keymap.preset.name()
+    ));
+
+    let actions_str = keymap::actions()
+        .iter()
+        .map(keymap::action_name)
+        .collect::<Vec<_>>()
+        .join(", ");
+    contents.push_str("# Keys that differ from the keymap preset, e.g.:\n");
+    contents.push_str("# north = [\"NumPad8\", \"Shift+Up\"]\n");
+    contents.push_str(&format!("# Actions: {}\n", actions_str));
+    contents.push_str("[keys]\n");
+
+    contents
+}
+
+fn default_document() -> Document {
+    default_contents()
+        .parse()
+        .expect("The default settings must be valid TOML.")
+}
+
+fn load_document() -> Document {
+    let path = path();
+    match fs::read_to_string(&path) {
+        Ok(contents) => match contents.parse::<Document>() {
+            Ok(document) => document,
+            Err(error) => {
+                log::error!(
+                    "Could not parse the settings file {}: {}",
+                    path.display(),
+                    error
+                );
+                default_document()
+            }
+        },
+        Err(_) => {
+            log::info!(
+                "No settings file at {}, using the defaults.",
+                path.display()
+            );
+            default_document()
+        }
+    }
+}
+
+/// Read the settings from the `document`. Any missing or invalid
+/// value is replaced with its default.
+fn settings_from_document(document: &Document) -> Settings {
+    let mut settings = Settings::default();
+
+    match document["display"].as_str() {
+        Some("fullscreen") => settings.fullscreen = true,
+        Some("window") => settings.fullscreen = false,
+        _ => log::warn!("The `display` setting must be \"fullscreen\" or \"window\"."),
+    }
+
+    match document["font_size"].as_integer() {
+        Some(font_size) if crate::engine::AVAILABLE_FONT_SIZES.contains(&(font_size as i32)) => {
+            settings.font_size = font_size as i32;
+        }
+        _ => log::warn!(
+            "The `font_size` setting must be one of: {:?}.",
+            crate::engine::AVAILABLE_FONT_SIZES
+        ),
+    }
+
+    match document["backend"].as_str() {
+        Some(backend) if graphics_backends().any(|b| b == backend) => {
+            settings.backend = backend.into();
+        }
+        _ => log::warn!(
+            "The `backend` setting must be one of: {:?}.",
+            graphics_backends().collect::<Vec<_>>()
+        ),
+    }
+
+    match document["palette"].as_str().and_then(Palette::from_name) {
+        Some(palette) => settings.palette = palette,
+        None => log::warn!(
+            "The `palette` setting must be one of: {:?}.",
+            Palette::all().map(Palette::name).collect::<Vec<_>>()
+        ),
+    }
+
+    let flag = |key: &str, default: bool| match document[key].as_bool() {
+        Some(value) => value,
+        None => {
+            log::warn!("The `{}` setting must be `true` or `false`.", key);
+            default
+        }
+    };
+    settings.permadeath = flag("permadeath", settings.permadeath);
+    settings.record_replays = flag("record_replays", settings.record_replays);
+
+    match document["keymap"].as_str().and_then(Preset::from_name) {
+        Some(preset) => settings.keymap = Keymap::from_preset(preset),
+        None => log::warn!(
+            "The `keymap` setting must be one of: {:?}.",
+            Preset::all().map(Preset::name).collect::<Vec<_>>()
+        ),
+    }
+
+    if let Some(table) = document["keys"].as_table_like() {
+        for (name, item) in table.iter() {
+            let action = match keymap::action_from_name(name) {
+                Some(action) => action,
+                None => {
+                    log::warn!("Unknown action in the `keys` setting: `{}`.", name);
+                    continue;
+                }
+            };
+            let keys = match item.as_array() {
+                Some(keys) => keys,
+                None => {
+                    log::warn!("The `keys.{}` setting must be a list of keys.", name);
+                    continue;
+                }
+            };
+            let mut parsed_keys = vec![];
+            for key in keys.iter() {
+                match key.as_str().map(str::parse::<Key>) {
+                    Some(Ok(key)) => parsed_keys.push(key),
+                    Some(Err(error)) => log::warn!("The `keys.{}` setting: {}", name, error),
+                    None => log::warn!("The `keys.{}` setting must be a list of strings.", name),
+                }
+            }
+            settings.keymap.set_keys(&action, parsed_keys);
+        }
+    }
+
+    settings
+}
+
+/// Write the `settings` into the `document`. Only the values are
+/// replaced so the comments and the order of the keys are preserved.
+fn update_document(document: &mut Document, settings: &Settings) {
+    document["display"] = value(if settings.fullscreen {
+        "fullscreen"
+    } else {
+        "window"
+    });
+    document["font_size"] = value(i64::from(settings.font_size));
+    document["backend"] = value(settings.backend.as_str());
+    document["palette"] = value(settings.palette.name());
+    document["permadeath"] = value(settings.permadeath);
+    document["record_replays"] = value(settings.record_replays);
+    document["keymap"] = value(settings.keymap.preset.name());
+
+    if !document["keys"].is_table() {
+        document["keys"] = toml_edit::table();
+    }
+    let keys_table = document["keys"]
+        .as_table_mut()
+        .expect("The `keys` setting must be a table.");
+    let customised = settings.keymap.customised();
+    for action in keymap::actions() {
+        let name = keymap::action_name(&action);
+        match customised.iter().find(|(a, _)| *a == action) {
+            Some((_, keys)) => {
+                let mut array = Array::default();
+                for key in keys {
+                    array.push(key.to_string());
+                }
+                *keys_table.entry(name) = value(array);
+            }
+            None => {
+                keys_table.remove(name);
+            }
+        }
+    }
+}
+
+/// Load the settings from the settings file or return the defaults
+/// if it doesn't exist yet.
+pub fn load() -> Settings {
+    let settings = settings_from_document(&load_document());
+    log::info!("Loaded settings: {:?}", settings);
+    settings
+}
+
+/// Write the `settings` into the settings file, creating it if
+/// necessary.
+pub fn save(settings: &Settings) -> Result<(), Box<dyn Error>> {
+    let path = path();
+    let mut document = load_document();
+    update_document(&mut document, settings);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, document.to_string())?;
+    log::info!("Saved the settings to {}", path.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_document_has_default_settings() {
+        assert_eq!(
+            settings_from_document(&default_document()),
+            Settings::default()
+        );
+    }
+
+    #[test]
+    fn test_update_keeps_comments() {
+        let mut document = "# I like it big\nfont_size = 16\n\n# My own note\npermadeath = true\n"
+            .parse::<Document>()
+            .unwrap();
+        let settings = Settings {
+            permadeath: false,
+            palette: Palette::Original,
+            ..settings_from_document(&document)
+        };
+        update_document(&mut document, &settings);
+
+        let contents = document.to_string();
+        assert!(contents.contains("# I like it big\nfont_size = 16"));
+        assert!(contents.contains("# My own note\npermadeath = false"));
+        assert!(contents.contains("palette = \"original\""));
+
+        let reloaded = settings_from_document(&contents.parse().unwrap());
+        assert_eq!(reloaded, settings);
+    }
+
+    #[test]
+    fn test_keymap_overrides() {
+        use crate::keymap::Action;
+        use crate::keys::KeyCode;
+        use crate::state::Command;
+
+        let mut document = default_document();
+        let mut settings = Settings {
+            keymap: Keymap::from_preset(Preset::Vi),
+            ..Settings::default()
+        };
+        settings.keymap.set_keys(
+            &Action::Command(Command::N),
+            vec![
+                Key::plain(KeyCode::W),
+                Key {
+                    shift: true,
+                    ..Key::plain(KeyCode::Up)
+                },
+            ],
+        );
+        settings
+            .keymap
+            .set_keys(&Action::Throw, vec![Key::plain(KeyCode::G)]);
+        update_document(&mut document, &settings);
+
+        let contents = document.to_string();
+        assert!(contents.contains("keymap = \"vi\""));
+        assert!(contents.contains("\nnorth = [\"W\", \"Shift+Up\"]"));
+        assert!(contents.contains("\nthrow = [\"G\"]"));
+        assert!(!contents.contains("\nsouth ="));
+        assert_eq!(settings_from_document(&contents.parse().unwrap()), settings);
+
+        // Going back to the preset removes the override
+        settings.keymap = Keymap::from_preset(Preset::Vi);
+        update_document(&mut document, &settings);
+        assert!(!document.to_string().contains("\nnorth ="));
+    }
+
+    #[test]
+    fn test_invalid_values_fall_back_to_defaults() {
+        let document = "display = 3\nfont_size = 7\npalette = \"sepia\"\npermadeath = \"no\"\n"
+            .parse::<Document>()
+            .unwrap();
+        assert_eq!(settings_from_document(&document), Settings::default());
+    }
+}
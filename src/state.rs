@@ -1,13 +1,20 @@
 use crate::{
     animation::{AreaOfEffect, ScreenFade},
+    companion::Companion,
+    distance_map::DistanceMaps,
     engine::Mouse,
+    generators::Generator,
+    item,
     keys::Keys,
+    message::{Event, Message, MessageLog},
     monster,
-    player::Player,
+    player::{Mind, Player},
     point::Point,
     random::{self, Random},
+    replay, savegame,
     stats::Stats,
     timer::Timer,
+    travel::Travel,
     util,
     window::Window,
     windows,
@@ -18,6 +25,7 @@ use std::{
     collections::VecDeque,
     error::Error,
     fs::File,
+    hash::Hasher,
     io::{self, Write},
     path::Path,
     path::PathBuf,
@@ -41,7 +49,7 @@ pub enum Side {
 // TODO: rename this to Input or something like that. This represents the raw
 // commands from the player or AI abstracted from keyboard, joystick or
 // whatever. But they shouldn't carry any context or data.
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Command {
     N,
     E,
@@ -51,12 +59,54 @@ pub enum Command {
     NW,
     SE,
     SW,
-    UseFood,
-    UseDose,
-    UseCardinalDose,
-    UseDiagonalDose,
-    UseStrongDose,
-    ShowMessageBox { ttl: Duration, message: String },
+    /// Use one item with this id from the inventory.
+    Use(String),
+    ShowMessageBox {
+        ttl: Duration,
+        message: String,
+    },
+    /// Take one step towards the nearest unexplored tile.
+    Explore,
+    /// Put one item with this id on the player's tile.
+    Drop(String),
+    /// Throw a dose at the `target` world position. It explodes there
+    /// rather than around the player.
+    Throw {
+        item: String,
+        target: Point,
+    },
+}
+
+impl Command {
+    /// The command moving the player one step in the `direction`.
+    pub fn movement(direction: Point) -> Option<Command> {
+        match direction.tuple() {
+            (0, -1) => Some(Command::N),
+            (0, 1) => Some(Command::S),
+            (-1, 0) => Some(Command::W),
+            (1, 0) => Some(Command::E),
+            (-1, -1) => Some(Command::NW),
+            (1, -1) => Some(Command::NE),
+            (-1, 1) => Some(Command::SW),
+            (1, 1) => Some(Command::SE),
+            _ => None,
+        }
+    }
+
+    /// The direction the movement command goes in.
+    pub fn direction(&self) -> Option<Point> {
+        match self {
+            Command::N => Some(Point::new(0, -1)),
+            Command::S => Some(Point::new(0, 1)),
+            Command::W => Some(Point::new(-1, 0)),
+            Command::E => Some(Point::new(1, 0)),
+            Command::NW => Some(Point::new(-1, -1)),
+            Command::NE => Some(Point::new(1, -1)),
+            Command::SW => Some(Point::new(-1, 1)),
+            Command::SE => Some(Point::new(1, 1)),
+            _ => None,
+        }
+    }
 }
 
 #[cfg(feature = "replay")]
@@ -81,12 +131,36 @@ pub fn generate_replay_path() -> Option<PathBuf> {
     None
 }
 
+/// A summary of the game state at the end of a turn. It's stored in
+/// the replay log and compared with the replayed game.
+///
+/// NOTE: the optional fields were added later. The replays recorded
+/// before that don't have them so we can't check them there.
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Verification {
     pub turn: i32,
     pub chunk_count: usize,
     pub player_pos: Point,
-    pub monsters: Vec<(Point, Point, monster::Kind)>,
+    /// The position, chunk position and `monster::Definition::id` of
+    /// every monster.
+    pub monsters: Vec<(Point, Point, String)>,
+    #[serde(default)]
+    pub player_mind: Option<Mind>,
+    #[serde(default)]
+    pub player_will: Option<i32>,
+    #[serde(default)]
+    pub player_tolerance: Option<i32>,
+    #[serde(default)]
+    pub player_ap: Option<i32>,
+    #[serde(default)]
+    pub player_inventory: Option<Vec<String>>,
+    /// Hash of the random number generator's internal state.
+    #[serde(default)]
+    pub rng_hash: Option<u64>,
+    /// Position and hash of the tiles and items of every chunk,
+    /// sorted by the position.
+    #[serde(default)]
+    pub chunk_digests: Option<Vec<(Point, u64)>>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -123,6 +197,9 @@ pub struct State {
     pub verifications: VecDeque<Verification>,
     #[serde(skip_serializing, skip_deserializing, default = "empty_command_logger")]
     pub command_logger: Box<dyn Write>,
+    /// The file `command_logger` is recording the game into.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub replay_path: Option<PathBuf>,
     pub side: Side,
     pub turn: i32,
     pub cheating: bool,
@@ -132,6 +209,8 @@ pub struct State {
     pub clock: Duration,
     pub replay_step: Duration,
     #[serde(skip_serializing, skip_deserializing)]
+    pub replay_controller: replay::Controller,
+    #[serde(skip_serializing, skip_deserializing)]
     pub stats: Stats,
     pub pos_timer: Timer,
     pub paused: bool,
@@ -144,6 +223,14 @@ pub struct State {
     /// show the endgame screen -- uncovered map, the score, etc.
     pub game_ended: bool,
     pub victory_npc_id: Option<MonsterId>,
+    /// The NPCs accompanying the player, the one who joined first
+    /// comes first.
+    pub companions: Vec<Companion>,
+    /// The monsters and the player find their way using these. They
+    /// are rebuilt from the world as needed. See
+    /// `game::update_distance_maps`.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub distance_maps: Option<DistanceMaps>,
 
     pub window_stack: windows::Windows<Window>,
 
@@ -160,6 +247,40 @@ pub struct State {
     /// effects here instead.
     pub show_endscreen_and_uncover_map_during_fadein: bool,
     pub uncovered_map: bool,
+
+    /// The slot this game was loaded from or last saved to.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub save_slot: Option<String>,
+    /// The saved games shown in the load game window. We only read
+    /// them when the window opens.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub save_slots: Vec<savegame::Slot>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub selected_save_slot: usize,
+    /// The command selected in the controls window.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub selected_command: usize,
+    /// The controls window is waiting for a key to bind to the
+    /// selected command.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub capturing_key: bool,
+    /// The route to the map tile the player clicked on.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub travel: Option<Travel>,
+    /// The world position the look and throw windows point at.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub cursor: Point,
+    /// The mouse position when it last moved the cursor.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub cursor_mouse_pos: Point,
+    /// The dose selected in the throw window.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub throw_item: Option<String>,
+    pub messages: MessageLog,
+    /// How many of the newest messages the message log window
+    /// scrolled past.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub message_log_scroll: usize,
 }
 
 impl State {
@@ -172,6 +293,9 @@ impl State {
         verifications: VecDeque<Verification>,
         log_writer: W,
         seed: u32,
+        generator: Generator,
+        monster_definitions: Vec<monster::Definition>,
+        item_definitions: Vec<item::Definition>,
         cheating: bool,
         invincible: bool,
         replay: bool,
@@ -184,7 +308,16 @@ impl State {
         let player_position = world_centre;
         let player = Player::new(player_position, invincible);
         let mut rng = random::from_seed(u64::from(seed));
-        let world = World::new(&mut rng, seed, world_size.x, 32, player.info());
+        let world = World::new(
+            &mut rng,
+            seed,
+            world_size.x,
+            32,
+            player.info(),
+            generator,
+            monster_definitions,
+            item_definitions,
+        );
 
         State {
             player,
@@ -203,6 +336,7 @@ impl State {
             commands,
             verifications,
             command_logger: Box::new(log_writer),
+            replay_path: None,
             side: Side::Player,
             turn: 0,
             cheating,
@@ -211,6 +345,7 @@ impl State {
             exit_after,
             clock: Duration::new(0, 0),
             replay_step: Duration::new(0, 0),
+            replay_controller: Default::default(),
             stats: Default::default(),
             pos_timer: Timer::new(Duration::from_millis(0)),
             old_screen_pos: (0, 0).into(),
@@ -220,6 +355,8 @@ impl State {
             screen_fading: None,
             game_ended: false,
             victory_npc_id: None,
+            companions: vec![],
+            distance_maps: None,
             window_stack: windows::Windows::new(Window::Game),
             first_game_already_generated: false,
             show_keboard_movement_hints: true,
@@ -227,6 +364,17 @@ impl State {
             current_help_window: windows::help::Page::DoseResponse,
             show_endscreen_and_uncover_map_during_fadein: false,
             uncovered_map: false,
+            save_slot: None,
+            save_slots: vec![],
+            selected_save_slot: 0,
+            selected_command: 0,
+            capturing_key: false,
+            travel: None,
+            cursor: Point::zero(),
+            cursor_mouse_pos: Point::zero(),
+            throw_item: None,
+            messages: Default::default(),
+            message_log_scroll: 0,
         }
     }
 
@@ -238,12 +386,35 @@ impl State {
         exit_after: bool,
         replay_path: Option<PathBuf>,
         invincible: bool,
+    ) -> State {
+        State::new_game_with_seed(
+            world_size,
+            map_size,
+            panel_width,
+            display_size,
+            exit_after,
+            replay_path,
+            invincible,
+            util::random_seed(),
+        )
+    }
+
+    /// Same as `new_game` but the world is generated from the given
+    /// seed. Useful for reproducing games without a replay file.
+    pub fn new_game_with_seed(
+        world_size: Point,
+        map_size: i32,
+        panel_width: i32,
+        display_size: Point,
+        exit_after: bool,
+        replay_path: Option<PathBuf>,
+        invincible: bool,
+        seed: u32,
     ) -> State {
         let commands = VecDeque::new();
         let verifications = VecDeque::new();
-        let seed = util::random_seed();
-        let mut writer: Box<dyn Write> = if let Some(replay_path) = replay_path {
-            match File::create(&replay_path) {
+        let mut writer: Box<dyn Write> = if let Some(ref replay_path) = replay_path {
+            match File::create(replay_path) {
                 Ok(f) => {
                     log::info!("Recording the gameplay to '{}'", replay_path.display());
                     Box::new(f)
@@ -259,11 +430,23 @@ Reason: '{}'.",
             Box::new(io::sink())
         };
 
-        log_header(&mut writer, seed);
+        let monster_definitions = monster::load_definitions();
+        let item_definitions = item::load_definitions();
+        replay::log_header(
+            &mut writer,
+            &replay::Header::new(
+                seed,
+                invincible,
+                world_size,
+                Generator::CURRENT,
+                monster_definitions.clone(),
+                item_definitions.clone(),
+            ),
+        );
         let cheating = false;
         let replay = false;
         let replay_full_speed = false;
-        State::new(
+        let mut state = State::new(
             world_size,
             map_size,
             panel_width,
@@ -272,14 +455,43 @@ Reason: '{}'.",
             verifications,
             writer,
             seed,
+            Generator::CURRENT,
+            monster_definitions,
+            item_definitions,
             cheating,
             invincible,
             replay,
             replay_full_speed,
             exit_after,
+        );
+        state.replay_path = replay_path;
+        state
+    }
+
+    /// The game the tests play: the default sizes and seed 42.
+    #[cfg(test)]
+    pub fn new_test_game() -> State {
+        State::new_game_with_seed(
+            crate::WORLD_SIZE,
+            crate::DISPLAYED_MAP_SIZE,
+            crate::PANEL_WIDTH,
+            crate::DISPLAY_SIZE,
+            false,
+            None,
+            false,
+            42,
         )
     }
 
+    /// Play the `command` and the monsters' turns that follow it.
+    #[cfg(test)]
+    pub fn run_command(&mut self, command: Command) {
+        self.commands.push_back(command);
+        while !self.commands.is_empty() {
+            crate::game::simulate_step(self);
+        }
+    }
+
     #[cfg(not(feature = "replay"))]
     #[allow(dead_code)]
     pub fn replay_game(
@@ -316,68 +528,33 @@ Reason: '{}'.",
         replay_full_speed: bool,
         exit_after: bool,
     ) -> Result<State, Box<dyn Error>> {
-        use serde_json;
-        use std::io::{BufRead, BufReader};
+        use crate::replay::Record;
+
         let mut commands = VecDeque::new();
         let mut verifications = VecDeque::new();
-        let seed: u32;
-        let file = File::open(replay_path)?;
-        let mut lines = BufReader::new(file).lines();
-        match lines.next() {
-            Some(seed_str) => seed = seed_str?.parse()?,
-            None => error!("The replay file is empty."),
-        };
-
-        match lines.next() {
-            Some(version) => {
-                let version = version?;
-                if version != crate::metadata::VERSION {
-                    log::warn!(
-                        "The replay file's version is: {}, but the program is: {}",
-                        version,
-                        crate::metadata::VERSION
-                    );
-                }
-            }
-            None => error!("The replay file is missing the version."),
-        };
-
-        match lines.next() {
-            Some(commit) => {
-                let commit = commit?;
-                if commit != crate::metadata::GIT_HASH {
-                    log::warn!(
-                        "The replay file's commit is: {}, but the program is: {}.",
-                        commit,
-                        crate::metadata::GIT_HASH
-                    );
-                }
-            }
-            None => error!("The replay file is missing the commit hash."),
-        };
-
-        loop {
-            match lines.next() {
-                Some(line) => {
-                    let line = line?;
-                    let command = serde_json::from_str(&line);
-                    // Try parsing it as a command, otherwise it's a verification
-                    if let Ok(command) = command {
-                        commands.push_back(command);
-                    } else {
-                        let verification = serde_json::from_str(&line)?;
-                        verifications.push_back(verification);
-                    }
-                }
-                None => break,
+        let contents = fs::read_to_string(replay_path)?;
+        let replay = replay::parse(&contents, world_size)?;
+        for record in replay.records {
+            match record {
+                Record::Command(command) => commands.push_back(command),
+                Record::Verification(verification) => verifications.push_back(verification),
+                Record::Annotation(note) => log::info!("Replay annotation: {}", note),
+                Record::Message(message) => log::debug!("Replay message: {}", message),
             }
         }
 
         log::info!("Replaying game log: '{}'", replay_path.display());
         let cheating = cheating;
-        let invincible = invincible;
+        let invincible = invincible || replay.header.invincible;
+        let seed = replay.header.seed;
+        let world_size = replay.header.world_size;
+        let generator = replay.header.world_generator;
+        let monster_definitions = replay.header.monsters;
+        let item_definitions = replay.header.items;
+        let controller =
+            replay::Controller::new(commands.len(), verifications.iter().cloned().collect());
         let replay = true;
-        Ok(State::new(
+        let mut state = State::new(
             world_size,
             map_size,
             panel_width,
@@ -386,12 +563,30 @@ Reason: '{}'.",
             verifications,
             Box::new(io::sink()),
             seed,
+            generator,
+            monster_definitions,
+            item_definitions,
             cheating,
             invincible,
             replay,
             replay_full_speed,
             exit_after,
-        ))
+        );
+        state.replay_controller = controller;
+        replay::save_snapshot(&mut state);
+        Ok(state)
+    }
+
+    /// Tell the player about the `event` and record it in the
+    /// replay.
+    pub fn log_event(&mut self, event: Event) {
+        let message = Message {
+            turn: self.turn,
+            event,
+        };
+        log::info!("Turn {}: {}", message.turn, message.event);
+        self.messages.push(message);
+        replay::log_message(&mut self.command_logger, message);
     }
 
     pub fn verification(&self) -> Verification {
@@ -401,104 +596,55 @@ Reason: '{}'.",
         for &chunk_pos in &chunks {
             for monster in self.world.chunk(chunk_pos).unwrap().monsters() {
                 if !monster.dead {
-                    monsters.push((monster.position, chunk_pos, monster.kind));
+                    monsters.push((monster.position, chunk_pos, monster.id.clone()));
                 }
             }
         }
-        monsters
-            .sort_by_key(|&(monster_pos, _chunk_pos, kind)| (monster_pos.x, monster_pos.y, kind));
+        monsters.sort_by(|(a, _, a_id), (b, _, b_id)| (a.x, a.y, a_id).cmp(&(b.x, b.y, b_id)));
+
+        let rng_hash = bincode::serialize(&self.rng).ok().map(|data| {
+            let mut hasher = util::StableHasher::default();
+            hasher.write(&data);
+            hasher.finish()
+        });
+
+        let mut chunk_digests = chunks
+            .iter()
+            .filter_map(|&pos| self.world.chunk(pos).map(|chunk| (pos, chunk)))
+            .map(|(pos, chunk)| {
+                let mut hasher = util::StableHasher::default();
+                // NOTE: the derived `Hash` writes native-endian
+                // `isize` discriminants so the digests would differ
+                // between 32 and 64-bit builds. Write explicit bytes.
+                for (_pos, cell) in chunk.cells() {
+                    hasher.write_u8(cell.tile.kind as u8);
+                    for item in &cell.items {
+                        hasher.write(&(item.id.len() as u32).to_le_bytes());
+                        hasher.write(item.id.as_bytes());
+                        hasher.write(&item.irresistible.to_le_bytes());
+                    }
+                }
+                (pos, hasher.finish())
+            })
+            .collect::<Vec<_>>();
+        chunk_digests.sort_by_key(|&(pos, _)| (pos.x, pos.y));
 
         Verification {
             turn: self.turn,
             chunk_count: chunks.len(),
             player_pos: self.player.pos,
             monsters,
+            player_mind: Some(self.player.mind),
+            player_will: Some(self.player.will.to_int()),
+            player_tolerance: Some(self.player.tolerance),
+            player_ap: Some(self.player.ap()),
+            player_inventory: Some(self.player.inventory.iter().map(|i| i.id.clone()).collect()),
+            rng_hash,
+            chunk_digests: Some(chunk_digests),
         }
     }
-
-    pub fn save_to_file(&self) -> Result<(), Box<dyn Error>> {
-        // TODO: select the filename dynamicaly!
-        let filename = "SAVEDGAME.sav";
-        let version_data = bincode::serialize(crate::metadata::VERSION)?;
-        let commit_data = bincode::serialize(crate::metadata::GIT_HASH)?;
-        let state_data = bincode::serialize(self)?;
-
-        // TODO: this can be compressed nicely!
-
-        let mut file = File::create(filename)?;
-        file.write_all(&version_data)?;
-        file.write_all(&commit_data)?;
-        file.write_all(&state_data)?;
-        file.flush()?;
-
-        Ok(())
-    }
-
-    pub fn load_from_file() -> Result<State, Box<dyn Error>> {
-        let filename = "SAVEDGAME.sav";
-        let state = {
-            let file = File::open(filename)?;
-            let version: String = bincode::deserialize_from(&file)?;
-            log::info!("Savefile version {}", version);
-            if version != crate::metadata::VERSION {
-                log::warn!("The game was saved in a different version: {}. This release has version: {}. The game might not load properly.",
-                           version,
-                           crate::metadata::VERSION);
-            }
-            let commit: String = bincode::deserialize_from(&file)?;
-            log::info!("Savefile commit {}", commit);
-            if commit != crate::metadata::GIT_HASH {
-                log::warn!("The game was saved in a different commit: {}. This release has commit: {}. The game might not load properly.",
-                           commit,
-                crate::metadata::GIT_HASH);
-            }
-            bincode::deserialize_from(&file)?
-        };
-
-        if let Err(error) = ::std::fs::remove_file(filename) {
-            log::error!(
-                "Failed to delete the successfully loaded savegame. Error: {:?}",
-                error
-            );
-        }
-
-        Ok(state)
-    }
 }
 
 fn empty_command_logger() -> Box<dyn Write> {
     Box::new(io::sink())
 }
-
-pub fn log_header<W: Write>(writer: &mut W, seed: u32) {
-    writeln!(writer, "{}", seed).unwrap();
-    writeln!(writer, "{}", crate::metadata::VERSION).unwrap();
-    writeln!(writer, "{}", crate::metadata::GIT_HASH).unwrap();
-}
-
-pub fn log_command<W: Write>(writer: &mut W, command: Command) {
-    use serde_json;
-    let json_command = serde_json::to_string(&command).expect(&format!(
-        "Could not \
-         serialise {:?} to \
-         json.",
-        command
-    ));
-    writeln!(writer, "{}", json_command).unwrap();
-}
-
-pub fn log_verification<W: Write>(writer: &mut W, verification: &Verification) {
-    use serde_json;
-    let json = serde_json::to_string(&verification).expect(&format!(
-        "Could not \
-         serialise \
-         {:?} to json.",
-        verification
-    ));
-    writeln!(writer, "{}", json).expect(&format!(
-        "Could not write the \
-         verification: '{}' to the \
-         replay log.",
-        json
-    ));
-}
@@ -0,0 +1,199 @@
+//! Timed conditions of the player and the monsters, e.g. being
+//! stunned.
+//!
+//! The `Player` and every `Monster` have their `StatusEffects`. They
+//! count down at the start of each turn and the owner decides what
+//! an active effect does (see `Player::new_turn` and
+//! `Monster::new_turn`).
+
+use crate::{
+    color::{self, Color},
+    formula,
+};
+
+use std::{
+    cmp,
+    fmt::{Display, Error, Formatter},
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Kind {
+    /// Can't do anything.
+    Stun,
+    /// Moves in a random direction.
+    Panic,
+    /// Has one action point less (but always at least one).
+    Slow,
+    /// Can't keep any food down.
+    Nausea,
+}
+
+/// What happens when an effect is added while the same kind is
+/// already active.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Stacking {
+    /// The durations add up to at most `max` turns.
+    Add { max: i32 },
+    /// The longer of the two durations is kept.
+    Longest,
+}
+
+impl Kind {
+    pub fn stacking(self) -> Stacking {
+        use self::Kind::*;
+        match self {
+            Stun => Stacking::Add {
+                max: formula::STUN_TURNS.1,
+            },
+            Panic => Stacking::Add {
+                max: formula::PANIC_TURNS.1,
+            },
+            Slow | Nausea => Stacking::Longest,
+        }
+    }
+
+    /// The background of the affected player or monster on the map.
+    pub fn color(self) -> Color {
+        use self::Kind::*;
+        match self {
+            Stun => color::stunned,
+            Panic => color::panicking,
+            Slow => color::slowed,
+            Nausea => color::nauseous,
+        }
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        use self::Kind::*;
+        let s = match *self {
+            Stun => "Stunned",
+            Panic => "Panicking",
+            Slow => "Slowed",
+            Nausea => "Nauseous",
+        };
+        f.write_str(s)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub kind: Kind,
+    /// How many more turns will the effect last.
+    pub turns: i32,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub struct StatusEffects {
+    effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    /// Add the effect for the given number of `turns` following the
+    /// `Stacking` rules of its kind.
+    pub fn add(&mut self, kind: Kind, turns: i32) {
+        if turns <= 0 {
+            return;
+        }
+        match self.effects.iter_mut().find(|e| e.kind == kind) {
+            Some(effect) => {
+                effect.turns = match kind.stacking() {
+                    Stacking::Add { max } => cmp::min(effect.turns + turns, max),
+                    Stacking::Longest => cmp::max(effect.turns, turns),
+                }
+            }
+            None => {
+                let turns = match kind.stacking() {
+                    Stacking::Add { max } => cmp::min(turns, max),
+                    Stacking::Longest => turns,
+                };
+                self.effects.push(StatusEffect { kind, turns });
+            }
+        }
+    }
+
+    /// How many more turns will the effect of the `kind` last. Zero
+    /// if it's not active.
+    pub fn turns(&self, kind: Kind) -> i32 {
+        self.effects
+            .iter()
+            .find(|e| e.kind == kind)
+            .map_or(0, |e| e.turns)
+    }
+
+    pub fn has(&self, kind: Kind) -> bool {
+        self.turns(kind) > 0
+    }
+
+    /// The active effects in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = &StatusEffect> {
+        self.effects.iter()
+    }
+
+    /// Count down all the effects by one turn and return the ones
+    /// that wore off.
+    pub fn tick(&mut self) -> Vec<Kind> {
+        for effect in &mut self.effects {
+            effect.turns -= 1;
+        }
+        let expired = self
+            .effects
+            .iter()
+            .filter(|e| e.turns <= 0)
+            .map(|e| e.kind)
+            .collect();
+        self.effects.retain(|e| e.turns > 0);
+        expired
+    }
+
+    /// The action points left at the start of the turn.
+    pub fn action_points(&self, max_ap: i32) -> i32 {
+        if self.has(Kind::Stun) {
+            0
+        } else if self.has(Kind::Slow) {
+            cmp::min(max_ap, cmp::max(max_ap - 1, 1))
+        } else {
+            max_ap
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Kind, StatusEffects};
+
+    #[test]
+    fn test_stacking() {
+        let mut status = StatusEffects::default();
+        status.add(Kind::Stun, 4);
+        status.add(Kind::Stun, 3);
+        assert_eq!(status.turns(Kind::Stun), 7);
+        status.add(Kind::Stun, 8);
+        assert_eq!(status.turns(Kind::Stun), 10);
+
+        status.add(Kind::Slow, 3);
+        status.add(Kind::Slow, 2);
+        assert_eq!(status.turns(Kind::Slow), 3);
+        assert!(!status.has(Kind::Nausea));
+    }
+
+    #[test]
+    fn test_tick() {
+        let mut status = StatusEffects::default();
+        status.add(Kind::Slow, 1);
+        status.add(Kind::Stun, 2);
+        assert_eq!(status.action_points(2), 0);
+        assert_eq!(status.tick(), vec![Kind::Slow]);
+        assert_eq!(status.action_points(2), 0);
+        assert_eq!(status.tick(), vec![Kind::Stun]);
+        assert_eq!(status, StatusEffects::default());
+        assert_eq!(status.action_points(2), 2);
+
+        status.add(Kind::Slow, 1);
+        assert_eq!(status.action_points(2), 1);
+        assert_eq!(status.action_points(1), 1);
+    }
+}
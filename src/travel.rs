@@ -0,0 +1,320 @@
+//! Travelling to a map tile the player clicked on and auto-exploring.
+//!
+//! The route is fed into the command queue one step at a time. Every
+//! step is a regular `Command` so the replays record exactly what the
+//! player did and stay deterministic.
+
+use crate::{
+    blocker::Blocker,
+    formula,
+    pathfinding::Path,
+    player::{Mind, Player},
+    point::Point,
+    rect::Rectangle,
+    state::{Command, State},
+    world::World,
+};
+
+use std::{
+    collections::{HashMap, VecDeque},
+    mem::{self, Discriminant},
+    time::Duration,
+};
+
+/// The delay between two steps so the player can see where they're
+/// going.
+const STEP_DURATION_MS: u64 = 50;
+
+/// Why the travel has ended.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Stop {
+    Arrived,
+    /// There's nothing left to explore nearby.
+    Explored,
+    MonsterInView,
+    IrresistibleDose,
+    MindChanged,
+    /// The player didn't end up where they were supposed to (they
+    /// were stunned, panicked, pulled by a dose, etc.) or the next
+    /// step is blocked.
+    OffCourse,
+}
+
+#[derive(Debug)]
+enum Route {
+    /// The remaining steps to the destination.
+    Destination(VecDeque<Point>),
+    /// Keep walking towards the nearest unexplored tile.
+    Explore,
+}
+
+#[derive(Debug)]
+pub struct Travel {
+    route: Route,
+    /// Where the player should be before taking the next step.
+    position: Point,
+    mind: Discriminant<Mind>,
+    visible_monsters: usize,
+    since_last_step: Duration,
+}
+
+impl Travel {
+    /// Plan a route from the player's position to the
+    /// `destination`. Returns `None` if the destination hasn't been
+    /// explored yet or it can't be reached.
+    pub fn new(state: &mut State, destination: Point) -> Option<Travel> {
+        let explored = state
+            .world
+            .cell(destination)
+            .map_or(false, |cell| cell.explored);
+        if !explored {
+            return None;
+        }
+
+        let path = Path::find(
+            state.player.pos,
+            destination,
+            &state.world,
+            Blocker::WALL,
+            state.player.pos,
+            formula::PATHFINDING_TRAVEL_LIMIT,
+        );
+        if path.len() == 0 {
+            return None;
+        }
+
+        Some(Travel::with_route(
+            state,
+            Route::Destination(path.collect()),
+        ))
+    }
+
+    /// Start exploring the map until there's a reason to stop.
+    pub fn explore(state: &State) -> Travel {
+        Travel::with_route(state, Route::Explore)
+    }
+
+    fn with_route(state: &State, route: Route) -> Travel {
+        Travel {
+            route,
+            position: state.player.pos,
+            mind: mem::discriminant(&state.player.mind),
+            visible_monsters: visible_monsters(&state.world, &state.player),
+            // NOTE: take the first step straight away
+            since_last_step: Duration::from_millis(STEP_DURATION_MS),
+        }
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        self.since_last_step += dt;
+    }
+
+    /// Whether enough time has passed since the previous step.
+    pub fn ready(&self) -> bool {
+        self.since_last_step >= Duration::from_millis(STEP_DURATION_MS)
+    }
+
+    /// The command taking the player one step further or the reason
+    /// to stop travelling.
+    pub fn next_command(&mut self, state: &mut State) -> Result<Command, Stop> {
+        let player_pos = state.player.pos;
+
+        if player_pos != self.position {
+            return Err(Stop::OffCourse);
+        }
+
+        if mem::discriminant(&state.player.mind) != self.mind {
+            return Err(Stop::MindChanged);
+        }
+
+        // NOTE: only stop for the monsters that weren't visible
+        // before. Otherwise the player couldn't travel away from
+        // a monster they can already see.
+        let visible_monsters = visible_monsters(&state.world, &state.player);
+        if visible_monsters > self.visible_monsters {
+            return Err(Stop::MonsterInView);
+        }
+        self.visible_monsters = visible_monsters;
+
+        let will = state.player.will.to_int();
+        if let Some((dose_pos, dose)) = state.world.nearest_dose(player_pos, 5) {
+            let resist_radius = formula::player_resist_radius(dose.irresistible, will);
+            if player_pos.tile_distance(dose_pos) < resist_radius {
+                return Err(Stop::IrresistibleDose);
+            }
+        }
+
+        let (next, command) = match self.route {
+            Route::Destination(ref mut steps) => {
+                let next = steps.pop_front().ok_or(Stop::Arrived)?;
+                let command = Command::movement(next - player_pos).ok_or(Stop::OffCourse)?;
+                (next, command)
+            }
+            // NOTE: the `Explore` command finds the same step again
+            // when it's processed. We only need it here to check the
+            // player got there.
+            Route::Explore => (explore_step(&state.world, &state.player)?, Command::Explore),
+        };
+
+        if state.world.monster_on_pos(next).is_some() {
+            return Err(Stop::OffCourse);
+        }
+
+        self.position = next;
+        self.since_last_step = Duration::from_millis(0);
+        Ok(command)
+    }
+}
+
+/// The number of hostile monsters within the player's sight.
+fn visible_monsters(world: &World, player: &Player) -> usize {
+    let radius = formula::exploration_radius(player.mind);
+    let area = Rectangle::center(player.pos, Point::from_i32(radius));
+    world
+        .monsters(area)
+        .filter(|monster| {
+            monster.alive()
+                && monster.is_hostile()
+                && monster.position.distance(player.pos) < radius as f32
+        })
+        .count()
+}
+
+/// The first step towards the nearest unexplored tile.
+///
+/// The path never leads into the irresistible area of a dose the
+/// player knows about. There is no step while any hostile monster is
+/// in sight.
+pub fn explore_step(world: &World, player: &Player) -> Result<Point, Stop> {
+    if visible_monsters(world, player) > 0 {
+        return Err(Stop::MonsterInView);
+    }
+
+    let search_area = Rectangle::center(
+        player.pos,
+        Point::from_i32(formula::PATHFINDING_EXPLORE_RADIUS),
+    );
+    let will = player.will.to_int();
+    let mut irresistible_areas = vec![];
+    for pos in search_area.points() {
+        if let Some(cell) = world.cell(pos).filter(|cell| cell.explored) {
+            for item in cell.items.iter().filter(|item| item.is_dose()) {
+                let radius = formula::player_resist_radius(item.irresistible, will);
+                if radius > 0 {
+                    irresistible_areas.push((pos, radius));
+                }
+            }
+        }
+    }
+    let irresistible = |pos: Point| {
+        irresistible_areas
+            .iter()
+            .any(|&(dose_pos, radius)| pos.tile_distance(dose_pos) < radius)
+    };
+
+    // NOTE: breadth-first search through the explored tiles. The
+    // first unexplored one we reach is the nearest.
+    let mut came_from = HashMap::new();
+    let mut queue = VecDeque::new();
+    came_from.insert(player.pos, player.pos);
+    queue.push_back(player.pos);
+
+    while let Some(current) = queue.pop_front() {
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let next = current + (dx, dy);
+                if came_from.contains_key(&next)
+                    || !search_area.contains(next)
+                    || !world.walkable(next, Blocker::WALL, player.pos)
+                    || irresistible(next)
+                {
+                    continue;
+                }
+                came_from.insert(next, current);
+
+                let explored = world.cell(next).map_or(true, |cell| cell.explored);
+                if !explored {
+                    let mut step = next;
+                    while came_from[&step] != player.pos {
+                        step = came_from[&step];
+                    }
+                    return Ok(step);
+                }
+                queue.push_back(next);
+            }
+        }
+    }
+
+    Err(Stop::Explored)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Stop, Travel};
+    use crate::{blocker::Blocker, formula, state::State};
+
+    fn run(travel: &mut Travel, state: &mut State) -> Stop {
+        loop {
+            match travel.next_command(state) {
+                Ok(command) => state.run_command(command),
+                Err(stop) => break stop,
+            }
+        }
+    }
+
+    #[test]
+    fn test_travel_to_explored_tile() {
+        let mut state = State::new_test_game();
+        let start = state.player.pos;
+        state
+            .world
+            .explore(start, formula::exploration_radius(state.player.mind));
+        // NOTE: an irresistible dose nearby would pull the player off
+        // the route
+        for x in -5..=5 {
+            for y in -5..=5 {
+                if let Some(cell) = state.world.cell_mut(start + (x, y)) {
+                    cell.items.clear();
+                }
+            }
+        }
+
+        let destination = (-3..=3)
+            .flat_map(|x| (-3..=3).map(move |y| start + (x, y)))
+            .filter(|&pos| pos.tile_distance(start) == 3)
+            .find(|&pos| state.world.walkable(pos, Blocker::WALL, start))
+            .unwrap();
+        let mut travel = Travel::new(&mut state, destination).unwrap();
+
+        assert_eq!(run(&mut travel, &mut state), Stop::Arrived);
+        assert_eq!(state.player.pos, destination);
+
+        // NOTE: we can't travel into the unknown
+        let unexplored = start + (100, 100);
+        assert!(Travel::new(&mut state, unexplored).is_none());
+    }
+
+    #[test]
+    fn test_explore() {
+        let mut state = State::new_test_game();
+        let start = state.player.pos;
+        state
+            .world
+            .explore(start, formula::exploration_radius(state.player.mind));
+
+        let mut travel = Travel::explore(&state);
+        let stop = loop {
+            let stop = run(&mut travel, &mut state);
+            // NOTE: the mind state changes as the time passes. Carry on.
+            if stop == Stop::MindChanged && state.turn < 30 {
+                travel = Travel::explore(&state);
+            } else {
+                break stop;
+            }
+        };
+
+        assert_ne!(stop, Stop::OffCourse);
+        assert!(state.turn > 0);
+        assert!(state.player.pos.tile_distance(start) > 0);
+    }
+}
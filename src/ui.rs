@@ -148,3 +148,81 @@ impl Button {
         }
     }
 }
+
+/// Lay out the buttons next to each other, centered horizontally
+/// within `rect`.
+pub fn button_row<T>(
+    rect: Rectangle,
+    y: i32,
+    labels: Vec<(T, String)>,
+    metrics: &dyn TextMetrics,
+) -> Vec<(T, Button)> {
+    let spacing = 2;
+    let widths = labels
+        .iter()
+        .map(|(_, label)| metrics.get_text_width(label, TextOptions::align_left()))
+        .collect::<Vec<_>>();
+    let total_width = widths.iter().sum::<i32>() + spacing * (widths.len() as i32 - 1).max(0);
+    let mut x = (rect.width() - total_width) / 2;
+    labels
+        .into_iter()
+        .zip(widths)
+        .map(|((value, label), width)| {
+            let button = Button::new(rect.top_left() + (x, y), &label);
+            x += width + spacing;
+            (value, button)
+        })
+        .collect()
+}
+
+/// Width of the panel drawn over the map by `render_side_panel`.
+const SIDE_PANEL_WIDTH: i32 = 20;
+
+/// Draw the `lines` in a panel over the map. The panel goes on the
+/// other side of the map than the `cursor` (in screen coordinates) so
+/// it doesn't cover it.
+pub fn render_side_panel(
+    lines: &[String],
+    map_size: Point,
+    cursor: Point,
+    metrics: &dyn TextMetrics,
+    display: &mut Display,
+) {
+    let text_flow = lines
+        .iter()
+        .map(|line| {
+            if line.is_empty() {
+                Text::Empty
+            } else {
+                Text::Paragraph(line)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let panel_x = if cursor.x < map_size.x / 2 {
+        map_size.x - SIDE_PANEL_WIDTH - 1
+    } else {
+        1
+    };
+    let padding = Point::new(1, 1);
+    let text_rect = Rectangle::from_point_and_size(
+        Point::new(panel_x, 1) + padding,
+        Point::new(SIDE_PANEL_WIDTH, map_size.y - 2) - (padding * 2),
+    );
+    // NOTE: the text flow rect ends on the line below the text
+    let text_height = text_flow_rect(&text_flow, text_rect, metrics).height() - 1;
+    let window_rect = Rectangle::from_point_and_size(
+        Point::new(panel_x, 1),
+        Point::new(SIDE_PANEL_WIDTH, text_height + padding.y * 2),
+    );
+
+    display.draw_rectangle(window_rect, color::window_edge);
+    display.draw_rectangle(
+        Rectangle::new(
+            window_rect.top_left() + (1, 1),
+            window_rect.bottom_right() - (1, 1),
+        ),
+        color::window_background,
+    );
+    render_text_flow(&text_flow, text_rect, metrics, display);
+}
@@ -76,3 +76,31 @@ pub fn random_seed() -> u32 {
     let random_float = unsafe { crate::engine::wasm::random() };
     (random_float * std::u32::MAX as f32) as u32
 }
+
+/// A hasher whose output doesn't change between program runs (unlike
+/// `DefaultHasher`). It's the 64-bit FNV-1a.
+///
+/// We use it to compare the game state of a replay with the state
+/// recorded in it. Only feed it explicit bytes: the derived `Hash`
+/// implementations depend on the platform and the Rust version.
+#[derive(Clone, Copy, Debug)]
+pub struct StableHasher(u64);
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl std::hash::Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
@@ -14,6 +14,14 @@ pub enum Window {
         message: String,
         ttl: Option<Duration>,
     },
+    // NOTE: the window stack is part of the saved game. Add new
+    // windows at the end so the older saves can still be loaded.
+    LoadGame,
+    Controls,
+    Look,
+    MessageLog,
+    Drop,
+    Throw,
 }
 
 pub fn message_box<S: Into<String>>(message: S) -> Window {
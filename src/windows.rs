@@ -1,11 +1,17 @@
 use serde::{Deserialize, Serialize};
 
 pub mod call_to_action;
+pub mod controls;
+pub mod drop_item;
 pub mod endgame;
 pub mod help;
+pub mod load_game;
+pub mod look;
 pub mod main_menu;
+pub mod message_log;
 pub mod settings;
 pub mod sidebar;
+pub mod throw;
 
 /// A stack of windows.
 ///
@@ -0,0 +1,234 @@
+use crate::{
+    color,
+    engine::{Display, Settings, TextMetrics, TextOptions},
+    keymap::{self, Preset},
+    point::Point,
+    rect::Rectangle,
+    state::State,
+    ui::{self, Button},
+};
+
+pub enum Action {
+    /// Wait for a key to bind to the action at this index.
+    Capture(usize),
+    /// Remove all keys bound to the action at this index.
+    Clear(usize),
+    Preset(Preset),
+    Back,
+}
+
+struct Layout {
+    window_rect: Rectangle,
+    rect: Rectangle,
+    command_rects: Vec<Rectangle>,
+    preset_options: Vec<(Preset, Button)>,
+    action_under_mouse: Option<Action>,
+    rect_under_mouse: Option<Rectangle>,
+    clear_button: Button,
+    reset_button: Button,
+    back_button: Button,
+}
+
+pub struct Window;
+
+impl Window {
+    fn layout(&self, state: &State, settings: &Settings, metrics: &dyn TextMetrics) -> Layout {
+        let screen_padding = Point::from_i32(2);
+        let window_rect = Rectangle::from_point_and_size(
+            screen_padding,
+            state.display_size - (screen_padding * 2),
+        );
+
+        let rect = Rectangle::new(
+            window_rect.top_left() + (2, 0),
+            window_rect.bottom_right() - (2, 1),
+        );
+
+        let mut action_under_mouse = None;
+        let mut rect_under_mouse = None;
+
+        let preset_options = ui::button_row(
+            rect,
+            4,
+            Preset::all()
+                .map(|preset| (preset, preset.name().to_string()))
+                .collect(),
+            metrics,
+        );
+
+        for &(preset, ref button) in &preset_options {
+            let button_rect = metrics.button_rect(button);
+            if button_rect.contains(state.mouse.tile_pos) {
+                action_under_mouse = Some(Action::Preset(preset));
+                rect_under_mouse = Some(button_rect);
+            }
+        }
+
+        let command_rects = (0..keymap::actions().len())
+            .map(|index| {
+                let command_rect = Rectangle::from_point_and_size(
+                    rect.top_left() + (0, 6 + index as i32),
+                    Point::new(rect.width(), 1),
+                );
+                if command_rect.contains(state.mouse.tile_pos) {
+                    action_under_mouse = Some(Action::Capture(index));
+                    rect_under_mouse = Some(command_rect);
+                }
+                command_rect
+            })
+            .collect();
+
+        let clear_button = Button::new(rect.bottom_left(), "[C]lear");
+        let reset_button =
+            Button::new(rect.bottom_left(), "[R]eset to preset").align_center(rect.width());
+        let back_button = Button::new(rect.bottom_right(), "[Esc] Back").align_right();
+
+        let button_rect = metrics.button_rect(&clear_button);
+        if button_rect.contains(state.mouse.tile_pos) {
+            action_under_mouse = Some(Action::Clear(state.selected_command));
+            rect_under_mouse = Some(button_rect);
+        }
+
+        let button_rect = metrics.button_rect(&reset_button);
+        if button_rect.contains(state.mouse.tile_pos) {
+            action_under_mouse = Some(Action::Preset(settings.keymap.preset));
+            rect_under_mouse = Some(button_rect);
+        }
+
+        let button_rect = metrics.button_rect(&back_button);
+        if button_rect.contains(state.mouse.tile_pos) {
+            action_under_mouse = Some(Action::Back);
+            rect_under_mouse = Some(button_rect);
+        }
+
+        Layout {
+            window_rect,
+            rect,
+            command_rects,
+            preset_options,
+            action_under_mouse,
+            rect_under_mouse,
+            clear_button,
+            reset_button,
+            back_button,
+        }
+    }
+
+    pub fn render(
+        &self,
+        state: &State,
+        settings: &Settings,
+        metrics: &dyn TextMetrics,
+        display: &mut Display,
+    ) {
+        let layout = self.layout(state, settings, metrics);
+        let rect = layout.rect;
+        let keymap = &settings.keymap;
+
+        display.draw_rectangle(layout.window_rect, color::window_edge);
+
+        display.draw_rectangle(
+            Rectangle::new(
+                layout.window_rect.top_left() + (1, 1),
+                layout.window_rect.bottom_right() - (1, 1),
+            ),
+            color::window_background,
+        );
+
+        display.draw_text(
+            rect.top_left() + (0, 1),
+            "Controls",
+            color::gui_text,
+            TextOptions::align_center(rect.width()),
+        );
+        display.draw_text(
+            rect.top_left() + (0, 3),
+            "Preset ([P] to switch):",
+            color::gui_text,
+            TextOptions::align_center(rect.width()),
+        );
+
+        if let Some(rect) = layout.rect_under_mouse {
+            display.draw_rectangle(rect, color::menu_highlight);
+        }
+
+        for (preset, button) in &layout.preset_options {
+            // Highlight the active preset
+            if *preset == keymap.preset {
+                let rect = metrics.button_rect(button);
+                display.draw_rectangle(rect, color::dim_background);
+            }
+            display.draw_button(button)
+        }
+
+        let keys_x = 18;
+        for (index, (action, &command_rect)) in keymap::actions()
+            .iter()
+            .zip(&layout.command_rects)
+            .enumerate()
+        {
+            if index == state.selected_command && layout.rect_under_mouse != Some(command_rect) {
+                display.draw_rectangle(command_rect, color::dim_background);
+            }
+            display.draw_text(
+                command_rect.top_left(),
+                keymap::action_description(action),
+                color::gui_text,
+                TextOptions::align_left(),
+            );
+
+            // NOTE: draw the keys one by one so we can show the
+            // conflicting ones in a different colour.
+            let mut pos = command_rect.top_left() + (keys_x, 0);
+            for (key_index, &key) in keymap.keys(action).iter().enumerate() {
+                let text = if key_index == 0 {
+                    key.to_string()
+                } else {
+                    format!(", {}", key)
+                };
+                let color = if keymap.is_conflicting(key, &state.world.item_definitions) {
+                    color::anxiety
+                } else {
+                    color::gui_text
+                };
+                display.draw_text(pos, &text, color, TextOptions::align_left());
+                pos.x += metrics.get_text_width(&text, TextOptions::align_left());
+            }
+        }
+
+        let selected_action = &keymap::actions()[state.selected_command];
+        let hint = if state.capturing_key {
+            format!(
+                "Press a key for \"{}\" ([Esc] to cancel).",
+                keymap::action_description(selected_action)
+            )
+        } else if !keymap.conflicts(&state.world.item_definitions).is_empty() {
+            "The red keys are bound to more than one action or item or used by the game.".into()
+        } else {
+            "[Up]/[Down] to select, [Enter] or click to add a key.".into()
+        };
+        display.draw_text(
+            rect.top_left() + (0, 7 + layout.command_rects.len() as i32),
+            &hint,
+            color::gui_text,
+            TextOptions {
+                wrap: true,
+                width: rect.width(),
+                ..Default::default()
+            },
+        );
+
+        display.draw_button(&layout.clear_button);
+        display.draw_button(&layout.reset_button);
+        display.draw_button(&layout.back_button);
+    }
+
+    pub fn hovered(
+        &self,
+        state: &State,
+        settings: &Settings,
+        metrics: &dyn TextMetrics,
+    ) -> Option<Action> {
+        self.layout(state, settings, metrics).action_under_mouse
+    }
+}
@@ -0,0 +1,22 @@
+use crate::{
+    engine::{Display, TextMetrics},
+    state::State,
+    ui,
+};
+
+pub struct Window;
+
+impl Window {
+    pub fn render(&self, state: &State, metrics: &dyn TextMetrics, display: &mut Display) {
+        let screen_left_top_corner = state.screen_position_in_world - (state.map_size / 2);
+        let player = state.player.pos - screen_left_top_corner;
+        let lines = vec![
+            "Drop which item?".to_string(),
+            String::new(),
+            "Press its key or click on it in the sidebar.".into(),
+            String::new(),
+            "[Esc] Cancel".into(),
+        ];
+        ui::render_side_panel(&lines, state.map_size, player, metrics, display);
+    }
+}
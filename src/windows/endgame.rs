@@ -105,24 +105,24 @@ impl Window {
             "You lost:"
         };
 
-        let perpetrator = state.player.perpetrator.as_ref();
+        let perpetrator = state
+            .player
+            .perpetrator
+            .as_ref()
+            .map(|monster| state.world.monster_definition(&monster.id));
 
         let endgame_description = match (cause_of_death, perpetrator) {
             (Some(Exhausted), None) => "Exhausted".into(),
-            (Some(Exhausted), Some(monster)) => format!(
-                "Exhausted because of {} ({})",
-                monster.name(),
-                monster.glyph()
-            ),
+            (Some(Exhausted), Some(monster)) => {
+                format!("Exhausted because of {} ({})", monster.name, monster.glyph)
+            }
             (Some(Overdosed), _) => "Overdosed".into(),
-            (Some(LostWill), Some(monster)) => format!(
-                "Lost all Will due to {} ({})",
-                monster.name(),
-                monster.glyph()
-            ),
+            (Some(LostWill), Some(monster)) => {
+                format!("Lost all Will due to {} ({})", monster.name, monster.glyph)
+            }
             (Some(LostWill), None) => unreachable!(),
             (Some(Killed), Some(monster)) => {
-                format!("Defeated by {} ({})", monster.name(), monster.glyph())
+                format!("Defeated by {} ({})", monster.name, monster.glyph)
             }
             (Some(Killed), None) => unreachable!(),
             (None, _) => "".into(), // Victory
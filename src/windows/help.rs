@@ -1,9 +1,12 @@
 use crate::{
     color,
     engine::{Display, TextMetrics},
+    formula,
+    keymap::{self, Keymap, Preset},
+    keys::Key,
     point::Point,
     rect::Rectangle,
-    state::State,
+    state::{Command, State},
     ui::{self, Button},
 };
 
@@ -19,9 +22,9 @@ pub enum Action {
 #[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Page {
     DoseResponse,
-    NumpadControls,
-    ArrowControls,
-    ViKeys,
+    MovementControls,
+    ItemControls,
+    KeymapPresets,
     HowToPlay,
     Legend,
     About,
@@ -32,10 +35,10 @@ impl Page {
         use self::Page::*;
         match self {
             DoseResponse => None,
-            NumpadControls => Some(DoseResponse),
-            ArrowControls => Some(NumpadControls),
-            ViKeys => Some(ArrowControls),
-            HowToPlay => Some(ViKeys),
+            MovementControls => Some(DoseResponse),
+            ItemControls => Some(MovementControls),
+            KeymapPresets => Some(ItemControls),
+            HowToPlay => Some(KeymapPresets),
             Legend => Some(HowToPlay),
             About => Some(Legend),
         }
@@ -44,10 +47,10 @@ impl Page {
     pub fn next(self) -> Option<Self> {
         use self::Page::*;
         match self {
-            DoseResponse => Some(NumpadControls),
-            NumpadControls => Some(ArrowControls),
-            ArrowControls => Some(ViKeys),
-            ViKeys => Some(HowToPlay),
+            DoseResponse => Some(MovementControls),
+            MovementControls => Some(ItemControls),
+            ItemControls => Some(KeymapPresets),
+            KeymapPresets => Some(HowToPlay),
             HowToPlay => Some(Legend),
             Legend => Some(About),
             About => None,
@@ -60,9 +63,9 @@ impl FmtDisplay for Page {
         use self::Page::*;
         let s = match *self {
             DoseResponse => "Dose Response",
-            NumpadControls => "Controls: numpad",
-            ArrowControls => "Controls: arrow keys",
-            ViKeys => "Controls: Vi keys",
+            MovementControls => "Controls: movement",
+            ItemControls => "Controls: items",
+            KeymapPresets => "Controls: presets",
             HowToPlay => "How to play",
             Legend => "Legend",
             About => "About Dose Response",
@@ -130,7 +133,13 @@ impl Window {
         }
     }
 
-    pub fn render(&self, state: &State, metrics: &dyn TextMetrics, display: &mut Display) {
+    pub fn render(
+        &self,
+        state: &State,
+        keymap: &Keymap,
+        metrics: &dyn TextMetrics,
+        display: &mut Display,
+    ) {
         use crate::ui::Text::*;
 
         let layout = self.layout(state, metrics);
@@ -156,6 +165,68 @@ impl Window {
         let homepage = &format!("Homepage: {}", crate::metadata::HOMEPAGE);
         let git_msg = &format!("Git commit: {}", crate::metadata::GIT_HASH);
 
+        // NOTE: the controls pages show the keys of the active keymap
+        // rather than a fixed set so they match any rebinding.
+        let action_keys = |action: &keymap::Action| {
+            format!(
+                "{}: {}",
+                keymap::action_description(action),
+                key_list(keymap.keys(action))
+            )
+        };
+        let command_keys = |commands: &[Command]| {
+            commands
+                .iter()
+                .map(|command| action_keys(&keymap::Action::Command(command.clone())))
+                .collect::<Vec<_>>()
+        };
+        let movement_keys = command_keys(&[
+            Command::N,
+            Command::S,
+            Command::W,
+            Command::E,
+            Command::NW,
+            Command::NE,
+            Command::SW,
+            Command::SE,
+        ]);
+        let explore_keys = command_keys(&[Command::Explore]);
+        let item_keys = state
+            .world
+            .item_definitions
+            .iter()
+            .map(|definition| format!("Use {}: {}", definition.name, key_list(&definition.keys)))
+            .collect::<Vec<_>>();
+        let window_keys = [
+            keymap::Action::Look,
+            keymap::Action::Drop,
+            keymap::Action::Throw,
+            keymap::Action::MessageLog,
+        ]
+        .iter()
+        .map(action_keys)
+        .collect::<Vec<_>>();
+        let throw_help = format!(
+            "You can throw a dose up to {} tiles away. It explodes where it lands.",
+            formula::THROW_RANGE
+        );
+        let presets = Preset::all()
+            .map(|preset| {
+                let description = match preset {
+                    Preset::Default => "the numpad, arrow keys and Vi keys all at once.",
+                    Preset::Numpad => "the numpad only. Imagine your @ is where [5] is and pick a direction.",
+                    Preset::Vi => "the \"Vi keys\": [H] [J] [K] [L] and [Y] [U] [B] [N] for the diagonals.",
+                    Preset::Arrows => "the arrow keys. [Shift] with [Left] or [Right] moves diagonally up, [Ctrl] diagonally down.",
+                };
+                let active = if preset == keymap.preset {
+                    " (active)"
+                } else {
+                    ""
+                };
+                format!("{}{}: {}", preset.name(), active, description)
+            })
+            .collect::<Vec<_>>();
+
         let mut lines = vec![];
         lines.push(Centered(&header));
         lines.push(EmptySpace(1));
@@ -173,53 +244,53 @@ impl Window {
                 ));
             }
 
-            Page::NumpadControls => {
+            Page::MovementControls => {
                 lines.push(Paragraph("You control the @ character. It moves just like the king in Chess: one step in any direction. That means up, down, left, right, but also diagonally."));
-                lines.push(Empty);
-                lines.push(Paragraph("You can use the numpad. Imagine your @ is in the middle (where [5] is) and you just pick a direction."));
                 lines.push(EmptySpace(1));
 
-                lines.push(SquareTiles(r"7 8 9"));
-                lines.push(SquareTiles(r" \|/ "));
-                lines.push(SquareTiles(r"4-@-6"));
-                lines.push(SquareTiles(r" /|\ "));
-                lines.push(SquareTiles(r"1 2 3"));
+                lines.push(SquareTiles(r"\|/"));
+                lines.push(SquareTiles(r"-@-"));
+                lines.push(SquareTiles(r"/|\"));
 
                 lines.push(EmptySpace(1));
-                lines.push(Paragraph("Using items: you can use an item you're carrying (food and later on, doses) by clicking on it in the sidebar or pressing its number on the keyboard (not numpad -- that's for movement)."));
+                for line in &movement_keys {
+                    lines.push(Paragraph(line));
+                }
+                lines.push(Empty);
+                lines.push(Paragraph("Click on a tile you've already seen to travel there. The auto-explore walks to the nearest unexplored place. Both stop as soon as a monster shows up."));
+                for line in &explore_keys {
+                    lines.push(Paragraph(line));
+                }
             }
 
-            Page::ArrowControls => {
-                lines.push(Paragraph("You control the @ character. It moves just like the king in Chess: one step in any direction. That means up, down, left, right, but also diagonally."));
+            Page::ItemControls => {
+                lines.push(Paragraph("Using items: you can use an item you're carrying (food and later on, doses) by clicking on it in the sidebar or pressing its key."));
                 lines.push(Empty);
-                lines.push(Paragraph("If you don't have a numpad, you can use the arrow keys. You will need [Shift] and [Ctrl] for diagonal movement. [Shift] means up and [Ctrl] means down. You combine them with the [Left] and [Right] keys."));
-
-                lines.push(EmptySpace(1));
-
-                lines.push(SquareTiles(r"Shift+Left  Up  Shift+Right"));
-                lines.push(SquareTiles(r"         \  |  /           "));
-                lines.push(SquareTiles(r"       Left-@-Right        "));
-                lines.push(SquareTiles(r"         /  |  \           "));
-                lines.push(SquareTiles(r"Ctrl+Left  Down Ctrl+Right "));
-
-                lines.push(EmptySpace(1));
-                lines.push(Paragraph("Using items: you can use an item you're carrying (food and later on, doses) by clicking on it in the sidebar or pressing its number on the keyboard (not numpad -- that's for movement)."));
+                for line in &item_keys {
+                    lines.push(Paragraph(line));
+                }
+                lines.push(Empty);
+                lines.push(Paragraph("Main menu: [Esc]"));
+                lines.push(Paragraph("This help: [?]"));
+                for line in &window_keys {
+                    lines.push(Paragraph(line));
+                }
+                lines.push(Empty);
+                lines.push(Paragraph(
+                    "Move the look and throw cursor with the mouse or the movement keys.",
+                ));
+                lines.push(Paragraph(&throw_help));
+                lines.push(Empty);
+                lines.push(Paragraph("You can change the movement, look, drop, throw and message log keys in the Settings under [C]ontrols. The item keys are set in the `items.json` data file."));
             }
 
-            Page::ViKeys => {
-                lines.push(Paragraph("You control the @ character. It moves just like the king in Chess: one step in any direction. That means up, down, left, right, but also diagonally."));
+            Page::KeymapPresets => {
+                lines.push(Paragraph("The keymap preset decides which keys move your @. Pick the one that fits your keyboard in the Settings under [C]ontrols. You can then rebind any single command too."));
                 lines.push(Empty);
-                lines.push(Paragraph("You can also move using the \"Vi keys\". Those map to the letters on your keyboard. This makes more sense if you've ever used the Vi text editor."));
-                lines.push(EmptySpace(1));
-
-                lines.push(SquareTiles(r"y k u"));
-                lines.push(SquareTiles(r" \|/ "));
-                lines.push(SquareTiles(r"h-@-l"));
-                lines.push(SquareTiles(r" /|\ "));
-                lines.push(SquareTiles(r"b j n"));
-
-                lines.push(EmptySpace(1));
-                lines.push(Paragraph("Using items: you can use an item you're carrying (food and later on, doses) by clicking on it in the sidebar or pressing its number on the keyboard (not numpad -- that's for movement)."));
+                for line in &presets {
+                    lines.push(Paragraph(line));
+                    lines.push(Empty);
+                }
             }
 
             Page::HowToPlay => {
@@ -313,3 +384,16 @@ impl Window {
         self.layout(state, metrics).action_under_mouse
     }
 }
+
+/// The `keys` in the format used by the help pages, e.g.
+/// `[NumPad8] [Up] [K]`.
+fn key_list(keys: &[Key]) -> String {
+    if keys.is_empty() {
+        "not bound".into()
+    } else {
+        keys.iter()
+            .map(|key| format!("[{}]", key))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{
+    color,
+    engine::{Display, TextMetrics, TextOptions},
+    point::Point,
+    rect::Rectangle,
+    savegame,
+    state::State,
+    ui::Button,
+};
+
+use std::cmp;
+
+pub enum Action {
+    Load(usize),
+    Delete(usize),
+    Back,
+}
+
+/// Every saved game takes this many lines in the list.
+const SLOT_HEIGHT: i32 = 3;
+
+struct Layout {
+    window_rect: Rectangle,
+    rect: Rectangle,
+    /// Index of the first visible slot and the rectangle of every
+    /// visible slot.
+    first_slot: usize,
+    slot_rects: Vec<Rectangle>,
+    action_under_mouse: Option<Action>,
+    rect_under_mouse: Option<Rectangle>,
+    delete_button: Button,
+    back_button: Button,
+}
+
+pub struct Window;
+
+impl Window {
+    fn layout(&self, state: &State, metrics: &dyn TextMetrics) -> Layout {
+        let screen_padding = Point::from_i32(2);
+        let window_rect = Rectangle::from_point_and_size(
+            screen_padding,
+            state.display_size - (screen_padding * 2),
+        );
+
+        let rect = Rectangle::new(
+            window_rect.top_left() + (2, 0),
+            window_rect.bottom_right() - (2, 1),
+        );
+
+        let mut action_under_mouse = None;
+        let mut rect_under_mouse = None;
+
+        // NOTE: keep the selected slot on the screen
+        let visible_slots = cmp::max(1, (rect.height() - 6) / SLOT_HEIGHT) as usize;
+        let first_slot = (state.selected_save_slot + 1).saturating_sub(visible_slots);
+
+        let slot_rects = (first_slot..state.save_slots.len())
+            .take(visible_slots)
+            .enumerate()
+            .map(|(row, index)| {
+                let slot_rect = Rectangle::from_point_and_size(
+                    rect.top_left() + (0, 3 + row as i32 * SLOT_HEIGHT),
+                    Point::new(rect.width(), SLOT_HEIGHT - 1),
+                );
+                if slot_rect.contains(state.mouse.tile_pos) {
+                    action_under_mouse = Some(Action::Load(index));
+                    rect_under_mouse = Some(slot_rect);
+                }
+                slot_rect
+            })
+            .collect();
+
+        let delete_button = Button::new(rect.bottom_left(), "[D]elete");
+        let back_button = Button::new(rect.bottom_right(), "[Esc] Back").align_right();
+
+        let button_rect = metrics.button_rect(&delete_button);
+        if button_rect.contains(state.mouse.tile_pos) && !state.save_slots.is_empty() {
+            action_under_mouse = Some(Action::Delete(state.selected_save_slot));
+            rect_under_mouse = Some(button_rect);
+        }
+
+        let button_rect = metrics.button_rect(&back_button);
+        if button_rect.contains(state.mouse.tile_pos) {
+            action_under_mouse = Some(Action::Back);
+            rect_under_mouse = Some(button_rect);
+        }
+
+        Layout {
+            window_rect,
+            rect,
+            first_slot,
+            slot_rects,
+            action_under_mouse,
+            rect_under_mouse,
+            delete_button,
+            back_button,
+        }
+    }
+
+    pub fn render(&self, state: &State, metrics: &dyn TextMetrics, display: &mut Display) {
+        let layout = self.layout(state, metrics);
+        let rect = layout.rect;
+
+        display.draw_rectangle(layout.window_rect, color::window_edge);
+
+        display.draw_rectangle(
+            Rectangle::new(
+                layout.window_rect.top_left() + (1, 1),
+                layout.window_rect.bottom_right() - (1, 1),
+            ),
+            color::window_background,
+        );
+
+        display.draw_text(
+            rect.top_left() + (0, 1),
+            "Load game",
+            color::gui_text,
+            TextOptions::align_center(rect.width()),
+        );
+
+        if state.save_slots.is_empty() {
+            display.draw_text(
+                rect.top_left() + (0, 3),
+                "There are no saved games.",
+                color::gui_text,
+                TextOptions::align_center(rect.width()),
+            );
+        }
+
+        for (row, &slot_rect) in layout.slot_rects.iter().enumerate() {
+            let index = layout.first_slot + row;
+            let slot = &state.save_slots[index];
+            let metadata = &slot.metadata;
+
+            if index == state.selected_save_slot {
+                display.draw_rectangle(slot_rect, color::dim_background);
+            }
+            if layout.rect_under_mouse == Some(slot_rect) {
+                display.draw_rectangle(slot_rect, color::menu_highlight);
+            }
+
+            let title = if index < 9 {
+                format!("[{}] {}", index + 1, slot.name)
+            } else {
+                slot.name.clone()
+            };
+            display.draw_text(
+                slot_rect.top_left(),
+                &title,
+                color::gui_text,
+                TextOptions::align_left(),
+            );
+            display.draw_text(
+                slot_rect.top_left() + (rect.width(), 0),
+                &savegame::saved_ago(metadata),
+                color::gui_text,
+                TextOptions::align_right(),
+            );
+
+            let played_secs = metadata.time_played.as_secs();
+            let details = format!(
+                "Turn {}, {}, Will {}, played {}:{:02}, seed {}",
+                metadata.turn,
+                metadata.mind,
+                metadata.will,
+                played_secs / 60,
+                played_secs % 60,
+                metadata.seed,
+            );
+            display.draw_text(
+                slot_rect.top_left() + (1, 1),
+                &details,
+                color::gui_text,
+                TextOptions::align_left(),
+            );
+        }
+
+        if let Some(rect) = layout.rect_under_mouse {
+            if !layout.slot_rects.contains(&rect) {
+                display.draw_rectangle(rect, color::menu_highlight);
+            }
+        }
+
+        if !state.save_slots.is_empty() {
+            display.draw_button(&layout.delete_button);
+        }
+        display.draw_button(&layout.back_button);
+    }
+
+    pub fn hovered(&self, state: &State, metrics: &dyn TextMetrics) -> Option<Action> {
+        self.layout(state, metrics).action_under_mouse
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{
+    color,
+    engine::{Display, TextMetrics},
+    formula,
+    player::Bonus,
+    point::Point,
+    rect::Rectangle,
+    state::State,
+    ui,
+};
+
+pub struct Window;
+
+impl Window {
+    /// Describe everything the player knows about the tile under the
+    /// cursor.
+    fn lines(&self, state: &State) -> Vec<String> {
+        let pos = state.cursor;
+        let player = &state.player;
+        let mut lines = vec![];
+
+        let cell = match state.world.cell(pos) {
+            Some(cell) if cell.explored || state.uncovered_map => cell,
+            _ => {
+                lines.push("You haven't seen this place yet.".into());
+                return lines;
+            }
+        };
+
+        // NOTE: this follows what `render::render_game` shows
+        let bonus = if cfg!(feature = "cheating") && state.cheating {
+            Bonus::UncoverMap
+        } else {
+            player.bonus
+        };
+        let radius = formula::exploration_radius(player.mind);
+        let in_fov = player.pos.distance(pos) < radius as f32;
+        let reveal_all = cell.always_visible
+            || bonus == Bonus::UncoverMap
+            || bonus == Bonus::SeeMonstersAndItems
+            || state.uncovered_map;
+
+        lines.push(cell.tile.name().into());
+        lines.push(cell.tile.description().into());
+
+        if pos == player.pos {
+            lines.push(String::new());
+            lines.push(format!("You ({})", player.mind));
+        }
+
+        let monster = state
+            .world
+            .monsters(Rectangle::center(pos, Point::zero()))
+            .next();
+        if let Some(monster) = monster {
+            if in_fov || reveal_all || monster.accompanying_player {
+                let definition = state.world.monster_definition(&monster.id);
+                lines.push(String::new());
+                lines.push(definition.name.clone());
+                lines.push(format!("State: {}", monster.ai_state));
+                if monster.is_hostile() {
+                    lines.push(format!(
+                        "Attack: {} ({})",
+                        definition.danger(),
+                        definition.attack
+                    ));
+                }
+                if let Some(bonus) = monster.companion_bonus {
+                    lines.push(format!("Companion bonus: {}", bonus));
+                }
+            }
+        }
+
+        for item in &cell.items {
+            let definition = state.world.item_definition(&item.id);
+            lines.push(String::new());
+            lines.push(definition.name.clone());
+            lines.push(definition.item_description(item));
+            if item.is_dose() {
+                let resist_radius =
+                    formula::player_resist_radius(item.irresistible, player.will.to_int());
+                if resist_radius > 0 {
+                    lines.push(format!("Irresistible within {} tiles.", resist_radius));
+                } else {
+                    lines.push("You can pick it up.".into());
+                }
+            }
+        }
+
+        lines
+    }
+
+    pub fn render(&self, state: &State, metrics: &dyn TextMetrics, display: &mut Display) {
+        let screen_left_top_corner = state.screen_position_in_world - (state.map_size / 2);
+        let cursor = state.cursor - screen_left_top_corner;
+        display.set_background(cursor, color::menu_highlight);
+
+        let mut lines = self.lines(state);
+        lines.push(String::new());
+        lines.push("[Esc] Back".into());
+        ui::render_side_panel(&lines, state.map_size, cursor, metrics, display);
+    }
+}
@@ -8,6 +8,7 @@ use crate::ui::{self, Text};
 #[derive(Debug)]
 pub enum MenuItem {
     Resume,
+    Continue,
     NewGame,
     Help,
     Settings,
@@ -21,6 +22,7 @@ impl MenuItem {
         use self::MenuItem::*;
         match self {
             Resume => "[R]esume",
+            Continue => "[C]ontinue",
             NewGame => "[N]ew Game",
             Help => "[H]elp",
             Settings => "S[e]ttings",
@@ -73,6 +75,13 @@ impl Window {
             options.push(MenuItem::Resume);
         }
 
+        // NOTE: offer to continue when there's no game to resume.
+        // Just like with `Load`, we only look for the autosave once
+        // the option is selected.
+        if state.first_game_already_generated || state.game_ended {
+            options.push(MenuItem::Continue);
+        }
+
         options.push(MenuItem::NewGame);
 
         // NOTE: we won't hiding this option, because it would require
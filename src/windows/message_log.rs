@@ -0,0 +1,160 @@
+use crate::{
+    color,
+    engine::{Display, TextMetrics, TextOptions},
+    point::Point,
+    rect::Rectangle,
+    state::State,
+    ui::Button,
+};
+
+pub enum Action {
+    Older,
+    Newer,
+    Back,
+}
+
+struct Layout {
+    window_rect: Rectangle,
+    rect: Rectangle,
+    older_button: Button,
+    newer_button: Button,
+    back_button: Button,
+    action_under_mouse: Option<Action>,
+    rect_under_mouse: Option<Rectangle>,
+}
+
+pub struct Window;
+
+impl Window {
+    fn layout(&self, state: &State, metrics: &dyn TextMetrics) -> Layout {
+        let screen_padding = Point::from_i32(2);
+        let window_rect = Rectangle::from_point_and_size(
+            screen_padding,
+            state.display_size - (screen_padding * 2),
+        );
+
+        let rect = Rectangle::new(
+            window_rect.top_left() + (2, 0),
+            window_rect.bottom_right() - (2, 1),
+        );
+
+        let mut action_under_mouse = None;
+        let mut rect_under_mouse = None;
+
+        let older_button = Button::new(rect.bottom_left(), "[Up] Older");
+        let newer_button =
+            Button::new(rect.bottom_left(), "[Down] Newer").align_center(rect.width());
+        let back_button = Button::new(rect.bottom_right(), "[Esc] Back").align_right();
+
+        let button_rect = metrics.button_rect(&older_button);
+        if button_rect.contains(state.mouse.tile_pos) {
+            action_under_mouse = Some(Action::Older);
+            rect_under_mouse = Some(button_rect);
+        }
+
+        let button_rect = metrics.button_rect(&newer_button);
+        if button_rect.contains(state.mouse.tile_pos) {
+            action_under_mouse = Some(Action::Newer);
+            rect_under_mouse = Some(button_rect);
+        }
+
+        let button_rect = metrics.button_rect(&back_button);
+        if button_rect.contains(state.mouse.tile_pos) {
+            action_under_mouse = Some(Action::Back);
+            rect_under_mouse = Some(button_rect);
+        }
+
+        Layout {
+            window_rect,
+            rect,
+            older_button,
+            newer_button,
+            back_button,
+            action_under_mouse,
+            rect_under_mouse,
+        }
+    }
+
+    pub fn render(&self, state: &State, metrics: &dyn TextMetrics, display: &mut Display) {
+        let layout = self.layout(state, metrics);
+        let rect = layout.rect;
+
+        display.draw_rectangle(layout.window_rect, color::window_edge);
+
+        display.draw_rectangle(
+            Rectangle::new(
+                layout.window_rect.top_left() + (1, 1),
+                layout.window_rect.bottom_right() - (1, 1),
+            ),
+            color::window_background,
+        );
+
+        display.draw_text(
+            rect.top_left() + (0, 1),
+            "Messages",
+            color::gui_text,
+            TextOptions::align_center(rect.width()),
+        );
+
+        if state.messages.is_empty() {
+            display.draw_text(
+                rect.top_left() + (0, 3),
+                "Nothing has happened yet.",
+                color::gui_text,
+                TextOptions::align_left(),
+            );
+        } else {
+            display.draw_text(
+                rect.top_left() + (0, 2),
+                "Turn",
+                color::old_message,
+                TextOptions::align_left(),
+            );
+        }
+
+        // NOTE: fill the window from the bottom up, starting with the
+        // newest message we didn't scroll past.
+        let turn_width = 6;
+        let options = TextOptions {
+            wrap: true,
+            width: rect.width() - turn_width,
+            ..Default::default()
+        };
+        let messages = state.messages.messages();
+        let top = rect.top_left().y + 3;
+        let mut y = rect.bottom_right().y - 1;
+        let newest = messages.len().saturating_sub(state.message_log_scroll);
+        for message in messages[..newest].iter().rev() {
+            let text = message.event.to_string();
+            let height = metrics.get_text_height(&text, options);
+            if y - height < top {
+                break;
+            }
+            y -= height;
+            display.draw_text(
+                Point::new(rect.top_left().x, y),
+                &message.turn.to_string(),
+                color::old_message,
+                TextOptions::align_left(),
+            );
+            display.draw_text(
+                Point::new(rect.top_left().x + turn_width, y),
+                &text,
+                color::gui_text,
+                options,
+            );
+        }
+
+        if let Some(rect) = layout.rect_under_mouse {
+            display.draw_rectangle(rect, color::menu_highlight);
+        }
+
+        display.draw_button(&layout.older_button);
+        display.draw_button(&layout.newer_button);
+        display.draw_button(&layout.back_button);
+    }
+
+    pub fn hovered(&self, state: &State, metrics: &dyn TextMetrics) -> Option<Action> {
+        self.layout(state, metrics).action_under_mouse
+    }
+}
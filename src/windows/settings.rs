@@ -1,6 +1,7 @@
 use crate::{
     color,
     engine::{Display, Settings, TextMetrics},
+    palette::Palette,
     point::Point,
     rect::Rectangle,
     state::State,
@@ -11,6 +12,13 @@ pub enum Action {
     Fullscreen,
     Window,
     FontSize(i32),
+    Permadeath,
+    KeepSaves,
+    Backend(&'static str),
+    Palette(Palette),
+    RecordReplays,
+    NoReplays,
+    Controls,
     Back,
 }
 
@@ -22,6 +30,13 @@ struct Layout {
     fullscreen_button: Button,
     window_button: Button,
     font_size_options: Vec<(i32, Button)>,
+    permadeath_button: Button,
+    keep_saves_button: Button,
+    backend_options: Vec<(&'static str, Button)>,
+    palette_options: Vec<(Palette, Button)>,
+    record_replays_button: Button,
+    no_replays_button: Button,
+    controls_button: Button,
     back_button: Button,
 }
 
@@ -47,6 +62,7 @@ impl Window {
         let window_button = Button::new(rect.top_left() + (20, 3), "[W]indow");
         let back_button =
             Button::new(rect.bottom_left() + (0, -1), "[Esc] Back").align_center(rect.width());
+        let controls_button = Button::new(rect.bottom_left() + (0, -1), "[C]ontrols");
 
         let button_rect = metrics.button_rect(&fullscreen_button);
         if button_rect.contains(state.mouse.tile_pos) {
@@ -60,6 +76,12 @@ impl Window {
             rect_under_mouse = Some(button_rect);
         }
 
+        let button_rect = metrics.button_rect(&controls_button);
+        if button_rect.contains(state.mouse.tile_pos) {
+            option_under_mouse = Some(Action::Controls);
+            rect_under_mouse = Some(button_rect);
+        }
+
         let button_rect = metrics.button_rect(&back_button);
         if button_rect.contains(state.mouse.tile_pos) {
             option_under_mouse = Some(Action::Back);
@@ -93,6 +115,82 @@ impl Window {
             }
         }
 
+        let saves_y = 8 + crate::engine::AVAILABLE_FONT_SIZES.len() as i32;
+        let permadeath_button = Button::new(
+            rect.top_left() + (rect.width() / 2 - 1, saves_y),
+            "[D]elete on load",
+        )
+        .align_right();
+        let keep_saves_button =
+            Button::new(rect.top_left() + (rect.width() / 2 + 1, saves_y), "[K]eep");
+
+        let button_rect = metrics.button_rect(&permadeath_button);
+        if button_rect.contains(state.mouse.tile_pos) {
+            option_under_mouse = Some(Action::Permadeath);
+            rect_under_mouse = Some(button_rect);
+        }
+
+        let button_rect = metrics.button_rect(&keep_saves_button);
+        if button_rect.contains(state.mouse.tile_pos) {
+            option_under_mouse = Some(Action::KeepSaves);
+            rect_under_mouse = Some(button_rect);
+        }
+
+        let backend_options = ui::button_row(
+            rect,
+            saves_y + 3,
+            crate::settings::graphics_backends()
+                .map(|backend| (backend, backend.to_string()))
+                .collect(),
+            metrics,
+        );
+
+        for &(backend, ref button) in &backend_options {
+            let button_rect = metrics.button_rect(button);
+            if button_rect.contains(state.mouse.tile_pos) {
+                option_under_mouse = Some(Action::Backend(backend));
+                rect_under_mouse = Some(button_rect);
+            }
+        }
+
+        let palette_options = ui::button_row(
+            rect,
+            saves_y + 6,
+            Palette::all()
+                .map(|palette| (palette, palette.name().to_string()))
+                .collect(),
+            metrics,
+        );
+
+        for &(palette, ref button) in &palette_options {
+            let button_rect = metrics.button_rect(button);
+            if button_rect.contains(state.mouse.tile_pos) {
+                option_under_mouse = Some(Action::Palette(palette));
+                rect_under_mouse = Some(button_rect);
+            }
+        }
+
+        let replays_y = saves_y + 9;
+        let record_replays_button = Button::new(
+            rect.top_left() + (rect.width() / 2 - 1, replays_y),
+            "[R]ecord",
+        )
+        .align_right();
+        let no_replays_button =
+            Button::new(rect.top_left() + (rect.width() / 2 + 1, replays_y), "[O]ff");
+
+        let button_rect = metrics.button_rect(&record_replays_button);
+        if button_rect.contains(state.mouse.tile_pos) {
+            option_under_mouse = Some(Action::RecordReplays);
+            rect_under_mouse = Some(button_rect);
+        }
+
+        let button_rect = metrics.button_rect(&no_replays_button);
+        if button_rect.contains(state.mouse.tile_pos) {
+            option_under_mouse = Some(Action::NoReplays);
+            rect_under_mouse = Some(button_rect);
+        }
+
         Layout {
             window_rect,
             rect,
@@ -101,6 +199,13 @@ impl Window {
             fullscreen_button,
             window_button,
             font_size_options,
+            permadeath_button,
+            keep_saves_button,
+            backend_options,
+            palette_options,
+            record_replays_button,
+            no_replays_button,
+            controls_button,
             back_button,
         }
     }
@@ -137,9 +242,17 @@ impl Window {
             Centered(&font_size),
             EmptySpace(crate::engine::AVAILABLE_FONT_SIZES.len() as i32),
             Empty,
-            // TODO: read values from: `crate::engine::AVAILABLE_BACKENDS`
-            Centered("Graphics backend:"),
-            Centered("Glutin / SDL"),
+            Centered("Saved games:"),
+            Centered("/"), // Delete on load / Keep
+            Empty,
+            Centered("Graphics backend (after restart, [B] to switch):"),
+            Empty, // Backends
+            Empty,
+            Centered("Colour palette ([P] to switch):"),
+            Empty, // Palettes
+            Empty,
+            Centered("Replays:"),
+            Centered("/"), // Record / Off
             Empty,
             Empty, // Back
         ];
@@ -172,6 +285,51 @@ impl Window {
             display.draw_button(button)
         }
 
+        // Highlight the active saved games option
+        {
+            let rect = if settings.permadeath {
+                metrics.button_rect(&layout.permadeath_button)
+            } else {
+                metrics.button_rect(&layout.keep_saves_button)
+            };
+            display.draw_rectangle(rect, color::dim_background);
+        }
+
+        display.draw_button(&layout.permadeath_button);
+        display.draw_button(&layout.keep_saves_button);
+
+        for (backend, button) in &layout.backend_options {
+            // Highlight the active backend
+            if *backend == settings.backend {
+                let rect = metrics.button_rect(button);
+                display.draw_rectangle(rect, color::dim_background);
+            }
+            display.draw_button(button)
+        }
+
+        for (palette, button) in &layout.palette_options {
+            // Highlight the active palette
+            if *palette == settings.palette {
+                let rect = metrics.button_rect(button);
+                display.draw_rectangle(rect, color::dim_background);
+            }
+            display.draw_button(button)
+        }
+
+        // Highlight the active replay recording option
+        {
+            let rect = if settings.record_replays {
+                metrics.button_rect(&layout.record_replays_button)
+            } else {
+                metrics.button_rect(&layout.no_replays_button)
+            };
+            display.draw_rectangle(rect, color::dim_background);
+        }
+
+        display.draw_button(&layout.record_replays_button);
+        display.draw_button(&layout.no_replays_button);
+
+        display.draw_button(&layout.controls_button);
         display.draw_button(&layout.back_button);
     }
 
@@ -1,8 +1,7 @@
 use crate::color;
-use crate::engine::{Display, TextMetrics};
-use crate::game;
+use crate::engine::{Display, TextMetrics, TextOptions};
 use crate::graphics;
-use crate::item;
+use crate::keymap::{self, Keymap};
 use crate::player::Mind;
 use crate::point::Point;
 use crate::rect::Rectangle;
@@ -11,17 +10,41 @@ use crate::ui::Button;
 use crate::util;
 
 use std::borrow::Cow;
+use std::cmp;
 use std::collections::HashMap;
 use std::time::Duration;
 
+/// The number of lines showing the latest messages.
+const MESSAGE_LINES: i32 = 4;
+
 pub enum Action {
     MainMenu,
     Help,
-    UseFood,
-    UseDose,
-    UseCardinalDose,
-    UseDiagonalDose,
-    UseStrongDose,
+    Look,
+    MessageLog,
+    Drop,
+    Throw,
+    /// Use the item with this id.
+    Use(String),
+}
+
+impl Action {
+    /// The id of the item the player clicked on.
+    pub fn item(&self) -> Option<&str> {
+        match self {
+            Action::Use(id) => Some(id),
+            _ => None,
+        }
+    }
+}
+
+/// The button `text` with the first key bound to the `action`, e.g.
+/// `[M] Messages`.
+fn label(keymap: &Keymap, action: &keymap::Action, text: &str) -> String {
+    match keymap.keys(action).first() {
+        Some(key) => format!("[{}] {}", key, text),
+        None => text.into(),
+    }
 }
 
 struct Layout {
@@ -33,9 +56,14 @@ struct Layout {
     progress_bar_pos: Point,
     stats_pos: Point,
     inventory_pos: Point,
-    inventory: HashMap<item::Kind, i32>,
+    inventory: HashMap<String, i32>,
     main_menu_button: Button,
     help_button: Button,
+    look_button: Button,
+    message_log_button: Button,
+    drop_button: Button,
+    throw_button: Button,
+    messages_rect: Rectangle,
     action_under_mouse: Option<Action>,
     rect_under_mouse: Option<Rectangle>,
 }
@@ -43,7 +71,7 @@ struct Layout {
 pub struct Window;
 
 impl Window {
-    fn layout(&self, state: &State, metrics: &dyn TextMetrics) -> Layout {
+    fn layout(&self, state: &State, keymap: &Keymap, metrics: &dyn TextMetrics) -> Layout {
         let x = state.map_size.x;
         let fg = color::gui_text;
         let bg = color::dim_background;
@@ -58,26 +86,21 @@ impl Window {
 
         let mut inventory = HashMap::new();
         for item in &state.player.inventory {
-            let count = inventory.entry(item.kind).or_insert(0);
+            let count = inventory.entry(item.id.clone()).or_insert(0);
             *count += 1;
         }
 
+        // NOTE: the items are shown in the order they're defined in
         let mut item_y_offset = 0;
-        for kind in item::Kind::iter() {
-            if inventory.get(&kind).is_some() {
+        for definition in &state.world.item_definitions {
+            if inventory.get(&definition.id).is_some() {
                 let rect = Rectangle::from_point_and_size(
                     inventory_pos + Point::new(-1, item_y_offset + 1),
                     Point::new(state.panel_width, 1),
                 );
                 if rect.contains(state.mouse.tile_pos) {
                     rect_under_mouse = Some(rect);
-                    action_under_mouse = Some(match kind {
-                        item::Kind::Food => Action::UseFood,
-                        item::Kind::Dose => Action::UseDose,
-                        item::Kind::CardinalDose => Action::UseCardinalDose,
-                        item::Kind::DiagonalDose => Action::UseDiagonalDose,
-                        item::Kind::StrongDose => Action::UseStrongDose,
-                    });
+                    action_under_mouse = Some(Action::Use(definition.id.clone()));
                 }
                 item_y_offset += 1;
             }
@@ -91,7 +114,33 @@ impl Window {
 
         let help_button = Button::new(Point::new(x + 1, bottom), "[?] Help").color(fg);
 
-        bottom -= 1;
+        bottom -= 2;
+
+        let look_label = label(keymap, &keymap::Action::Look, "Look");
+        let look_button = Button::new(Point::new(x + 1, bottom), &look_label).color(fg);
+
+        bottom -= 2;
+
+        let message_log_label = label(keymap, &keymap::Action::MessageLog, "Messages");
+        let message_log_button =
+            Button::new(Point::new(x + 1, bottom), &message_log_label).color(fg);
+
+        bottom -= 2;
+
+        let drop_label = label(keymap, &keymap::Action::Drop, "Drop");
+        let drop_button = Button::new(Point::new(x + 1, bottom), &drop_label).color(fg);
+        let throw_x = metrics.button_rect(&drop_button).right() + 2;
+        let throw_label = label(keymap, &keymap::Action::Throw, "Throw");
+        let throw_button = Button::new(Point::new(throw_x, bottom), &throw_label).color(fg);
+
+        bottom -= 2;
+
+        let messages_rect = Rectangle::new(
+            Point::new(x + 1, bottom - MESSAGE_LINES + 1),
+            Point::new(x + state.panel_width - 2, bottom),
+        );
+
+        bottom -= MESSAGE_LINES + 1;
 
         let main_menu_rect = metrics.button_rect(&main_menu_button);
         if main_menu_rect.contains(state.mouse.tile_pos) {
@@ -105,6 +154,30 @@ impl Window {
             rect_under_mouse = Some(help_rect);
         }
 
+        let look_rect = metrics.button_rect(&look_button);
+        if look_rect.contains(state.mouse.tile_pos) {
+            action_under_mouse = Some(Action::Look);
+            rect_under_mouse = Some(look_rect);
+        }
+
+        let message_log_rect = metrics.button_rect(&message_log_button);
+        if message_log_rect.contains(state.mouse.tile_pos) {
+            action_under_mouse = Some(Action::MessageLog);
+            rect_under_mouse = Some(message_log_rect);
+        }
+
+        let drop_rect = metrics.button_rect(&drop_button);
+        if drop_rect.contains(state.mouse.tile_pos) {
+            action_under_mouse = Some(Action::Drop);
+            rect_under_mouse = Some(drop_rect);
+        }
+
+        let throw_rect = metrics.button_rect(&throw_button);
+        if throw_rect.contains(state.mouse.tile_pos) {
+            action_under_mouse = Some(Action::Throw);
+            rect_under_mouse = Some(throw_rect);
+        }
+
         Layout {
             x,
             fg,
@@ -118,23 +191,34 @@ impl Window {
             rect_under_mouse,
             main_menu_button,
             help_button,
+            look_button,
+            message_log_button,
+            drop_button,
+            throw_button,
+            messages_rect,
             bottom,
         }
     }
 
-    pub fn hovered(&self, state: &State, metrics: &dyn TextMetrics) -> Option<Action> {
-        self.layout(state, metrics).action_under_mouse
+    pub fn hovered(
+        &self,
+        state: &State,
+        keymap: &Keymap,
+        metrics: &dyn TextMetrics,
+    ) -> Option<Action> {
+        self.layout(state, keymap, metrics).action_under_mouse
     }
 
     pub fn render(
         &self,
         state: &State,
+        keymap: &Keymap,
         metrics: &dyn TextMetrics,
         dt: Duration,
         fps: i32,
         display: &mut Display,
     ) {
-        let layout = self.layout(state, metrics);
+        let layout = self.layout(state, keymap, metrics);
         let x = layout.x;
         let fg = layout.fg;
         let bg = layout.bg;
@@ -201,11 +285,14 @@ impl Window {
         if !layout.inventory.is_empty() {
             display.draw_button(&Button::new(layout.inventory_pos, "Inventory:").color(fg));
 
-            for kind in item::Kind::iter() {
-                if let Some(count) = layout.inventory.get(&kind) {
-                    lines.push(
-                        format!("[{}] {:?}: {}", game::inventory_key(kind), kind, count).into(),
-                    );
+            for definition in &state.world.item_definitions {
+                if let Some(count) = layout.inventory.get(&definition.id) {
+                    let name = &definition.name;
+                    let line = match definition.keys.first() {
+                        Some(key) => format!("[{}] {}: {}", key, name, count),
+                        None => format!("{}: {}", name, count),
+                    };
+                    lines.push(line.into());
                 }
             }
         }
@@ -238,11 +325,8 @@ impl Window {
         }
 
         if player.alive() {
-            if player.stun.to_int() > 0 {
-                lines.push(format!("Stunned({})", player.stun.to_int()).into());
-            }
-            if player.panic.to_int() > 0 {
-                lines.push(format!("Panicking({})", player.panic.to_int()).into());
+            for effect in player.status.iter() {
+                lines.push(format!("{}({})", effect.kind, effect.turns).into());
             }
         }
 
@@ -279,6 +363,7 @@ impl Window {
             );
         }
 
+        let lines_bottom = layout.inventory_pos.y + lines.len() as i32;
         for (y, line) in lines.into_iter().enumerate() {
             display.draw_text(
                 Point {
@@ -293,6 +378,37 @@ impl Window {
 
         display.draw_button(&layout.main_menu_button);
         display.draw_button(&layout.help_button);
+        display.draw_button(&layout.look_button);
+        display.draw_button(&layout.message_log_button);
+        display.draw_button(&layout.drop_button);
+        display.draw_button(&layout.throw_button);
+
+        // NOTE: show as many of the latest messages as fit, the
+        // newest one at the bottom. Don't cover the lines above.
+        let rect = layout.messages_rect;
+        let top = cmp::max(rect.top_left().y, lines_bottom + 1);
+        let options = TextOptions {
+            wrap: true,
+            width: rect.width(),
+            ..Default::default()
+        };
+        let mut y = rect.bottom_right().y + 1;
+        for message in state.messages.recent(MESSAGE_LINES as usize).iter().rev() {
+            let text = message.event.to_string();
+            let height = metrics.get_text_height(&text, options);
+            if y - height < top {
+                break;
+            }
+            y -= height;
+            // NOTE: the turn counter moves on right after the
+            // messages are logged
+            let color = if state.turn - message.turn <= 1 {
+                fg
+            } else {
+                color::old_message
+            };
+            display.draw_text(Point::new(rect.top_left().x, y), &text, color, options);
+        }
 
         if state.cheating {
             display.draw_text(
@@ -0,0 +1,60 @@
+use crate::{
+    color,
+    engine::{Display, TextMetrics},
+    formula, game,
+    point::Line,
+    state::State,
+    ui,
+};
+
+pub struct Window;
+
+impl Window {
+    pub fn render(&self, state: &State, metrics: &dyn TextMetrics, display: &mut Display) {
+        let screen_left_top_corner = state.screen_position_in_world - (state.map_size / 2);
+        let player_pos = state.player.pos;
+        let target = state.cursor;
+        let can_throw = game::can_throw(&state.world, player_pos, target);
+
+        for pos in Line::new(player_pos, target).skip(1) {
+            display.set_background(pos - screen_left_top_corner, color::dim_background);
+        }
+        if can_throw {
+            display.set_background(target - screen_left_top_corner, color::menu_highlight);
+        }
+
+        let status = if can_throw {
+            "It will explode here.".to_string()
+        } else if target == player_pos {
+            "Pick the tile to throw it at.".into()
+        } else if player_pos.tile_distance(target) > formula::THROW_RANGE {
+            format!(
+                "Too far, you can throw up to {} tiles.",
+                formula::THROW_RANGE
+            )
+        } else {
+            "There's something in the way.".into()
+        };
+        let name = state
+            .throw_item
+            .as_ref()
+            .map_or("Nothing", |id| &state.world.item_definition(id).name);
+        let lines = vec![
+            format!("Throw: {}", name),
+            String::new(),
+            status,
+            String::new(),
+            "Press another dose's key to switch.".into(),
+            String::new(),
+            "[Enter] Throw".into(),
+            "[Esc] Cancel".into(),
+        ];
+        ui::render_side_panel(
+            &lines,
+            state.map_size,
+            target - screen_left_top_corner,
+            metrics,
+            display,
+        );
+    }
+}
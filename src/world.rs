@@ -1,10 +1,10 @@
 use crate::{
     blocker::Blocker,
     formula,
-    generators::{self, GeneratedWorld},
-    item::Item,
+    generators::{ChunkInfo, GeneratedWorld, Generator, WorldGenerator},
+    item::{self, Item},
     level::{self, Cell, Level},
-    monster::Monster,
+    monster::{self, Monster},
     player::PlayerInfo,
     point::{CircularArea, Point, SquareArea},
     random::{self, Random},
@@ -32,7 +32,15 @@ pub struct Chunk {
 }
 
 impl Chunk {
-    fn new(world_seed: u32, position: ChunkPosition, size: i32, player_position: Point) -> Self {
+    fn new(
+        world_seed: u32,
+        position: ChunkPosition,
+        size: i32,
+        player_position: Point,
+        generator: &dyn WorldGenerator,
+        monster_definitions: &[monster::Definition],
+        item_definitions: &[item::Definition],
+    ) -> Self {
         use std::num::Wrapping;
         let pos = position.position;
         // NOTE: `x` and `y` overflow on negative values here, but all
@@ -53,12 +61,15 @@ impl Chunk {
         };
 
         let mut throwavay_rng = chunk.rng.clone();
-        let generated_data = generators::forrest::generate(
-            &mut chunk.rng,
-            &mut throwavay_rng,
-            chunk.level.size(),
+        let info = ChunkInfo {
+            world_seed,
+            position: pos,
+            size: chunk.level.size(),
             player_position,
-        );
+            monster_definitions,
+            item_definitions,
+        };
+        let generated_data = generator.generate(&info, &mut chunk.rng, &mut throwavay_rng);
 
         chunk.populate(generated_data);
 
@@ -81,7 +92,7 @@ impl Chunk {
             self.add_monster(monster);
             assert!(!self.level.walkable(pos, Blocker::WALL | Blocker::MONSTER));
         }
-        for &(pos, item) in &items {
+        for (pos, item) in items {
             let pos = self.level.level_position(pos);
             assert!(self.level.walkable(pos, Blocker::WALL));
             self.level.add_item(pos, item);
@@ -154,6 +165,12 @@ pub struct World {
     max_half_size: i32,
     chunk_size: i32,
     chunks: HashMap<ChunkPosition, Chunk>,
+    pub monster_definitions: Vec<monster::Definition>,
+    pub item_definitions: Vec<item::Definition>,
+    pub generator: Generator,
+    /// Goes up whenever the monsters, the items or the tiles change.
+    #[serde(skip)]
+    revision: u32,
 }
 
 impl World {
@@ -163,6 +180,9 @@ impl World {
         dimension: i32,
         chunk_size: i32,
         player_info: PlayerInfo,
+        generator: Generator,
+        monster_definitions: Vec<monster::Definition>,
+        item_definitions: Vec<item::Definition>,
     ) -> Self {
         assert!(dimension > 0);
         assert!(chunk_size > 0);
@@ -174,6 +194,10 @@ impl World {
             max_half_size: dimension / 2,
             chunk_size,
             chunks: HashMap::new(),
+            monster_definitions,
+            item_definitions,
+            generator,
+            revision: 0,
         };
 
         // TODO: I don't think this code belongs in World. Move it
@@ -203,14 +227,9 @@ impl World {
 
         // Remove monsters from the starting area
         for pos in easy_area.points() {
-            let remove_monster = self.monster_on_pos(pos).map_or(false, |m| {
-                use crate::monster::Kind::*;
-                let easy_monster = match m.kind {
-                    Shadows | Voices => false,
-                    Hunger | Anxiety | Depression | Npc => true,
-                    Signpost => true, // NOTE: should never be generated on its own
-                };
-                safe_area.contains(pos) || easy_monster
+            let monster_id = self.monster_on_pos(pos).map(|m| m.id.clone());
+            let remove_monster = monster_id.map_or(false, |id| {
+                safe_area.contains(pos) || !self.monster_definition(&id).near_start
             });
             if remove_monster {
                 self.remove_monster(pos)
@@ -226,8 +245,8 @@ impl World {
         // Clear any doses whos irresistible area touches the player's
         // position.
         {
-            let resist_radius =
-                formula::player_resist_radius(formula::DOSE_PREFAB.irresistible, player_info.will);
+            let dose = self.item_definition_of_kind(item::Kind::Dose);
+            let resist_radius = formula::player_resist_radius(dose.irresistible, player_info.will);
             let resist_area = Rectangle::center(player_info.pos, Point::from_i32(resist_radius));
             for point in resist_area.points() {
                 if let Some(cell) = self.cell_mut(point) {
@@ -240,18 +259,15 @@ impl World {
             }
         }
 
+        let lethal_ids = self
+            .item_definitions
+            .iter()
+            .filter(|definition| !definition.near_start)
+            .map(|definition| definition.id.clone())
+            .collect::<Vec<_>>();
         for pos in no_lethal_dose_area.points() {
             if let Some(cell) = self.cell_mut(pos) {
-                for index in (0..cell.items.len()).rev() {
-                    use crate::item::Kind::*;
-                    let lethal_dose = match cell.items[index].kind {
-                        Food | Dose => false,
-                        StrongDose | CardinalDose | DiagonalDose => true,
-                    };
-                    if lethal_dose {
-                        cell.items.remove(index);
-                    }
-                }
+                cell.items.retain(|item| !lethal_ids.contains(&item.id));
             }
         }
 
@@ -272,7 +288,7 @@ impl World {
                     continue;
                 }
 
-                let dose = formula::DOSE_PREFAB;
+                let dose = self.item_definition_of_kind(item::Kind::Dose).item();
 
                 let resist_radius =
                     formula::player_resist_radius(dose.irresistible, player_info.will);
@@ -310,7 +326,7 @@ impl World {
             };
             let pos = player_info.pos + offset;
             if self.walkable(pos, Blocker::WALL, player_info.pos) {
-                let food = formula::FOOD_PREFAB;
+                let food = self.item_definition_of_kind(item::Kind::Food).item();
                 if let Some(chunk) = self.chunk_mut(pos) {
                     let level_position = chunk.level_position(pos);
                     if chunk.level.cell(level_position).items.is_empty() {
@@ -375,11 +391,56 @@ impl World {
 
         let seed = self.seed;
         let chunk_size = self.chunk_size;
+        let monster_definitions = &self.monster_definitions;
+        let item_definitions = &self.item_definitions;
+        let generator = self.generator;
         // TODO: figure out how to generate the starting chunks so the
         // player has some doses and food and no monsters.
-        self.chunks
-            .entry(chunk_position)
-            .or_insert_with(|| Chunk::new(seed, chunk_position, chunk_size, (0, 0).into()));
+        self.chunks.entry(chunk_position).or_insert_with(|| {
+            Chunk::new(
+                seed,
+                chunk_position,
+                chunk_size,
+                (0, 0).into(),
+                generator.world_generator(),
+                monster_definitions,
+                item_definitions,
+            )
+        });
+    }
+
+    /// The definition of the monster with the given `id`.
+    pub fn monster_definition(&self, id: &str) -> &monster::Definition {
+        self.monster_definitions
+            .iter()
+            .find(|definition| definition.id == id)
+            .unwrap_or_else(|| panic!("The `{}` monster is not defined.", id))
+    }
+
+    /// The first definition of the monster `kind`. The game uses it
+    /// to place the NPCs and signposts.
+    pub fn monster_definition_of_kind(&self, kind: monster::Kind) -> &monster::Definition {
+        self.monster_definitions
+            .iter()
+            .find(|definition| definition.kind == kind)
+            .unwrap_or_else(|| panic!("No `{:?}` monster is defined.", kind))
+    }
+
+    /// The definition of the item with the given `id`.
+    pub fn item_definition(&self, id: &str) -> &item::Definition {
+        self.item_definitions
+            .iter()
+            .find(|definition| definition.id == id)
+            .unwrap_or_else(|| panic!("The `{}` item is not defined.", id))
+    }
+
+    /// The first definition of the item `kind` that can be near the
+    /// start. The game places it there.
+    pub fn item_definition_of_kind(&self, kind: item::Kind) -> &item::Definition {
+        self.item_definitions
+            .iter()
+            .find(|definition| definition.kind == kind && definition.near_start)
+            .unwrap_or_else(|| panic!("No `{:?}` item can be near the start.", kind))
     }
 
     pub fn cell(&self, world_pos: Point) -> Option<&Cell> {
@@ -393,6 +454,13 @@ impl World {
     }
 
     pub fn cell_mut(&mut self, world_pos: Point) -> Option<&mut Cell> {
+        self.revision = self.revision.wrapping_add(1);
+        self.cell_mut_unchanged(world_pos)
+    }
+
+    /// Like `cell_mut` but for changes that don't count as the world
+    /// changing (e.g. exploring it).
+    fn cell_mut_unchanged(&mut self, world_pos: Point) -> Option<&mut Cell> {
         let chunk = self.chunk_mut(world_pos);
         // NOTE: the positions within a chunk/level start from zero so
         // we need to de-offset them with the chunk position.
@@ -402,6 +470,12 @@ impl World {
         })
     }
 
+    /// A number that changes every time the monsters, the items or
+    /// the tiles in the world change.
+    pub fn revision(&self) -> u32 {
+        self.revision
+    }
+
     /// Check whether the given position is within the bounds of the World.
     ///
     /// While the world should be "technically infinite", we well have
@@ -434,6 +508,14 @@ impl World {
         self.within_bounds(pos) && level_cell_walkable
     }
 
+    /// How many `formula::AP_FRACTIONS` does moving onto the
+    /// position cost.
+    pub fn move_cost(&self, pos: Point) -> i32 {
+        self.cell(pos).map_or(formula::AP_FRACTIONS, |cell| {
+            formula::move_cost(cell.tile.kind)
+        })
+    }
+
     /// Pick up the top `Item` stacked on the tile. If the position is
     /// not withing bounds, nothing happens.
     pub fn pickup_item(&mut self, pos: Point) -> Option<Item> {
@@ -463,6 +545,22 @@ impl World {
         }
     }
 
+    /// Return the `MonsterId` of the monster at the given position.
+    ///
+    /// NOTE: the ID changes when the monster moves to another chunk.
+    pub fn monster_id(&self, world_pos: Point) -> Option<MonsterId> {
+        let chunk = self.chunk(world_pos)?;
+        let monster_index = chunk
+            .level
+            .monster_on_pos(chunk.level_position(world_pos))?;
+        Some(MonsterId {
+            chunk_position: ChunkPosition {
+                position: chunk.position,
+            },
+            monster_index,
+        })
+    }
+
     /// Return a reference to a `Monster` given its `MonsterId`.
     pub fn monster(&self, id: MonsterId) -> Option<&Monster> {
         self.chunk(id.chunk_position.position)
@@ -487,6 +585,7 @@ impl World {
         if monster_position == destination {
             return;
         }
+        self.revision = self.revision.wrapping_add(1);
         let blocker = Blocker::PLAYER | Blocker::MONSTER | Blocker::WALL;
         assert!(
             self.walkable(destination, blocker, player_position),
@@ -553,6 +652,7 @@ impl World {
             // monsters from the map.
             if let Some(index) = index {
                 chunk.monsters[index].dead = true;
+                self.revision = self.revision.wrapping_add(1);
             }
         }
     }
@@ -571,7 +671,7 @@ impl World {
     pub fn explore(&mut self, centre: Point, radius: i32) {
         for pos in CircularArea::new(centre, radius) {
             if self.within_bounds(pos) {
-                if let Some(cell) = self.cell_mut(pos) {
+                if let Some(cell) = self.cell_mut_unchanged(pos) {
                     cell.explored = true;
                 }
             }
@@ -582,7 +682,7 @@ impl World {
     pub fn always_visible(&mut self, centre: Point, radius: i32) {
         for pos in CircularArea::new(centre, radius) {
             if self.within_bounds(pos) {
-                if let Some(cell) = self.cell_mut(pos) {
+                if let Some(cell) = self.cell_mut_unchanged(pos) {
                     cell.explored = true;
                     cell.always_visible = true;
                 }
@@ -605,7 +705,7 @@ impl World {
                 self.cell(pos)
                     .map_or(vec![].iter(), |cell| cell.items.iter())
                     .filter(|i| i.is_dose())
-                    .map(|&item| (pos, item)),
+                    .map(|item| (pos, item.clone())),
             );
         }
 
@@ -613,7 +713,7 @@ impl World {
             let mut result = dose;
             for d in &doses {
                 if centre.tile_distance(d.0) < centre.tile_distance(result.0) {
-                    result = *d;
+                    result = d.clone();
                 }
             }
             result
@@ -717,6 +817,16 @@ impl World {
             .filter(move |m| m.alive() && area.contains(m.position))
     }
 
+    /// Return an iterator over all monsters in the world.
+    ///
+    /// NOTE: The order of the monsters is not specified.
+    pub fn all_monsters(&self) -> impl Iterator<Item = &Monster> {
+        self.chunks
+            .values()
+            .flat_map(Chunk::monsters)
+            .filter(|m| m.alive())
+    }
+
     pub fn positions_of_all_chunks(&self) -> Vec<Point> {
         self.chunks
             .keys()
@@ -724,3 +834,62 @@ impl World {
             .collect()
     }
 }
+
+/// The world as the 1.0.0 release saved it. Only used to read its
+/// saved games.
+pub mod legacy {
+    use super::ChunkPosition;
+    use crate::{level::legacy::Level, monster::legacy::Monster, point::Point, random::Random};
+
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Chunk {
+        position: Point,
+        rng: Random,
+        level: Level,
+        monsters: Vec<Monster>,
+    }
+
+    impl From<Chunk> for super::Chunk {
+        fn from(old: Chunk) -> Self {
+            super::Chunk {
+                position: old.position,
+                rng: old.rng,
+                level: old.level.into(),
+                monsters: old.monsters.into_iter().map(Into::into).collect(),
+            }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct World {
+        seed: u32,
+        max_half_size: i32,
+        chunk_size: i32,
+        chunks: HashMap<ChunkPosition, Chunk>,
+    }
+
+    impl From<World> for super::World {
+        fn from(old: World) -> Self {
+            // NOTE: the definitions are filled in by the caller. The
+            // 1.0.0 release generated the forest everywhere.
+            super::World {
+                seed: old.seed,
+                max_half_size: old.max_half_size,
+                chunk_size: old.chunk_size,
+                chunks: old
+                    .chunks
+                    .into_iter()
+                    .map(|(pos, chunk)| (pos, chunk.into()))
+                    .collect(),
+                monster_definitions: vec![],
+                item_definitions: vec![],
+                generator: Default::default(),
+                revision: 0,
+            }
+        }
+    }
+}